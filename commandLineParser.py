@@ -1,6 +1,10 @@
 from __future__ import unicode_literals
 
+import json
+import os
 import re
+import sys
+import threading
 
 try:
 	from typing import Optional
@@ -128,7 +132,39 @@ def tryGetOverallStatus(overallStatusString):
 
 	return SeventhModStatusUpdate(overallPercentage=int(match.group(1)), currentTask=match.group(2))
 
-# Print a status update which will be recognized by the command line parser
-def printSeventhModStatusUpdate(overallPercentage, currentTask):
-	# type: (int, str) -> None
+# Print a status update which will be recognized by the command line parser. The launcher is also sent the
+# progress - it only has room for a short description, so if currentTask is long, also give a short phase.
+def printSeventhModStatusUpdate(overallPercentage, currentTask, phase=None, currentFile=None):
+	# type: (int, str, Optional[str], Optional[str]) -> None
 	print("<<< Status: {}% {} >>>".format(overallPercentage, currentTask))
+	printLauncherProgress(phase if phase is not None else currentTask, overallPercentage, currentFile)
+
+# The launcher (install_loader) shows the install progress in its own window, using status lines written to
+# stderr. They're only sent if the launcher started the installer. The status lines are described in
+# install_loader/src/install_status.rs
+LAUNCHER_STATUS_PREFIX = "<<<LAUNCHER_STATUS>>>"
+_launcherStatusEnabled = os.environ.get("INSTALL_LOADER_STATUS_LINES") == "1"
+_launcherStatusLock = threading.Lock()
+
+def _printLauncherStatus(status):
+	# type: (dict) -> None
+	if not _launcherStatusEnabled:
+		return
+
+	# The real stderr is used, as sys.stderr is redirected to the log. Each line is written with a single call while
+	# holding the lock, so status lines from different threads aren't mixed together.
+	line = LAUNCHER_STATUS_PREFIX + json.dumps(status) + "\n"
+	with _launcherStatusLock:
+		try:
+			sys.__stderr__.write(line)
+			sys.__stderr__.flush()
+		except Exception:
+			pass
+
+def printLauncherProgress(phase, percentage=None, currentFile=None):
+	# type: (str, Optional[int], Optional[str]) -> None
+	_printLauncherStatus({'type': 'progress', 'phase': phase, 'percentage': percentage, 'currentFile': currentFile})
+
+def printLauncherError(message):
+	# type: (str) -> None
+	_printLauncherStatus({'type': 'error', 'message': message})
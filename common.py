@@ -403,6 +403,17 @@ You can try manually running [{}] once so the installer can use the file.""".for
 					return
 
 # You can use the 'exist_ok' of python3 to do this already, but not in python 2
+class InstallStoppedError(Exception):
+	pass
+
+# Set when the installer has been asked to shut down. A running install stops at the next safe point (between
+# downloads or extractions), rather than being killed part way through writing a file.
+installStopRequested = threading.Event()
+
+def stopInstallIfRequested():
+	if installStopRequested.is_set():
+		raise InstallStoppedError("The install was stopped because the installer is shutting down")
+
 def makeDirsExistOK(directoryToMake):
 	if os.path.exists(directoryToMake):
 		return
@@ -1055,10 +1066,12 @@ class DownloaderAndExtractor:
 				max_attempts = DownloaderAndExtractor.MAX_DOWNLOAD_ATTEMPTS_METALINK
 
 			for attempt in range(max_attempts):
+				stopInstallIfRequested()
 				overallPercentage = int(i*self.downloadProgressAmount/len(self.downloadList))
 				if not self.suppressDownloadStatus:
 					commandLineParser.printSeventhModStatusUpdate(overallPercentage, "Downloading: {} (total) DL Folder: [{}] URL: [{}] (Attempt: {}/{})"
-					                                          .format(prettyPrintFileSize(totalDownloadSize), self.downloadTempDir, url, attempt + 1, max_attempts))
+					                                          .format(prettyPrintFileSize(totalDownloadSize), self.downloadTempDir, url, attempt + 1, max_attempts),
+					                                          phase="Downloading", currentFile=url)
 				if aria(self.downloadTempDir, url=url, followMetaLink=DownloaderAndExtractor.__urlIsMetalink(url)) != 0:
 					print("ERROR - failed to download [{}]. Trying again in 3 seconds...".format(url))
 					time.sleep(3)
@@ -1132,8 +1145,10 @@ class DownloaderAndExtractor:
 
 		# extract or copy all files from the download folder to the game directory
 		for i, extractableItem in enumerate(self.extractList):
+			stopInstallIfRequested()
 			overallPercentage = self.downloadProgressAmount + int(i*self.extractionProgressAmount/len(self.extractList))
-			commandLineParser.printSeventhModStatusUpdate(overallPercentage, "Extracting {}".format(extractableItem))
+			commandLineParser.printSeventhModStatusUpdate(overallPercentage, "Extracting {}".format(extractableItem),
+			                                              phase="Extracting", currentFile=extractableItem.filename)
 
 			destinationFolder, destinationFileName = remapPaths(extractableItem.destinationPath, extractableItem.filename)
 
@@ -1179,7 +1194,8 @@ class DownloaderAndExtractor:
 		"""
 		MAX_QUERY_ATTEMPTS = 5
 		for attempt_no in range(1, MAX_QUERY_ATTEMPTS + 1):
-			commandLineParser.printSeventhModStatusUpdate(1, "Inspecting URL '{}' (attempt {}/{})".format(url, attempt_no, MAX_QUERY_ATTEMPTS))
+			commandLineParser.printSeventhModStatusUpdate(1, "Inspecting URL '{}' (attempt {}/{})".format(url, attempt_no, MAX_QUERY_ATTEMPTS),
+			                                              phase="Inspecting URLs", currentFile=url)
 
 			try:
 				if DownloaderAndExtractor.__urlIsMetalink(url):
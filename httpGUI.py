@@ -1,6 +1,8 @@
 # see https://blog.anvileight.com/posts/simple-python-http-server/
 from __future__ import print_function, unicode_literals
 
+import binascii
+import hmac
 import itertools
 import os
 import json
@@ -33,6 +35,11 @@ except ImportError:
 	import SimpleHTTPServer as server
 	from BaseHTTPServer import HTTPServer
 
+try:
+	from http.cookies import SimpleCookie, CookieError
+except ImportError:
+	from Cookie import SimpleCookie, CookieError
+
 #tk is only required for the below  _askGameExeAndValidate function
 try:
 	from tkinter import Tk
@@ -257,8 +264,38 @@ def _loggerMessageToStatusDict(message):
 	# if the message is not a aria or 7zip message, just show it in the gui log window
 	return {"msg": displayedMessage}
 
-def start_server(working_directory, post_handlers, installRunningLock, serverStartedCallback=lambda _: None):
-	# type: (str, dict, threading.RLock, function) -> None
+# The web server only handles requests which include the session token, so other web pages (and other programs)
+# can't send commands to the installer. The launcher (install_loader) generates the token and passes it in this
+# environment variable - see install_loader/src/session_token.rs
+SESSION_TOKEN_ENV_VAR = 'INSTALL_LOADER_SESSION_TOKEN'
+# The launcher sends the token in this header. Browsers open a URL with the token in the query string once, which
+# sets the session cookie.
+SESSION_TOKEN_HEADER = 'X-Installer-Token'
+SESSION_TOKEN_QUERY_PARAMETER = 'token'
+
+def getSessionToken():
+	# type: () -> str
+	"""Returns the session token given by the launcher, or a new random token if the installer was started without it"""
+	token = os.environ.get(SESSION_TOKEN_ENV_VAR)
+	if token:
+		return token
+
+	return binascii.hexlify(os.urandom(32)).decode('ascii')
+
+def addSessionTokenToURL(url, session_token):
+	# type: (str, str) -> str
+	return '{}?{}={}'.format(url, SESSION_TOKEN_QUERY_PARAMETER, session_token)
+
+def _sessionTokensMatch(token, session_token):
+	# type: (Optional[str], str) -> bool
+	if token is None:
+		return False
+
+	# compare_digest() takes the same time however much of the token matches
+	return hmac.compare_digest(token.encode('utf-8'), session_token.encode('utf-8'))
+
+def start_server(working_directory, post_handlers, installRunningLock, session_token, serverStartedCallback=lambda _: None):
+	# type: (str, dict, threading.RLock, str, function) -> None
 	"""
 	Starts a http server which handles POST requests with callbacks by the given 'post_handlers' argument.
 
@@ -269,6 +306,7 @@ def start_server(working_directory, post_handlers, installRunningLock, serverSta
 		the function represents the action to take when the post request with the specified 'path/url' is received:
 		- the fn takes one argument, which is the data/body of the post request, as a UTF-8 string
 		- the fn should return the response in the form of a UTF-8 string
+	:param session_token: requests without this token are rejected (see getSessionToken())
 
 	:return: None
 	:exception: see serve_forever() of the SimpleHTTPRequestHandler class. In particular, if you try to run two
@@ -284,7 +322,55 @@ def start_server(working_directory, post_handlers, installRunningLock, serverSta
 		- Trying to list a directory gives a 404 instead
 		- All returned files have caching disabled
 		- If an exception occurs while handling a request, the exception is passed to the browser
+		- Requests without the session_token captured from the outer scope are rejected with a 403
 		"""
+		def session_cookie_name(self):
+			# Cookies aren't separated by port, so the port is included in case several installers are open at once
+			return 'installer_session_{}'.format(self.server.server_address[1])
+
+		def request_session_token(self):
+			""" Returns the session token sent in the launcher's header or the session cookie, or None if there isn't one """
+			header_token = self.headers.get(SESSION_TOKEN_HEADER)
+			if header_token is not None:
+				return header_token
+
+			cookies = SimpleCookie()
+			try:
+				cookies.load(self.headers.get('Cookie', ''))
+			except CookieError:
+				return None
+
+			cookie = cookies.get(self.session_cookie_name())
+			return cookie.value if cookie is not None else None
+
+		def check_session(self):
+			"""
+			Returns True if the request has the session token, and should be handled. Otherwise a response has already
+			been sent, and the request should be ignored:
+			- Opening a URL with the session token in its query string sets the session cookie, then redirects to the
+			  same URL without the token, so it doesn't stay in the address bar or browser history
+			- Requests without the session token get a 403 error
+			"""
+			parts = urlparse.urlsplit(self.path)
+			query_tokens = urlparse.parse_qs(parts.query).get(SESSION_TOKEN_QUERY_PARAMETER)
+			if self.command in ('GET', 'HEAD') and query_tokens and _sessionTokensMatch(query_tokens[0], session_token):
+				self.send_response(303)
+				# Other query parameters are kept, as links opened in the browser by the launcher may have them
+				other_query = '&'.join(part for part in parts.query.split('&') if part.split('=', 1)[0] != SESSION_TOKEN_QUERY_PARAMETER)
+				self.send_header('Location', urlparse.urlunsplit(('', '', parts.path, other_query, '')))
+				# SameSite=Strict stops the cookie being sent with requests from other web pages
+				self.send_header('Set-Cookie', '{}={}; Path=/; HttpOnly; SameSite=Strict'.format(self.session_cookie_name(), session_token))
+				self.send_header('Cache-Control', 'no-cache, no-store, must-revalidate')
+				self.end_headers()
+				return False
+
+			if _sessionTokensMatch(self.request_session_token(), session_token):
+				return True
+
+			print('Rejected {} request for [{}] without the installer session token'.format(self.command, parts.path))
+			self.send_error(403, "Missing or invalid installer session token")
+			return False
+
 		def list_directory(self, path):
 			""" This override function always returns a 404 when a directory listing is requested """
 			self.send_error(404, "No permission to list directory")
@@ -305,6 +391,10 @@ def start_server(working_directory, post_handlers, installRunningLock, serverSta
 			None, in which case the caller has nothing further to do.
 
 			"""
+			# --------- THE FOLLOWING WAS ADDED ---------
+			if not self.check_session():
+				return None
+			# --------- END ADDED SECTION ---------
 			originalPath = self.translate_path(self.path)
 			# --------- THE FOLLOWING WAS ADDED ---------
 			# Python 3 has the ability to change web directory built-in, but Python 2 does not.
@@ -364,6 +454,9 @@ def start_server(working_directory, post_handlers, installRunningLock, serverSta
 			pass
 
 		def do_POST(self):
+			if not self.check_session():
+				return
+
 			content_length = int(self.headers['Content-Length'])
 			body_as_string = self.rfile.read(content_length).decode('utf-8')
 
@@ -730,13 +823,32 @@ class InstallerGUI:
 
 		self.installRunningLock = threading.Lock()
 		self.installRunningLock.acquire()
+		self.shutdownStarted = False
+		self.shutdownStartedLock = threading.Lock()
 
 		# This caches the self.try_start_install(...) function, only used for install previews
 		self.cachedFullInstallConfigs = {}  # type: Dict[str, Tuple[bool, installConfiguration.FullInstallConfiguration]]
 		self.updates = None
 
 	def shutdown(self):
-		self.installRunningLock.release()
+		# Shutdown can be requested more than once (e.g. by the web page, then by the launcher), but the
+		# installRunningLock can only be released once
+		with self.shutdownStartedLock:
+			if self.shutdownStarted:
+				return
+			self.shutdownStarted = True
+
+		# Let a running install stop at a safe point before the web server is shut down. This waits on another
+		# thread, so the shutdown request is answered straight away.
+		common.installStopRequested.set()
+
+		def releaseWhenInstallStopped():
+			if self.installAlreadyInProgress():
+				print("Waiting for the install to stop before shutting down...")
+				self.threadHandle.join()
+			self.installRunningLock.release()
+
+		threading.Thread(target=releaseWhenInstallStopped).start()
 
 	def loadDonationStatus(self):
 		self.donationMonthsRemaining, self.donationProgressPercent = common.getDonationStatus()
@@ -832,11 +944,17 @@ class InstallerGUI:
 		if self.installAlreadyInProgress():
 			raise Exception("Can't start install - installer already running.")
 
+		if common.installStopRequested.is_set():
+			raise Exception("Can't start install - the installer is shutting down.")
+
 		def errorPrintingInstaller(args):
 			try:
 				installerFunction(args)
+			except common.InstallStoppedError as e:
+				print(e)
 			except Exception as e:
 				self.threadException = e
+				commandLineParser.printLauncherError("The install failed: {}".format(e))
 
 				raise
 			common.tryDeleteLockFile()
@@ -1208,26 +1326,34 @@ class InstallerGUI:
 			'installer_data': handleInstallerData,
 		}
 
+		sessionToken = getSessionToken()
+
 		def on_server_started(web_server):
 			page = 'loading_screen.html'
-			web_server_url = 'http://{}:{}/{}'.format(web_server.server_address[0], web_server.server_address[1], page)
-			print("If the web page did not open, you can manually navigate to {} in your browser.".format(web_server_url))
+			page_url = 'http://{}:{}/{}'.format(web_server.server_address[0], web_server.server_address[1], page)
+			web_server_url = addSessionTokenToURL(page_url, sessionToken)
+			# This output ends up in the logs, which users share, so the session token is never printed
+			print("Web server started at {} (the session token is not shown)".format(page_url))
+
+			# The server info is always written, as the loader also uses it to ask the installer to shut down
+			try:
+				serverInfoPath = 'server-info.json'
+				with open(serverInfoPath, 'w') as serverInfo:
+					serverInfo.write(json.dumps({
+						'ip': web_server.server_address[0],
+						'port': web_server.server_address[1],
+						'page': page,
+					}))
+			except Exception as e:
+				print("Failed to write server info: {}".format(e))
+
 			if common.Globals.LAUNCH_BROWSER:
 				common.openURLInBrowser(web_server_url)
 			else:
-				try:
-					serverInfoPath = 'server-info.json'
-					print("NOTE: Launching browser from Python script is disabled. Server started at {}, info written to {}".format(web_server_url, serverInfoPath))
-					with open(serverInfoPath, 'w') as serverInfo:
-						serverInfo.write(json.dumps({
-							'ip': web_server.server_address[0],
-							'port': web_server.server_address[1],
-							'page': page,
-						}))
-				except Exception as e:
-					print("Failed to write server info: {}".format(e))
+				print("NOTE: Launching browser from Python script is disabled. Server started at {}, info written to {}".format(page_url, serverInfoPath))
 
 		start_server(working_directory=workingDirectory,
 		             post_handlers=post_handlers,
 		             installRunningLock=self.installRunningLock,
+		             session_token=sessionToken,
 		             serverStartedCallback=on_server_started)
@@ -0,0 +1,118 @@
+// Compares how quickly the installer archive is extracted when compressed with xz and with zstd.
+// Run with: cargo bench --features zstd
+// By default a generated archive is used. Set INSTALL_DATA_TAR to the path of an uncompressed
+// install_data.tar (travis_build_script.py leaves one in the src folder) to benchmark the real installer data.
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+// Each format is decompressed this many times, and the fastest run is reported
+const RUNS: usize = 5;
+
+// Compression settings should roughly match travis_build_script.py
+const XZ_LEVEL: u32 = 9;
+const ZSTD_LEVEL: i32 = 19;
+
+fn main() {
+	let tar = match env::var_os("INSTALL_DATA_TAR") {
+		Some(path) => fs::read(&path).expect("Failed to read INSTALL_DATA_TAR"),
+		None => generated_tar(),
+	};
+	println!("Uncompressed archive: {:.1} MB", megabytes(tar.len()));
+
+	let xz = compress("xz", || {
+		let mut encoder = xz2::read::XzEncoder::new(&tar[..], XZ_LEVEL);
+		let mut compressed = Vec::new();
+		encoder.read_to_end(&mut compressed).unwrap();
+		compressed
+	});
+	let zstd = compress("zstd", || {
+		zstd::stream::encode_all(&tar[..], ZSTD_LEVEL).unwrap()
+	});
+
+	let xz_time = extract("xz", tar.len(), || xz2::read::XzDecoder::new(&xz[..]));
+	let zstd_time = extract("zstd", tar.len(), || {
+		zstd::stream::read::Decoder::new(&zstd[..]).unwrap()
+	});
+
+	println!(
+		"zstd extracts {:.1}x faster than xz, with an archive size of {:+.1}% compared to xz",
+		xz_time.as_secs_f64() / zstd_time.as_secs_f64(),
+		(zstd.len() as f64 / xz.len() as f64 - 1.0) * 100.0
+	);
+}
+
+fn compress<F: FnOnce() -> Vec<u8>>(name: &str, compress: F) -> Vec<u8> {
+	let start_time = Instant::now();
+	let compressed = compress();
+	println!(
+		"{:>4}: compressed to {:.1} MB in {:.1}s",
+		name,
+		megabytes(compressed.len()),
+		start_time.elapsed().as_secs_f64()
+	);
+	compressed
+}
+
+// Reads every entry of the compressed archive, the same way the loader does, but without writing to disk
+fn extract<R: Read, F: Fn() -> R>(name: &str, uncompressed_size: usize, decoder: F) -> Duration {
+	let mut fastest = Duration::MAX;
+	for _ in 0..RUNS {
+		let start_time = Instant::now();
+		let mut archive = tar::Archive::new(decoder());
+		for entry in archive.entries().unwrap() {
+			io::copy(&mut entry.unwrap(), &mut io::sink()).unwrap();
+		}
+		fastest = fastest.min(start_time.elapsed());
+	}
+
+	println!(
+		"{:>4}: extracted in {:.3}s ({:.1} MB/s)",
+		name,
+		fastest.as_secs_f64(),
+		megabytes(uncompressed_size) / fastest.as_secs_f64()
+	);
+	fastest
+}
+
+// Builds an archive which is a mix of text-like files (like the Python sources) and poorly
+// compressible binary files (like the Python runtime's .dll and .pyd files)
+fn generated_tar() -> Vec<u8> {
+	let mut builder = tar::Builder::new(Vec::new());
+	let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+	for file_index in 0..200 {
+		let data: Vec<u8> = if file_index % 4 == 0 {
+			(0..256 * 1024)
+				.map(|_| {
+					// xorshift
+					state ^= state << 13;
+					state ^= state >> 7;
+					state ^= state << 17;
+					// Only use a few bits per byte, so the data compresses about as well as a binary
+					(state & 0x1F) as u8
+				})
+				.collect()
+		} else {
+			format!(
+				"def function_{0}(argument):\n\treturn argument * {0}\n\n",
+				file_index
+			)
+			.repeat(2000)
+			.into_bytes()
+		};
+
+		let mut header = tar::Header::new_gnu();
+		header.set_size(data.len() as u64);
+		header.set_mode(0o644);
+		header.set_cksum();
+		builder
+			.append_data(&mut header, format!("file_{}.bin", file_index), &data[..])
+			.unwrap();
+	}
+	builder.into_inner().unwrap()
+}
+
+fn megabytes(bytes: usize) -> f64 {
+	bytes as f64 / 1_000_000.0
+}
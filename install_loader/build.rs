@@ -1,4 +1,6 @@
+use std::env;
 use std::io;
+use std::path::{Path, PathBuf};
 #[cfg(windows)] use winres::WindowsResource;
 
 fn main() -> io::Result<()> {
@@ -9,5 +11,35 @@ fn main() -> io::Result<()> {
             .set_icon("src/resources/icon.ico")
             .compile()?;
     }
+
+    // Only compile the installer data into the executable if the build script has created it.
+    // Otherwise the loader is built without any data, and it must be appended with stamp_payload.py
+    // or given with --payload at runtime.
+    // A chunked archive (install_data.chunks) is used if the build script created one, as it extracts in parallel.
+    // Otherwise a .tar.zst archive is preferred if the zstd decoder is enabled, as it is faster to extract.
+    let mut archive_candidates = vec!["src/install_data.chunks"];
+    if env::var_os("CARGO_FEATURE_ZSTD").is_some() {
+        archive_candidates.push("src/install_data.tar.zst");
+    }
+    if env::var_os("CARGO_FEATURE_XZ").is_some() {
+        archive_candidates.push("src/install_data.tar.xz");
+    }
+    let manifest_path = "src/install_data_manifest.json";
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/resources/icon.ico");
+    for archive_path in &archive_candidates {
+        println!("cargo:rerun-if-changed={}", archive_path);
+    }
+    println!("cargo:rerun-if-changed={}", manifest_path);
+    println!("cargo:rustc-check-cfg=cfg(embedded_payload)");
+
+    let archive_path = archive_candidates.iter().find(|path| Path::new(path).exists());
+    if let (Some(archive_path), true) = (archive_path, Path::new(manifest_path).exists()) {
+        let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+        println!("cargo:rustc-env=EMBEDDED_PAYLOAD_ARCHIVE={}", manifest_dir.join(archive_path).display());
+        println!("cargo:rustc-cfg=embedded_payload");
+    }
+
     Ok(())
-}
\ No newline at end of file
+}
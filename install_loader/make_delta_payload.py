@@ -0,0 +1,174 @@
+"""
+Creates a delta payload, which updates an extraction of the base version of the installer data to the
+target version. Only added files, and binary patches for changed files, are included - so the delta is
+much smaller than the full installer data when only a few files have changed.
+
+The loader applies the delta with --delta-payload <file>. If the installed version doesn't match the
+base version, the loader does a full extraction instead. See binary_patch.rs for the patch format.
+
+Usage:
+	python make_delta_payload.py <base_folder> <target_folder> <base_version> <target_version> <output_file>
+
+<base_folder> and <target_folder> are the extracted 'install_data' folders of each version.
+"""
+import argparse
+import hashlib
+import io
+import json
+import lzma
+import os
+import struct
+import tarfile
+import tempfile
+import unicodedata
+
+from stamp_payload import stamp_payload
+
+PATCH_MAGIC = b'7THPATCH'
+OP_COPY = 0x00
+OP_INSERT = 0x01
+
+# Size of the blocks used to find matching data in the base file
+BLOCK_SIZE = 32
+
+# Changed files whose patch isn't much smaller than the file are included in full instead
+MAX_PATCH_RATIO = 0.5
+
+
+def list_files(folder):
+	"""
+	Returns a dict of '/' separated, NFC normalized relative path -> full path, for each file in folder
+	"""
+	files = {}
+	for root, _dirs, filenames in os.walk(folder):
+		for filename in filenames:
+			full_path = os.path.join(root, filename)
+			relative_path = unicodedata.normalize('NFC', os.path.relpath(full_path, folder).replace(os.sep, '/'))
+			files[relative_path] = full_path
+	return files
+
+
+def read_file(path):
+	with open(path, 'rb') as file:
+		return file.read()
+
+
+def make_patch(base, target):
+	"""
+	Returns a patch which rebuilds target from base, as a sequence of copies from base and inserted data
+	"""
+	block_offsets = {}
+	for offset in range(0, len(base) - BLOCK_SIZE + 1, BLOCK_SIZE):
+		block_offsets.setdefault(base[offset:offset + BLOCK_SIZE], offset)
+
+	patch = io.BytesIO()
+	patch.write(PATCH_MAGIC)
+	patch.write(struct.pack('<Q', len(target)))
+
+	def write_insert(data):
+		if data:
+			patch.write(struct.pack('<BQ', OP_INSERT, len(data)))
+			patch.write(data)
+
+	insert_start = 0
+	position = 0
+	while position + BLOCK_SIZE <= len(target):
+		base_offset = block_offsets.get(target[position:position + BLOCK_SIZE])
+		if base_offset is None:
+			position += 1
+			continue
+
+		# Extend the match backwards into the pending inserted data, then forwards as far as possible
+		match_start = position
+		while match_start > insert_start and base_offset > 0 and base[base_offset - 1] == target[match_start - 1]:
+			match_start -= 1
+			base_offset -= 1
+
+		match_end = position + BLOCK_SIZE
+		base_end = base_offset + (match_end - match_start)
+		while match_end < len(target) and base_end < len(base) and base[base_end] == target[match_end]:
+			match_end += 1
+			base_end += 1
+
+		write_insert(target[insert_start:match_start])
+		patch.write(struct.pack('<BQQ', OP_COPY, base_offset, match_end - match_start))
+		insert_start = position = match_end
+
+	write_insert(target[insert_start:])
+	return patch.getvalue()
+
+
+def make_delta_payload(base_folder, target_folder, base_version, target_version, output_path):
+	base_files = list_files(base_folder)
+	target_files = list_files(target_folder)
+
+	manifest_files = {}
+	added = []
+	patched = []
+	archive_buffer = io.BytesIO()
+	with tarfile.open(fileobj=archive_buffer, mode='w:xz', preset=9 | lzma.PRESET_EXTREME) as archive:
+		def add_to_archive(relative_path, data):
+			info = tarfile.TarInfo(relative_path)
+			info.size = len(data)
+			info.mtime = os.path.getmtime(target_files[relative_path])
+			archive.addfile(info, io.BytesIO(data))
+
+		for relative_path, target_path in sorted(target_files.items()):
+			target = read_file(target_path)
+			manifest_files[relative_path] = {
+				'size': len(target),
+				'sha256': hashlib.sha256(target).hexdigest(),
+			}
+
+			if relative_path in base_files:
+				base = read_file(base_files[relative_path])
+				if base == target:
+					continue
+
+				patch = make_patch(base, target)
+				if len(patch) < len(target) * MAX_PATCH_RATIO:
+					patched.append(relative_path)
+					add_to_archive(relative_path, patch)
+					continue
+
+			added.append(relative_path)
+			add_to_archive(relative_path, target)
+
+	removed = sorted(set(base_files) - set(target_files))
+
+	manifest = {
+		'version': target_version,
+		'files': manifest_files,
+		'delta': {
+			'base_version': base_version,
+			'added': added,
+			'patched': patched,
+			'removed': removed,
+		},
+	}
+
+	with tempfile.TemporaryDirectory() as temp_folder:
+		archive_path = os.path.join(temp_folder, 'delta.tar.xz')
+		manifest_path = os.path.join(temp_folder, 'delta_manifest.json')
+
+		with open(archive_path, 'wb') as archive_file:
+			archive_file.write(archive_buffer.getvalue())
+
+		with open(manifest_path, 'w', encoding='utf-8') as manifest_file:
+			json.dump(manifest, manifest_file, indent='\t', sort_keys=True)
+
+		stamp_payload(output_path, archive_path, manifest_path, True)
+
+	print(f"Delta from [{base_version}] to [{target_version}]: {len(added)} added, {len(patched)} patched, {len(removed)} removed")
+
+
+if __name__ == '__main__':
+	parser = argparse.ArgumentParser(description='Create a delta payload which updates the base version of the installer data to the target version')
+	parser.add_argument('base_folder', help='The install_data folder of the base version')
+	parser.add_argument('target_folder', help='The install_data folder of the target version')
+	parser.add_argument('base_version', help='The version of the base installer data, as written in the extraction lock (the git tag)')
+	parser.add_argument('target_version', help='The version of the target installer data')
+	parser.add_argument('output', help='The delta payload file to create')
+	args = parser.parse_args()
+
+	make_delta_payload(args.base_folder, args.target_folder, args.base_version, args.target_version, args.output)
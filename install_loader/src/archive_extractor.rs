@@ -1,52 +1,210 @@
+use crate::binary_patch;
+use crate::compression::CompressionFormat;
+use crate::extraction_journal::ExtractionJournal;
+use crate::payload::{ManifestEntry, Payload, PayloadManifest, PayloadSource};
 use crate::version;
 use progress_streams::ProgressReader;
-use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::{self, TryRecvError};
 use std::sync::mpsc::{Receiver, Sender};
-use std::{fs, thread};
-use tar::Archive;
-use xz2::read::XzDecoder;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use std::{fs, io, thread};
+use tar::{Archive, Entry, EntryType};
+use unicode_normalization::UnicodeNormalization;
+
+const EXTRACTION_LOCK_FILENAME: &str = "installer_loader_extraction_lock.txt";
+
+// Suffixes of the folders created next to the sub folder during extraction
+const STAGING_SUFFIX: &str = ".staging";
+const BACKUP_SUFFIX: &str = ".old";
+
+// Files and folders created in the extraction folder after it was extracted (by the launcher or the
+// python installer), which are moved into each new extraction. Anything else in the previous
+// extraction is deleted with it, so files the new version no longer has can't be left behind.
+const CARRIED_OVER_FILES: &[&str] = &[
+	// Installer logs (see config.rs and LOG_FOLDER in common.py), and the zip of them made for support
+	"INSTALLER_LOGS",
+	"07th-mod-logs.zip",
+	// Webview data, like the session cookie (see config.rs)
+	"webview",
+	// Which launch mode worked last time (see launch_preferences.rs)
+	"launcher-state.json",
+	// Written by the python installer's web server (see config.rs)
+	"server-info.json",
+	"server-info-old.json",
+];
+
+// If any of these files are missing, the extracted installer is assumed to be broken
+// (for example, if an antivirus program has quarantined them), and will be re-extracted.
+// Only the Windows bundle contains a python runtime - elsewhere the system python may be used
+// (see python_interpreter::resolve()).
+#[cfg(windows)]
+const REQUIRED_FILES: &[&str] = &["python/python.exe", "main.py", "cli_interactive.py"];
+#[cfg(not(windows))]
+const REQUIRED_FILES: &[&str] = &["main.py", "cli_interactive.py"];
 
 enum ExtractionReport {
-	InProgress { percentage: usize },
+	// The preflight checks failed, so nothing was extracted
+	PreflightFailed(String),
+	InProgress(ExtractionProgress),
+	Repairing { damaged_files: Vec<String> },
 	Finished,
+	Cancelled,
 }
 
 enum ExtractionStateMachine {
 	NotStarted,
 	Started(Receiver<Result<ExtractionReport, String>>),
 	Finished,
+	Cancelled,
+	PreflightFailed(String),
+}
+
+// Reasons the staged extraction can stop before completion
+#[derive(Debug)]
+enum ExtractionFailure {
+	Cancelled,
+	Error(String),
 }
 
 pub enum ExtractionStatus {
 	NotStarted,
-	Started(Option<usize>),
+	// There isn't enough disk space, or the extraction folder isn't writeable (see preflight_checks()).
+	// The message explains how to fix the problem. Nothing was extracted.
+	PreflightFailed(String),
+	Started(Option<ExtractionProgress>),
+	// Some extracted files were missing or modified (usually due to antivirus), and are being restored
+	Repairing(Vec<String>),
 	Finished,
+	// The extraction was cancelled with ArchiveExtractor::cancel(), and any partial output was removed
+	Cancelled,
 	Error(String),
 }
 
+#[derive(Clone, Default)]
+pub struct ExtractionProgress {
+	// Percentage of the compressed archive which has been read
+	pub percentage: usize,
+	// Path within the archive of the most recently extracted entry
+	pub current_entry: String,
+	// Uncompressed bytes written to disk so far, out of the total uncompressed size of the archive
+	pub bytes_written: u64,
+	pub bytes_total: u64,
+	// Number of files extracted so far, out of the total number of files in the archive
+	pub entries_done: usize,
+	pub entries_total: usize,
+	// Measured extraction speed in uncompressed bytes per second
+	pub bytes_per_second: f64,
+	// Estimated time until extraction completes, based on the measured extraction speed
+	pub eta: Option<Duration>,
+}
+
+impl ExtractionProgress {
+	// Returns a one line summary of the progress, for example:
+	// "120/4000 files, 10.5/60.0 MB @ 5.2 MB/s, about 0m 10s remaining"
+	pub fn summary(&self) -> String {
+		let eta = match self.eta {
+			Some(eta) => format!(", about {}m {:02}s remaining", eta.as_secs() / 60, eta.as_secs() % 60),
+			None => String::new(),
+		};
+
+		// Payloads without a manifest don't have a known total size
+		if self.bytes_total == 0 {
+			return format!(
+				"{} files, {:.1} MB @ {:.1} MB/s",
+				self.entries_done,
+				self.bytes_written as f64 / 1_000_000.0,
+				self.bytes_per_second / 1_000_000.0
+			);
+		}
+
+		format!(
+			"{}/{} files, {:.1}/{:.1} MB @ {:.1} MB/s{}",
+			self.entries_done,
+			self.entries_total,
+			self.bytes_written as f64 / 1_000_000.0,
+			self.bytes_total as f64 / 1_000_000.0,
+			self.bytes_per_second / 1_000_000.0,
+			eta
+		)
+	}
+}
+
 pub struct ArchiveExtractor {
 	receiver: ExtractionStateMachine,
+	// If false, extraction is skipped if the files from this version of the installer were already extracted
+	force_extraction: bool,
+	payload_source: PayloadSource,
+	// If set, this delta payload is applied to the current extraction instead of doing a full extraction
+	delta_payload: Option<PathBuf>,
+	// If true, the extraction thread checks the disk space and folder permissions before extracting
+	preflight_checks: bool,
+	// Set by cancel(). The extraction thread checks this before extracting each archive entry, and while
+	// verifying the extracted files.
+	cancel_requested: Arc<AtomicBool>,
+	// The most recent progress update, so the final 100% update can keep its file and byte counts
+	last_progress: Option<ExtractionProgress>,
 }
 
 impl ArchiveExtractor {
-	pub fn new() -> ArchiveExtractor {
+	pub fn new(
+		force_extraction: bool,
+		payload_source: PayloadSource,
+		delta_payload: Option<PathBuf>,
+	) -> ArchiveExtractor {
 		ArchiveExtractor {
 			receiver: ExtractionStateMachine::NotStarted,
+			force_extraction,
+			payload_source,
+			delta_payload,
+			preflight_checks: false,
+			cancel_requested: Arc::new(AtomicBool::new(false)),
+			last_progress: None,
 		}
 	}
 
+	// Check there is enough disk space, and the extraction folder is writeable, before extracting.
+	// If not, poll_status() returns ExtractionStatus::PreflightFailed.
+	pub fn enable_preflight_checks(&mut self) {
+		self.preflight_checks = true;
+	}
+
 	pub fn start_extraction(&mut self, sub_folder_path: &Path) {
 		match self.receiver {
 			ExtractionStateMachine::NotStarted => {
 				let (sender, receiver) = mpsc::channel::<Result<ExtractionReport, String>>();
-				extract_archive_new_thread(sub_folder_path, sender);
+				extract_archive_new_thread(
+					sub_folder_path,
+					self.force_extraction,
+					self.payload_source.clone(),
+					self.delta_payload.clone(),
+					self.preflight_checks,
+					Arc::clone(&self.cancel_requested),
+					sender,
+				);
 				self.receiver = ExtractionStateMachine::Started(receiver);
 			}
 			_ => {}
 		}
 	}
 
+	// Asks the extraction thread to stop at the next archive entry (or file being verified). This doesn't
+	// wait for it to stop - keep calling poll_status(), which returns ExtractionStatus::Cancelled once the
+	// partially extracted files have been removed. The extraction may still finish (or fail) if it was
+	// too late to cancel it.
+	pub fn cancel(&mut self) {
+		self.cancel_requested.store(true, Ordering::SeqCst);
+
+		if let ExtractionStateMachine::NotStarted = self.receiver {
+			self.receiver = ExtractionStateMachine::Cancelled;
+		}
+	}
+
 	// This doesn't correctly handle the case where
 	pub fn poll_status(&mut self) -> ExtractionStatus {
 		match &mut self.receiver {
@@ -72,44 +230,337 @@ impl ArchiveExtractor {
 				};
 
 				// If extraction complete, report 100%, and advance to final state
-				// Otherwise, return the percentage completion and stay in same state
-				let percentage: usize = match progress {
+				// Otherwise, return the progress and stay in same state
+				match progress {
+					ExtractionReport::PreflightFailed(reason) => {
+						self.receiver = ExtractionStateMachine::PreflightFailed(reason.clone());
+						ExtractionStatus::PreflightFailed(reason)
+					}
 					ExtractionReport::Finished => {
 						self.receiver = ExtractionStateMachine::Finished;
-						100
+						ExtractionStatus::Started(Some(ExtractionProgress {
+							percentage: 100,
+							eta: None,
+							..self.last_progress.take().unwrap_or_default()
+						}))
 					}
-					ExtractionReport::InProgress { percentage } => percentage,
-				};
-
-				ExtractionStatus::Started(Some(percentage))
+					ExtractionReport::InProgress(progress) => {
+						self.last_progress = Some(progress.clone());
+						ExtractionStatus::Started(Some(progress))
+					}
+					ExtractionReport::Repairing { damaged_files } => {
+						ExtractionStatus::Repairing(damaged_files)
+					}
+					ExtractionReport::Cancelled => {
+						self.receiver = ExtractionStateMachine::Cancelled;
+						ExtractionStatus::Cancelled
+					}
+				}
 			}
 			ExtractionStateMachine::Finished => ExtractionStatus::Finished,
+			ExtractionStateMachine::Cancelled => ExtractionStatus::Cancelled,
+			ExtractionStateMachine::PreflightFailed(reason) => {
+				ExtractionStatus::PreflightFailed(reason.clone())
+			}
 		}
 	}
 }
 
 fn extract_archive_new_thread(
 	sub_folder_path: &Path,
+	force_extraction: bool,
+	payload_source: PayloadSource,
+	delta_payload: Option<PathBuf>,
+	preflight_checks: bool,
+	cancel_requested: Arc<AtomicBool>,
 	progress_update: Sender<Result<ExtractionReport, String>>,
 ) {
 	let mut path_copy = PathBuf::new();
 	path_copy.push(sub_folder_path);
+	// The thread isn't joined - poll_status() reports when it has finished
 	thread::spawn(move || {
 		println!("Spawning extraction thread");
-		extract_archive(path_copy.as_path(), progress_update);
+		extract_archive(
+			path_copy.as_path(),
+			force_extraction,
+			&payload_source,
+			delta_payload.as_deref(),
+			preflight_checks,
+			&cancel_requested,
+			progress_update,
+		);
 	});
 }
 
+// Verifies the files in the sub folder against the payload's manifest, without repairing them.
+// Returns the manifest path of each file which is missing or modified.
+pub fn verify_extraction(
+	sub_folder_path: &Path,
+	payload_source: &PayloadSource,
+) -> Result<Vec<String>, String> {
+	let payload = payload_source.load()?;
+	let manifest = payload.manifest.as_ref().ok_or_else(|| {
+		format!("{} has no manifest, so the extracted files can't be verified", payload.description)
+	})?;
+
+	Ok(find_damaged_files(sub_folder_path, manifest, &AtomicBool::new(false)).unwrap_or_default())
+}
+
+// An entry in the installer archive, as listed by list_archive_entries()
+pub struct ArchiveListing {
+	pub path: String,
+	pub entry_type: &'static str,
+	pub size: u64,
+}
+
+// Lists every entry in the payload's archive, checking each one against the entry policy
+pub fn list_archive_entries(payload_source: &PayloadSource) -> Result<Vec<ArchiveListing>, String> {
+	let payload = payload_source.load()?;
+	let mut listings = Vec::new();
+
+	for chunk in payload.chunks()? {
+		let decoder = CompressionFormat::detect(chunk).and_then(|format| format.decoder(chunk))?;
+		let mut archive = Archive::new(decoder);
+		let entries = archive.entries().map_err(|e| e.to_string())?;
+		for entry in entries {
+			let entry = entry.map_err(|e| e.to_string())?;
+			let (path, entry_type) = match validate_archive_entry(&entry).map_err(|e| e.to_string())? {
+				ValidatedEntry::Skip => continue,
+				ValidatedEntry::Directory(path) => (path, "directory"),
+				ValidatedEntry::File(path) => (path, "file"),
+				ValidatedEntry::Symlink { path, .. } => (path, "symlink"),
+				ValidatedEntry::HardLink { path, .. } => (path, "hardlink"),
+			};
+
+			listings.push(ArchiveListing {
+				path: manifest_path(&path),
+				entry_type,
+				size: entry.size(),
+			});
+		}
+	}
+
+	Ok(listings)
+}
+
+// Checks that there is enough free disk space to extract the installer, and that the folders
+// used during extraction are writeable. Returns a message explaining how to fix the problem if not.
+// Nothing is created, so a previous extraction interrupted while being swapped (see
+// recover_interrupted_swap()) can still be restored.
+fn preflight_checks(sub_folder_path: &Path, payload: &Payload) -> Result<(), String> {
+	// The previous extraction is kept until the new extraction is complete, so the full
+	// uncompressed size is always required, plus some extra space for the installer's own files.
+	// If the payload has no manifest, the uncompressed size is unknown and the check is skipped.
+	let uncompressed_size = payload.uncompressed_size().unwrap_or(0);
+	let required_space = uncompressed_size + uncompressed_size / 10;
+
+	let existing_folder = nearest_existing_ancestor(sub_folder_path);
+	let display_path = existing_folder
+		.canonicalize()
+		.unwrap_or_else(|_| existing_folder.clone());
+
+	match fs2::available_space(&existing_folder) {
+		Ok(available_space) if available_space < required_space => {
+			return Err(format!(
+				"Not enough disk space to extract the installer.\n\
+{:.1} MB is required on the drive containing [{}], but only {:.1} MB is free.\n\
+Please free up some disk space, or move the installer to a different drive, then try again.",
+				required_space as f64 / 1_000_000.0,
+				display_path.display(),
+				available_space as f64 / 1_000_000.0
+			));
+		}
+		Ok(_) => {}
+		Err(e) => println!("Warning - Couldn't determine free disk space: {}", e),
+	}
+
+	// The staging folder is created next to the sub folder, so both the sub folder and
+	// the folder containing it need to be writeable. Folders which don't exist yet will be
+	// created in the nearest folder which does, so that folder is checked instead.
+	let parent_folder = match sub_folder_path.parent() {
+		Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+		_ => PathBuf::from("."),
+	};
+
+	for folder in [parent_folder.as_path(), sub_folder_path].iter() {
+		let folder = nearest_existing_ancestor(folder);
+		if let Err(e) = probe_writeable(&folder) {
+			let folder_display = folder.canonicalize().unwrap_or_else(|_| folder.to_path_buf());
+			let mut message = format!(
+				"The installer can't write to the folder [{}]\nError: {}\n\n\
+Please move the installer to a user writeable folder (like your Downloads folder), then try again.",
+				folder_display.display(),
+				e
+			);
+
+			if e.kind() == io::ErrorKind::PermissionDenied {
+				message.push_str("\nIf the folder is writeable, your antivirus (or Windows 'Controlled Folder Access') may be blocking the installer.");
+			}
+
+			return Err(message);
+		}
+	}
+
+	Ok(())
+}
+
+// Checks an existing folder is writeable by creating and deleting a file inside it
+fn probe_writeable(folder: &Path) -> io::Result<()> {
+	let probe_path = folder.join("07th-mod_write_test.tmp");
+	fs::write(&probe_path, b"test")?;
+	fs::remove_file(&probe_path)
+}
+
+// Returns the path, or the closest parent of the path, which exists on disk
+fn nearest_existing_ancestor(path: &Path) -> PathBuf {
+	path.ancestors()
+		.find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+		.map(Path::to_path_buf)
+		.unwrap_or_else(|| PathBuf::from("."))
+}
+
 fn extract_archive(
 	sub_folder_path: &Path,
+	force_extraction: bool,
+	payload_source: &PayloadSource,
+	delta_payload: Option<&Path>,
+	run_preflight_checks: bool,
+	cancel_requested: &AtomicBool,
 	progress_update: Sender<Result<ExtractionReport, String>>,
 ) {
-	let saved_git_tag_path = sub_folder_path.join("installer_loader_extraction_lock.txt");
+	// If a previous run was interrupted while swapping in a new extraction, restore the previous tree.
+	// This must happen before the preflight checks, which look at the sub folder.
+	recover_interrupted_swap(sub_folder_path);
+
+	// The payload is loaded here (rather than on the UI thread) as loading a stamped payload hashes the
+	// whole archive. It is then reused for the full extraction.
+	let mut loaded_payload = None;
+	if run_preflight_checks {
+		let result = payload_source.load().and_then(|payload| {
+			preflight_checks(sub_folder_path, &payload).map(|()| payload)
+		});
+
+		match result {
+			Ok(payload) => loaded_payload = Some(payload),
+			Err(reason) => {
+				let _ = progress_update.send(Ok(ExtractionReport::PreflightFailed(reason)));
+				return;
+			}
+		}
+	}
 
-	// During compilation, include the installer archive in .tar.xz format
-	//NOTE: The below file must be placed adjacent to this source file!
-	//The archive should not contain any subfolders - one will be created automatically
-	let archive_bytes = include_bytes!("install_data.tar.xz");
+	// A delta payload only contains the changes from its base version. If it can't be applied to
+	// the current extraction, the full payload is extracted instead.
+	let delta_result = match delta_payload {
+		Some(delta_path) if !force_extraction => {
+			match update_from_delta_payload(sub_folder_path, delta_path, cancel_requested, &progress_update) {
+				Err(ExtractionFailure::Error(reason)) => {
+					println!(
+						"07th-Mod Installer Loader: Can't apply delta payload [{}] - doing a full extraction instead. Reason: {}",
+						delta_path.display(),
+						reason
+					);
+					None
+				}
+				result => Some(result),
+			}
+		}
+		_ => None,
+	};
+
+	let result = if let Some(result) = delta_result {
+		result
+	} else {
+		match loaded_payload.map_or_else(|| payload_source.load(), Ok) {
+			Ok(payload) => full_extraction(
+				sub_folder_path,
+				force_extraction,
+				&payload,
+				cancel_requested,
+				&progress_update,
+			),
+			Err(error_message) => Err(ExtractionFailure::Error(error_message)),
+		}
+	};
+
+	match result {
+		Ok(()) => {}
+		Err(ExtractionFailure::Cancelled) => {
+			println!("Extraction Cancelled.");
+			// The receiver may have already been dropped if the program is exiting
+			let _ = progress_update.send(Ok(ExtractionReport::Cancelled));
+			return;
+		}
+		Err(ExtractionFailure::Error(error_message)) => {
+			progress_update
+				.send(Err(error_message))
+				.expect("Failed to send error progress update");
+			return;
+		}
+	}
+
+	progress_update
+		.send(Ok(ExtractionReport::Finished))
+		.expect("Failed to send progress update - aborting extraction");
+	println!("Extraction Complete.");
+}
+
+// Extracts the full payload, unless it was already extracted (in which case it is only verified)
+fn full_extraction(
+	sub_folder_path: &Path,
+	force_extraction: bool,
+	payload: &Payload,
+	cancel_requested: &AtomicBool,
+	progress_update: &Sender<Result<ExtractionReport, String>>,
+) -> Result<(), ExtractionFailure> {
+	let saved_git_tag_path = sub_folder_path.join(EXTRACTION_LOCK_FILENAME);
+
+	//NOTE: The archive should not contain any subfolders - one will be created automatically
+
+	println!(
+		"07th-Mod Installer Loader: Using {} (version [{}])",
+		payload.description, payload.version
+	);
+
+	if !force_extraction
+		&& extraction_is_up_to_date(sub_folder_path, &saved_git_tag_path, &payload.version)
+	{
+		println!(
+			"07th-Mod Installer Loader: Files for version [{}] already extracted to [{}] - skipping extraction",
+			payload.version,
+			sub_folder_path.display()
+		);
+
+		// Quickly check every extracted file against the manifest, and restore any files which are missing or
+		// modified. The files were already hashed when they were extracted, so they aren't hashed again here.
+		// If the extraction time can't be read, fall back to hashing every file.
+		let extracted_at = fs::metadata(&saved_git_tag_path)
+			.and_then(|metadata| metadata.modified())
+			.ok();
+		verify_and_repair(sub_folder_path, payload, extracted_at, cancel_requested, progress_update).map_err(
+			|failure| {
+				// Force a full extraction next time, as the current extraction can't be repaired
+				if let ExtractionFailure::Error(_) = failure {
+					let _ = fs::remove_file(&saved_git_tag_path);
+				}
+				failure
+			},
+		)
+	} else {
+		staged_extraction(sub_folder_path, payload, cancel_requested, progress_update)
+	}
+}
+
+// Extracts the archive into a sibling staging folder, verifies it, then swaps it into place.
+// The previous extraction (if any) is kept until the swap succeeds, so a failed or interrupted
+// extraction never leaves the sub folder partially extracted.
+fn staged_extraction(
+	sub_folder_path: &Path,
+	payload: &Payload,
+	cancel_requested: &AtomicBool,
+	progress_update: &Sender<Result<ExtractionReport, String>>,
+) -> Result<(), ExtractionFailure> {
+	let staging_path = sibling_path(sub_folder_path, STAGING_SUFFIX);
 
 	let cwd = std::env::current_dir()
 		.map(|path| path.display().to_string())
@@ -118,74 +569,2023 @@ fn extract_archive(
 	println!(
 		"07th-Mod Installer Loader: Please wait. Extracting to [{}\\{}]",
 		cwd,
-		sub_folder_path.display()
+		staging_path.display()
 	);
 
-	// Pipe from the XzDecoder (.xz handler) to the Archive (.tar handler), then extract all files.
-	let mut progress_counter = ProgressCounter::new(archive_bytes.len(), 1_000_000);
-	let intermediate_reader = ProgressReader::new(&archive_bytes[..], |progress_bytes: usize| {
-		if let Some(percentage) = progress_counter.update(progress_bytes) {
-			println!("Extraction {}%", percentage);
+	let extraction_error_message = format!("Can't extract files. Make sure all installers are closed, you have enough disk space, and try again.\n\
+Also check permissions to write to the folder (try moving installer to a different folder)\n\
+[{}\\{}]\n\
+You can also try 'Run as Administrator', but the installer may not work correctly.", cwd, sub_folder_path.display());
+
+	// If a previous extraction of this payload was interrupted, resume it. Otherwise, remove any
+	// leftovers from a previous failed extraction.
+	let payload_id = format!("{}\t{}", payload.version, payload.archive.len());
+	let resume_point = read_resume_point(&staging_path, &payload_id, payload, cancel_requested);
+	if cancel_requested.load(Ordering::SeqCst) {
+		let _ = fs::remove_dir_all(&staging_path);
+		return Err(ExtractionFailure::Cancelled);
+	}
+	let journal = match &resume_point {
+		Some(resume_point) => {
+			println!(
+				"Resuming interrupted extraction - {} files in {} chunk(s) were already extracted",
+				resume_point.written_files.len(),
+				resume_point.finished_chunks.len()
+			);
+			ExtractionJournal::resume(&staging_path)
+		}
+		None => {
+			if staging_path.exists() {
+				if let Err(e) = fs::remove_dir_all(&staging_path) {
+					println!("Failed to remove old staging folder {:?}: {}", staging_path, e);
+					return Err(ExtractionFailure::Error(extraction_error_message));
+				}
+			}
+
+			fs::create_dir_all(&staging_path)
+				.and_then(|()| ExtractionJournal::create(&staging_path, &payload_id))
+		}
+	};
+	let resume_point = resume_point.unwrap_or_default();
+
+	// The journal only makes interrupted extractions resumable, so extraction continues without it
+	let mut journal = match journal {
+		Ok(journal) => Some(journal),
+		Err(e) => {
+			println!("Warning - Failed to open extraction journal, extraction won't be resumable: {}", e);
+			None
+		}
+	};
+
+	// The archive may be split into independently compressed chunks, which are decompressed and
+	// extracted in parallel. Once cancellation is requested, each worker stops before its next entry.
+	// Progress is measured by how much of the compressed archive has been read, and a progress
+	// report is sent after the next file is written, once enough progress has been made.
+	let chunks = payload.chunks().map_err(ExtractionFailure::Error)?;
+	for (chunk_index, chunk) in chunks.iter().enumerate() {
+		let compression_format = CompressionFormat::detect(chunk).map_err(ExtractionFailure::Error)?;
+		compression_format
+			.check_supported()
+			.map_err(ExtractionFailure::Error)?;
+		println!(
+			"Installer archive chunk {} uses {} compression",
+			chunk_index,
+			compression_format.name()
+		);
+	}
+
+	let archive_length = chunks.iter().map(|chunk| chunk.len()).sum();
+	let mut progress_counter = ProgressCounter::new(archive_length, 1_000_000);
+	let mut last_bytes_read = 0;
+
+	// Files which were extracted before the extraction was interrupted aren't counted
+	let mut throughput = match &payload.manifest {
+		Some(manifest) if !resume_point.written_files.is_empty() => ThroughputTracker::for_files(
+			manifest,
+			manifest
+				.files
+				.keys()
+				.filter(|relative_path| !resume_point.written_files.contains(*relative_path)),
+		),
+		_ => ThroughputTracker::new(payload),
+	};
+	let on_worker_event = |event: WorkerEvent, bytes_read: usize| {
+		let (chunk_index, entry_path, entry_size) = match event {
+			WorkerEvent::FileWritten {
+				chunk_index,
+				relative_path,
+				size,
+			} => (chunk_index, relative_path, size),
+			WorkerEvent::ChunkFinished(chunk_index) => {
+				record_in_journal(&mut journal, |journal| journal.chunk_finished(chunk_index));
+				return;
+			}
+		};
+
+		let entry_manifest_path = manifest_path(&entry_path);
+		record_in_journal(&mut journal, |journal| {
+			journal.file_written(chunk_index, &entry_manifest_path)
+		});
+		throughput.entry_written(entry_size);
+
+		let new_bytes_read = bytes_read - last_bytes_read;
+		last_bytes_read = bytes_read;
+		if let Some(percentage) = progress_counter.update(new_bytes_read) {
+			let progress = throughput.progress(percentage, entry_manifest_path);
+			println!("Extraction {}% - {}", percentage, progress.summary());
 			progress_update
-				.send(Ok(ExtractionReport::InProgress { percentage }))
+				.send(Ok(ExtractionReport::InProgress(progress)))
 				.expect("Failed to send progress update - aborting extraction");
 		}
+	};
+
+	match unpack_chunks_parallel(
+		&chunks,
+		&staging_path,
+		&resume_point,
+		cancel_requested,
+		on_worker_event,
+	) {
+		Ok(true) => {}
+		Ok(false) => {
+			let _ = fs::remove_dir_all(&staging_path);
+			return Err(ExtractionFailure::Cancelled);
+		}
+		Err(e) => {
+			println!("Extraction to staging folder failed: {}", e);
+			let _ = fs::remove_dir_all(&staging_path);
+
+			// Entries rejected by the entry policy mean the archive itself is bad, not the disk or folder
+			if e.kind() == io::ErrorKind::InvalidData {
+				return Err(ExtractionFailure::Error(format!(
+					"The installer archive is corrupt or has been tampered with - please re-download the installer.\n{}",
+					e
+				)));
+			}
+
+			return Err(ExtractionFailure::Error(extraction_error_message));
+		}
+	}
+
+	// Check every extracted file against the manifest, and restore any files which are missing or modified
+	if let Err(failure) = verify_and_repair(&staging_path, payload, None, cancel_requested, progress_update) {
+		let _ = fs::remove_dir_all(&staging_path);
+		return Err(failure);
+	}
+
+	// Last chance to cancel - after this point the new extraction replaces the old one
+	if cancel_requested.load(Ordering::SeqCst) {
+		let _ = fs::remove_dir_all(&staging_path);
+		return Err(ExtractionFailure::Cancelled);
+	}
+
+	// Extraction was successful, so it no longer needs to be resumable. Write extraction lock with
+	// installer version, so we don't need to extract again unless installer's version changes
+	if let Err(e) = ExtractionJournal::remove(&staging_path) {
+		println!("Warning - Failed to remove extraction journal: {}", e);
+	}
+	write_extraction_lock(staging_path.join(EXTRACTION_LOCK_FILENAME), &payload.version);
+
+	if let Err(e) = swap_into_place(&staging_path, sub_folder_path) {
+		println!("Failed to move staging folder into place: {}", e);
+		let _ = fs::remove_dir_all(&staging_path);
+		return Err(ExtractionFailure::Error(extraction_error_message));
+	}
+
+	Ok(())
+}
+
+// Updates the current extraction using a delta payload. The unchanged and patched files are copied
+// from the current extraction into the staging folder (using hard links where possible), then the
+// delta is applied there. The result is verified against the delta's manifest before it is swapped
+// into place. Returns an error if the delta can't be applied to the current extraction.
+fn update_from_delta_payload(
+	sub_folder_path: &Path,
+	delta_path: &Path,
+	cancel_requested: &AtomicBool,
+	progress_update: &Sender<Result<ExtractionReport, String>>,
+) -> Result<(), ExtractionFailure> {
+	let delta = PayloadSource::File(delta_path.to_path_buf())
+		.load()
+		.map_err(ExtractionFailure::Error)?;
+	let manifest = delta
+		.manifest
+		.as_ref()
+		.ok_or_else(|| ExtractionFailure::Error(String::from("the delta payload has no manifest")))?;
+	let delta_manifest = manifest.delta.as_ref().ok_or_else(|| {
+		ExtractionFailure::Error(String::from("the payload is not a delta payload"))
+	})?;
+
+	let saved_git_tag_path = sub_folder_path.join(EXTRACTION_LOCK_FILENAME);
+	let installed_version = fs::read_to_string(&saved_git_tag_path)
+		.map(|version| version.trim().to_string())
+		.map_err(|e| ExtractionFailure::Error(format!("couldn't read the extraction lock: {}", e)))?;
+
+	// The delta may have been applied on a previous run
+	if installed_version == delta.version {
+		let damaged_files =
+			find_damaged_files(sub_folder_path, manifest, cancel_requested).ok_or(ExtractionFailure::Cancelled)?;
+		if damaged_files.is_empty() {
+			println!(
+				"07th-Mod Installer Loader: Files for version [{}] already extracted to [{}] - skipping extraction",
+				delta.version,
+				sub_folder_path.display()
+			);
+			return Ok(());
+		}
+
+		return Err(ExtractionFailure::Error(format!(
+			"{} installed files are missing or modified",
+			damaged_files.len()
+		)));
+	}
+
+	if installed_version != delta_manifest.base_version {
+		return Err(ExtractionFailure::Error(format!(
+			"the installed version [{}] doesn't match the delta's base version [{}]",
+			installed_version, delta_manifest.base_version
+		)));
+	}
+
+	println!(
+		"07th-Mod Installer Loader: Updating [{}] from version [{}] to [{}] using {}: {} added, {} patched, {} removed",
+		sub_folder_path.display(),
+		delta_manifest.base_version,
+		delta.version,
+		delta.description,
+		delta_manifest.added.len(),
+		delta_manifest.patched.len(),
+		delta_manifest.removed.len()
+	);
+
+	let staging_path = sibling_path(sub_folder_path, STAGING_SUFFIX);
+	if staging_path.exists() {
+		fs::remove_dir_all(&staging_path).map_err(|e| {
+			ExtractionFailure::Error(format!("couldn't remove old staging folder: {}", e))
+		})?;
+	}
+
+	let result = apply_delta_in_staging(
+		sub_folder_path,
+		&staging_path,
+		&delta,
+		cancel_requested,
+		progress_update,
+	)
+	.and_then(|()| {
+		// Last chance to cancel - after this point the new extraction replaces the old one
+		if cancel_requested.load(Ordering::SeqCst) {
+			return Err(ExtractionFailure::Cancelled);
+		}
+
+		// Files removed by the delta weren't copied into the staging folder, and the swap only carries
+		// over the CARRIED_OVER_FILES, so they are deleted along with the previous extraction
+		write_extraction_lock(staging_path.join(EXTRACTION_LOCK_FILENAME), &delta.version);
+		swap_into_place(&staging_path, sub_folder_path).map_err(|e| {
+			ExtractionFailure::Error(format!("couldn't move staging folder into place: {}", e))
+		})
 	});
 
-	let xz_reader = XzDecoder::new(intermediate_reader);
+	if result.is_err() {
+		let _ = fs::remove_dir_all(&staging_path);
+	}
+
+	result
+}
 
-	if let Err(_e) = Archive::new(xz_reader).unpack(sub_folder_path) {
-		let error_message = format!("Can't extract files. Make sure all installers are closed, you have enough disk space, and try again.\n\
-Also check permissions to write to the folder (try moving installer to a different folder)\n\
-[{}\\{}]\n\
-You can also try 'Run as Administrator', but the installer may not work correctly.", cwd, sub_folder_path.display());
-		progress_update
-			.send(Err(error_message))
-			.expect("Failed to send error progress update");
-	} else {
-		// Extraction was successful. Write extraction lock with installer version,
-		// so we don't need to extract again unless installer's version changes
-		write_extraction_lock(&saved_git_tag_path);
-		progress_update
-			.send(Ok(ExtractionReport::Finished))
-			.expect("Failed to send progress update - aborting extraction");
-		println!("Extraction Complete.");
+fn apply_delta_in_staging(
+	sub_folder_path: &Path,
+	staging_path: &Path,
+	delta: &Payload,
+	cancel_requested: &AtomicBool,
+	progress_update: &Sender<Result<ExtractionReport, String>>,
+) -> Result<(), ExtractionFailure> {
+	let io_error = |e: io::Error| ExtractionFailure::Error(e.to_string());
+	// Checked by the caller
+	let manifest = delta.manifest.as_ref().unwrap();
+	let delta_manifest = manifest.delta.as_ref().unwrap();
+
+	let added: HashSet<&str> = delta_manifest.added.iter().map(String::as_str).collect();
+	let patched: HashSet<&str> = delta_manifest.patched.iter().map(String::as_str).collect();
+
+	// Files removed by the delta aren't in the manifest, so they are left behind
+	for relative_path in manifest.files.keys().filter(|path| !added.contains(path.as_str())) {
+		link_or_copy(&sub_folder_path.join(relative_path), &staging_path.join(relative_path))
+			.map_err(|e| {
+				ExtractionFailure::Error(format!(
+					"couldn't copy [{}] from the current extraction: {}",
+					relative_path, e
+				))
+			})?;
+	}
+
+	let root = extraction_root(staging_path).map_err(io_error)?;
+	let mut throughput = ThroughputTracker::for_files(
+		manifest,
+		delta_manifest.added.iter().chain(delta_manifest.patched.iter()),
+	);
+
+	for chunk in delta.chunks().map_err(ExtractionFailure::Error)? {
+		let decoder = CompressionFormat::detect(chunk)
+			.and_then(|format| format.decoder(chunk))
+			.map_err(ExtractionFailure::Error)?;
+		let mut archive = Archive::new(decoder);
+
+		for entry in archive.entries().map_err(io_error)? {
+			if cancel_requested.load(Ordering::SeqCst) {
+				return Err(ExtractionFailure::Cancelled);
+			}
+
+			let mut entry = entry.map_err(io_error)?;
+			let validated_entry = validate_archive_entry(&entry).map_err(io_error)?;
+			let relative_path = match &validated_entry {
+				ValidatedEntry::File(relative_path) => relative_path.clone(),
+				_ => {
+					unpack_validated_entry(&mut entry, &root, &validated_entry).map_err(io_error)?;
+					continue;
+				}
+			};
+
+			let entry_manifest_path = manifest_path(&relative_path);
+			let destination = root.join(&relative_path);
+
+			// Files are always removed before they are written, as they may be hard linked to the current extraction
+			if patched.contains(entry_manifest_path.as_str()) {
+				let mut patch = Vec::new();
+				io::Read::read_to_end(&mut entry, &mut patch).map_err(io_error)?;
+				let base = fs::read(&destination).map_err(io_error)?;
+				let base_permissions = fs::metadata(&destination).map_err(io_error)?.permissions();
+				let patched_file = binary_patch::apply_patch(&base, &patch).map_err(|e| {
+					ExtractionFailure::Error(format!("couldn't patch [{}]: {}", entry_manifest_path, e))
+				})?;
+				fs::remove_file(&destination).map_err(io_error)?;
+				fs::write(&destination, patched_file).map_err(io_error)?;
+				// Patched files keep the permissions (e.g. the executable bit) of the file they replace
+				fs::set_permissions(&destination, base_permissions).map_err(io_error)?;
+			} else if added.contains(entry_manifest_path.as_str()) {
+				let _ = fs::remove_file(&destination);
+				unpack_validated_entry(&mut entry, &root, &validated_entry).map_err(io_error)?;
+			} else {
+				return Err(ExtractionFailure::Error(format!(
+					"the delta contains [{}], which isn't listed as added or patched",
+					entry_manifest_path
+				)));
+			}
+
+			throughput.entry_written(manifest.files.get(&entry_manifest_path).map_or(0, |entry| entry.size));
+			let percentage = if throughput.entries_total > 0 {
+				throughput.entries_done * 100 / throughput.entries_total
+			} else {
+				100
+			};
+			progress_update
+				.send(Ok(ExtractionReport::InProgress(
+					throughput.progress(percentage, entry_manifest_path),
+				)))
+				.expect("Failed to send progress update - aborting extraction");
+		}
+	}
+
+	let damaged_files =
+		find_damaged_files(staging_path, manifest, cancel_requested).ok_or(ExtractionFailure::Cancelled)?;
+	if !damaged_files.is_empty() {
+		return Err(ExtractionFailure::Error(format!(
+			"{} files don't match the delta's manifest after applying it: {:?}",
+			damaged_files.len(),
+			damaged_files
+		)));
 	}
+
+	Ok(())
 }
 
-fn write_extraction_lock<P: AsRef<Path>>(saved_git_tag_path: P) {
-	fs::write(saved_git_tag_path, version::travis_tag())
-		.unwrap_or_else(|e| println!("Warning - Failed to write loader extraction lock: {:?}", e))
+// Hard links 'from' to 'to', or copies it if hard links aren't supported. Parent folders are created as needed.
+fn link_or_copy(from: &Path, to: &Path) -> io::Result<()> {
+	if let Some(parent) = to.parent() {
+		fs::create_dir_all(parent)?;
+	}
+
+	fs::hard_link(from, to).or_else(|_| fs::copy(from, to).map(|_| ()))
 }
 
-struct ProgressCounter {
-	bytes_so_far: usize,
-	last_printed: usize,
-	// The last byte count when the bytes were printed
-	data_length: usize,
-	print_interval: usize,
+// The parts of an interrupted extraction which don't need to be extracted again
+#[derive(Default)]
+struct ResumePoint {
+	finished_chunks: HashSet<usize>,
+	// Manifest paths of the files which were written, and still match the manifest
+	written_files: HashSet<String>,
 }
 
-impl ProgressCounter {
-	pub fn new(data_length: usize, print_interval: usize) -> ProgressCounter {
-		ProgressCounter {
-			bytes_so_far: 0,
-			last_printed: 0,
-			data_length,
-			print_interval,
+// Reads the extraction journal left in the staging folder by an interrupted extraction of this
+// payload. Each file in the journal is verified against the manifest - if it is missing or modified,
+// it is extracted again, along with the rest of the chunk it came from. Returns None if there is
+// nothing to resume, if the payload has no manifest to verify the files with, or if cancellation was requested.
+fn read_resume_point(
+	staging_path: &Path,
+	payload_id: &str,
+	payload: &Payload,
+	cancel_requested: &AtomicBool,
+) -> Option<ResumePoint> {
+	let manifest = payload.manifest.as_ref()?;
+	let journal = ExtractionJournal::read(staging_path, payload_id)?;
+
+	let mut resume_point = ResumePoint {
+		finished_chunks: journal.finished_chunks,
+		written_files: HashSet::new(),
+	};
+
+	for (relative_path, chunk_index) in journal.files {
+		if cancel_requested.load(Ordering::SeqCst) {
+			return None;
 		}
-	}
 
-	pub fn update(&mut self, read: usize) -> Option<usize> {
-		self.bytes_so_far += read;
+		let intact = manifest.files.get(&relative_path).map_or(false, |entry| {
+			file_matches_manifest_entry(&staging_path.join(&relative_path), entry, cancel_requested)
+		});
 
-		if (self.bytes_so_far - self.last_printed > self.print_interval)
-			|| (self.bytes_so_far == self.data_length)
-		{
-			self.last_printed = self.bytes_so_far;
-			Some(self.bytes_so_far * 100 / self.data_length)
+		if intact {
+			resume_point.written_files.insert(relative_path);
 		} else {
-			None
+			resume_point.finished_chunks.remove(&chunk_index);
+		}
+	}
+
+	Some(resume_point)
+}
+
+// Runs 'record' on the journal. If it fails, the journal is dropped, as any later records could be
+// read back without the one which failed.
+fn record_in_journal<F: FnOnce(&mut ExtractionJournal) -> io::Result<()>>(
+	journal: &mut Option<ExtractionJournal>,
+	record: F,
+) {
+	if let Some(journal_file) = journal {
+		if let Err(e) = record(journal_file) {
+			println!("Warning - Failed to write extraction journal, extraction won't be resumable: {}", e);
+			*journal = None;
+		}
+	}
+}
+
+// Sent by the extraction workers as they make progress
+enum WorkerEvent {
+	FileWritten {
+		chunk_index: usize,
+		relative_path: PathBuf,
+		size: u64,
+	},
+	// Every entry of the chunk has been written
+	ChunkFinished(usize),
+}
+
+// Decompresses and extracts the chunks of the archive on a pool of worker threads, one chunk at a time
+// per worker. 'on_worker_event' is called on the calling thread as each file is written and each chunk
+// is finished, along with the total number of compressed bytes read so far, so progress is always
+// reported in order. Chunks and files in 'resume_point' are skipped.
+// Returns Ok(false) if the extraction was cancelled. If any worker fails, the other workers are
+// stopped and the error is returned.
+fn unpack_chunks_parallel<F: FnMut(WorkerEvent, usize)>(
+	chunks: &[&[u8]],
+	destination: &Path,
+	resume_point: &ResumePoint,
+	cancel_requested: &AtomicBool,
+	mut on_worker_event: F,
+) -> io::Result<bool> {
+	fs::create_dir_all(destination)?;
+
+	// Read the umask before any workers start creating files
+	#[cfg(unix)]
+	process_umask();
+
+	let worker_count = thread::available_parallelism()
+		.map(|count| count.get())
+		.unwrap_or(1)
+		.min(chunks.len())
+		.max(1);
+	println!("Extracting {} chunk(s) using {} worker(s)", chunks.len(), worker_count);
+
+	let next_chunk = AtomicUsize::new(0);
+	let bytes_read = AtomicUsize::new(0);
+	// Set when cancellation is requested, or when any worker fails, so that every worker stops
+	let stop_workers = AtomicBool::new(false);
+
+	thread::scope(|scope| {
+		let (event_sender, event_receiver) = mpsc::channel();
+
+		let workers: Vec<_> = (0..worker_count)
+			.map(|_| {
+				let event_sender = event_sender.clone();
+				let (next_chunk, bytes_read, stop_workers) = (&next_chunk, &bytes_read, &stop_workers);
+				scope.spawn(move || -> io::Result<bool> {
+					loop {
+						let chunk_index = next_chunk.fetch_add(1, Ordering::SeqCst);
+						let chunk = match chunks.get(chunk_index) {
+							Some(chunk) => chunk,
+							None => return Ok(true),
+						};
+
+						if resume_point.finished_chunks.contains(&chunk_index) {
+							bytes_read.fetch_add(chunk.len(), Ordering::SeqCst);
+							continue;
+						}
+
+						match unpack_chunk(
+							chunk_index,
+							chunk,
+							destination,
+							&resume_point.written_files,
+							bytes_read,
+							stop_workers,
+							&event_sender,
+						) {
+							// The receiver is only dropped after all workers have finished
+							Ok(true) => {
+								let _ = event_sender.send(WorkerEvent::ChunkFinished(chunk_index));
+							}
+							Ok(false) => return Ok(false),
+							Err(e) => {
+								stop_workers.store(true, Ordering::SeqCst);
+								return Err(e);
+							}
+						}
+					}
+				})
+			})
+			.collect();
+
+		// Only the workers hold senders now, so the loop below ends once every worker has finished
+		drop(event_sender);
+
+		loop {
+			match event_receiver.recv_timeout(Duration::from_millis(100)) {
+				Ok(event) => on_worker_event(event, bytes_read.load(Ordering::SeqCst)),
+				Err(mpsc::RecvTimeoutError::Timeout) => {}
+				Err(mpsc::RecvTimeoutError::Disconnected) => break,
+			}
+
+			if cancel_requested.load(Ordering::SeqCst) {
+				stop_workers.store(true, Ordering::SeqCst);
+			}
+		}
+
+		// Report the first error from any worker, rather than the workers it caused to stop
+		let mut completed = true;
+		for worker in workers {
+			match worker.join() {
+				Ok(Ok(finished)) => completed &= finished,
+				Ok(Err(e)) => return Err(e),
+				Err(_) => return Err(io::Error::new(io::ErrorKind::Other, "Extraction worker panicked")),
+			}
+		}
+
+		Ok(completed && !cancel_requested.load(Ordering::SeqCst))
+	})
+}
+
+// Decompresses and extracts a single chunk of the archive. 'bytes_read' is increased as the
+// compressed chunk is read, and each file written is sent to 'event_sender'.
+fn unpack_chunk(
+	chunk_index: usize,
+	chunk: &[u8],
+	destination: &Path,
+	skip_files: &HashSet<String>,
+	bytes_read: &AtomicUsize,
+	stop_workers: &AtomicBool,
+	event_sender: &Sender<WorkerEvent>,
+) -> io::Result<bool> {
+	let progress_reader = ProgressReader::new(chunk, |progress_bytes: usize| {
+		bytes_read.fetch_add(progress_bytes, Ordering::SeqCst);
+	});
+
+	let decoder = CompressionFormat::detect(chunk)
+		.and_then(|format| format.decoder(progress_reader))
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+	unpack_cancellable(
+		Archive::new(decoder),
+		destination,
+		skip_files,
+		stop_workers,
+		|relative_path, size| {
+			// The receiver is only dropped after all workers have finished
+			let _ = event_sender.send(WorkerEvent::FileWritten {
+				chunk_index,
+				relative_path: relative_path.to_path_buf(),
+				size,
+			});
+		},
+	)
+}
+
+// Extracts each entry of the archive into 'destination', checking for cancellation between entries.
+// 'on_file_written' is called with the path and size of each file after it is extracted.
+// Files whose manifest path is in 'skip_files' were already extracted, so they are skipped.
+// Returns Ok(false) if the extraction was cancelled.
+// Every entry is checked against the entry policy before extraction - if any entry is rejected,
+// an error of kind io::ErrorKind::InvalidData is returned.
+fn unpack_cancellable<R: io::Read, F: FnMut(&Path, u64)>(
+	mut archive: Archive<R>,
+	destination: &Path,
+	skip_files: &HashSet<String>,
+	cancel_requested: &AtomicBool,
+	mut on_file_written: F,
+) -> io::Result<bool> {
+	fs::create_dir_all(destination)?;
+	let root = extraction_root(destination)?;
+
+	for entry in archive.entries()? {
+		if cancel_requested.load(Ordering::SeqCst) {
+			return Ok(false);
+		}
+
+		let mut entry = entry?;
+		let validated_entry = validate_archive_entry(&entry)?;
+
+		if let ValidatedEntry::File(relative_path) = &validated_entry {
+			if !skip_files.is_empty() && skip_files.contains(&manifest_path(relative_path)) {
+				continue;
+			}
+		}
+
+		unpack_validated_entry(&mut entry, &root, &validated_entry)?;
+
+		if let ValidatedEntry::File(relative_path) = &validated_entry {
+			on_file_written(relative_path, entry.size());
+		}
+	}
+
+	Ok(true)
+}
+
+// Why an archive entry was rejected by the entry policy
+#[derive(Debug, PartialEq)]
+enum EntryPolicyViolation {
+	AbsolutePath(String),
+	ParentDirectory(String),
+	EmptyPath,
+	MissingLinkTarget(String),
+	LinkEscapesRoot { path: String, target: String },
+	// The entry would be written through a symlink which was already extracted, to outside the root
+	PathEscapesRoot(String),
+	UnsupportedEntryType { path: String, entry_type: String },
+}
+
+impl fmt::Display for EntryPolicyViolation {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			EntryPolicyViolation::AbsolutePath(path) => {
+				write!(f, "Archive entry [{}] has an absolute path", path)
+			}
+			EntryPolicyViolation::ParentDirectory(path) => {
+				write!(f, "Archive entry [{}] contains a '..' path component", path)
+			}
+			EntryPolicyViolation::EmptyPath => write!(f, "Archive entry has an empty path"),
+			EntryPolicyViolation::MissingLinkTarget(path) => {
+				write!(f, "Archive link [{}] has no link target", path)
+			}
+			EntryPolicyViolation::LinkEscapesRoot { path, target } => write!(
+				f,
+				"Archive link [{}] points to [{}], which is outside the extraction folder",
+				path, target
+			),
+			EntryPolicyViolation::PathEscapesRoot(path) => write!(
+				f,
+				"Archive entry [{}] is inside a symlink which points outside the extraction folder",
+				path
+			),
+			EntryPolicyViolation::UnsupportedEntryType { path, entry_type } => write!(
+				f,
+				"Archive entry [{}] has unsupported type [{}]",
+				path, entry_type
+			),
+		}
+	}
+}
+
+impl From<EntryPolicyViolation> for io::Error {
+	fn from(violation: EntryPolicyViolation) -> io::Error {
+		io::Error::new(io::ErrorKind::InvalidData, violation.to_string())
+	}
+}
+
+// An archive entry which has been accepted by the entry policy. All paths are relative to the
+// extraction root, contain only normal components, and are Unicode NFC normalized.
+#[derive(Debug, PartialEq)]
+enum ValidatedEntry {
+	Directory(PathBuf),
+	File(PathBuf),
+	// The target is stored as it appears in the archive (relative to the symlink's folder)
+	Symlink { path: PathBuf, target: PathBuf },
+	// The target is relative to the extraction root
+	HardLink { path: PathBuf, target: PathBuf },
+	// Metadata-only entries (pax global headers, or the archive root folder itself)
+	Skip,
+}
+
+fn validate_archive_entry<R: io::Read>(entry: &Entry<R>) -> io::Result<ValidatedEntry> {
+	let path = entry.path()?;
+	let link_name = entry.link_name()?;
+	Ok(validate_entry(
+		&path,
+		entry.header().entry_type(),
+		link_name.as_deref(),
+	)?)
+}
+
+// The archive entry policy. Rejects absolute paths, '..' components, links which point outside the
+// extraction root, and device/FIFO entries. The payload is normally embedded in the .exe, but this
+// makes sure a modified or externally loaded payload can never write outside the extraction folder.
+fn validate_entry(
+	path: &Path,
+	entry_type: EntryType,
+	link_name: Option<&Path>,
+) -> Result<ValidatedEntry, EntryPolicyViolation> {
+	if entry_type.is_pax_global_extensions() {
+		return Ok(ValidatedEntry::Skip);
+	}
+
+	let relative_path = normalize_entry_path(path)?;
+
+	if entry_type.is_dir() {
+		// Archives created from '.' contain an entry for the root folder itself
+		if relative_path.as_os_str().is_empty() {
+			return Ok(ValidatedEntry::Skip);
+		}
+		return Ok(ValidatedEntry::Directory(relative_path));
+	}
+
+	if relative_path.as_os_str().is_empty() {
+		return Err(EntryPolicyViolation::EmptyPath);
+	}
+
+	let display_path = path.display().to_string();
+
+	if entry_type.is_symlink() || entry_type.is_hard_link() {
+		let target = match link_name {
+			Some(target) if target.components().next().is_some() => target,
+			_ => return Err(EntryPolicyViolation::MissingLinkTarget(display_path)),
+		};
+
+		let escapes_root = || EntryPolicyViolation::LinkEscapesRoot {
+			path: display_path.clone(),
+			target: target.display().to_string(),
+		};
+
+		if entry_type.is_hard_link() {
+			// Hard link targets are paths of other entries in the archive
+			let target = normalize_entry_path(target).map_err(|_| escapes_root())?;
+			return Ok(ValidatedEntry::HardLink {
+				path: relative_path,
+				target,
+			});
+		}
+
+		// Symlink targets are relative to the folder containing the symlink.
+		// Resolve the target lexically, and make sure it never leaves the extraction root.
+		let mut resolved: Vec<Component> = relative_path
+			.parent()
+			.map(|parent| parent.components().collect())
+			.unwrap_or_default();
+
+		for component in target.components() {
+			match component {
+				Component::Prefix(_) | Component::RootDir => return Err(escapes_root()),
+				Component::ParentDir => {
+					if resolved.pop().is_none() {
+						return Err(escapes_root());
+					}
+				}
+				Component::CurDir => {}
+				Component::Normal(_) => resolved.push(component),
+			}
+		}
+
+		return Ok(ValidatedEntry::Symlink {
+			path: relative_path,
+			target: target.to_path_buf(),
+		});
+	}
+
+	if entry_type.is_file() || entry_type == EntryType::Continuous {
+		return Ok(ValidatedEntry::File(relative_path));
+	}
+
+	// Block/character devices, FIFOs, sparse files and any unknown entry types are never allowed
+	Err(EntryPolicyViolation::UnsupportedEntryType {
+		path: display_path,
+		entry_type: format!("{:?}", entry_type),
+	})
+}
+
+// Converts an archive path to a relative path containing only normal components.
+// '.' components are dropped, and each component is Unicode NFC normalized so that the same
+// filename always maps to the same file on disk, however the archive was created.
+fn normalize_entry_path(path: &Path) -> Result<PathBuf, EntryPolicyViolation> {
+	let mut normalized = PathBuf::new();
+
+	for component in path.components() {
+		match component {
+			Component::Prefix(_) | Component::RootDir => {
+				return Err(EntryPolicyViolation::AbsolutePath(path.display().to_string()))
+			}
+			Component::ParentDir => {
+				return Err(EntryPolicyViolation::ParentDirectory(path.display().to_string()))
+			}
+			Component::CurDir => {}
+			Component::Normal(part) => match part.to_str() {
+				Some(part) => normalized.push(part.nfc().collect::<String>()),
+				None => normalized.push(part),
+			},
+		}
+	}
+
+	Ok(normalized)
+}
+
+// Returns the absolute path entries should be extracted under.
+// On Windows, canonicalize() returns an extended-length ('\\?\') path, which allows extracting
+// files whose full path is longer than the 260 character MAX_PATH limit.
+fn extraction_root(destination: &Path) -> io::Result<PathBuf> {
+	destination.canonicalize()
+}
+
+fn unpack_validated_entry<R: io::Read>(
+	entry: &mut Entry<R>,
+	root: &Path,
+	validated_entry: &ValidatedEntry,
+) -> io::Result<()> {
+	let relative_path = match validated_entry {
+		ValidatedEntry::Skip => return Ok(()),
+		ValidatedEntry::Directory(relative_path)
+		| ValidatedEntry::File(relative_path)
+		| ValidatedEntry::Symlink {
+			path: relative_path,
+			..
+		}
+		| ValidatedEntry::HardLink {
+			path: relative_path,
+			..
+		} => relative_path,
+	};
+
+	// The final component isn't followed, as files and links replace any existing symlink
+	let parent_folder = relative_path.parent().unwrap_or_else(|| Path::new(""));
+	if !resolves_inside_root(root, parent_folder) {
+		return Err(EntryPolicyViolation::PathEscapesRoot(relative_path.display().to_string()).into());
+	}
+
+	let destination = root.join(relative_path);
+	if let ValidatedEntry::Directory(_) = validated_entry {
+		return fs::create_dir_all(destination);
+	}
+
+	if let Some(parent) = destination.parent() {
+		fs::create_dir_all(parent)?;
+	}
+
+	// Hard link targets are relative to the root, and symlink targets to the link's folder
+	let link_target = match validated_entry {
+		ValidatedEntry::HardLink { target, .. } => Some((target, target.to_path_buf())),
+		ValidatedEntry::Symlink { target, .. } => Some((target, parent_folder.join(target))),
+		_ => None,
+	};
+
+	if let Some((target, target_from_root)) = link_target {
+		if !resolves_inside_root(root, &target_from_root) {
+			return Err(EntryPolicyViolation::LinkEscapesRoot {
+				path: relative_path.display().to_string(),
+				target: target.display().to_string(),
+			}
+			.into());
+		}
+	}
+
+	// tar would create hard links relative to the current directory, so they are created manually
+	if let ValidatedEntry::HardLink { target, .. } = validated_entry {
+		let _ = fs::remove_file(&destination);
+		return fs::hard_link(root.join(target), &destination);
+	}
+
+	entry.unpack(&destination)?;
+
+	if let ValidatedEntry::File(_) = validated_entry {
+		apply_mode_policy(&destination, entry.header().mode()?)?;
+	}
+
+	Ok(())
+}
+
+// Resolves 'relative_path' against the entries already extracted under 'root', following any symlinks
+// in it, and returns true if it stays inside the root. validate_entry() checks each link target on its
+// own, but a chain of links can still escape: 'b' -> '.' then 'c' -> 'b/..' is the root's parent.
+// Links extracted by other workers, or by an interrupted extraction, are also followed.
+// '..' is only allowed after a component which already exists, as otherwise a later entry could turn
+// that component into a symlink and change where the path points.
+fn resolves_inside_root(root: &Path, relative_path: &Path) -> bool {
+	let mut resolved = root.to_path_buf();
+
+	for component in relative_path.components() {
+		match component {
+			Component::CurDir => continue,
+			Component::ParentDir => {
+				if fs::symlink_metadata(&resolved).is_err() {
+					return false;
+				}
+				resolved.pop();
+			}
+			Component::Normal(part) => {
+				resolved.push(part);
+				let is_symlink = fs::symlink_metadata(&resolved)
+					.map(|metadata| metadata.file_type().is_symlink())
+					.unwrap_or(false);
+				if is_symlink {
+					// Links which don't resolve yet (because their target hasn't been extracted) are rejected
+					match resolved.canonicalize() {
+						Ok(target) => resolved = target,
+						Err(_) => return false,
+					}
+				}
+			}
+			Component::Prefix(_) | Component::RootDir => return false,
+		}
+
+		if !resolved.starts_with(root) {
+			return false;
+		}
+	}
+
+	true
+}
+
+// Only the read, write and execute bits are kept from the tar headers - setuid, setgid and the
+// sticky bit are never set, as the archive shouldn't be able to grant extra privileges.
+#[cfg(unix)]
+const PRESERVED_MODE_BITS: u32 = 0o777;
+
+// Sets the permissions of an extracted file from its tar header mode, masked by the process umask,
+// so executables (like the bundled python) stay executable.
+#[cfg(unix)]
+fn apply_mode_policy(path: &Path, header_mode: u32) -> io::Result<()> {
+	use std::os::unix::fs::PermissionsExt;
+	let mode = extracted_file_mode(header_mode, process_umask());
+	fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(unix)]
+fn extracted_file_mode(header_mode: u32, umask: u32) -> u32 {
+	header_mode & PRESERVED_MODE_BITS & !umask
+}
+
+// Windows has no mode bits - files keep the default permissions of the folder they're extracted to
+#[cfg(not(unix))]
+fn apply_mode_policy(_path: &Path, _header_mode: u32) -> io::Result<()> {
+	Ok(())
+}
+
+// The umask can only be read by changing it, so it is read once and cached, rather than
+// changing it while other threads may be creating files.
+#[cfg(unix)]
+fn process_umask() -> u32 {
+	use std::sync::atomic::AtomicU32;
+	use std::sync::Once;
+
+	static READ_UMASK: Once = Once::new();
+	static UMASK: AtomicU32 = AtomicU32::new(0o022);
+
+	READ_UMASK.call_once(|| {
+		// SAFETY: umask() always succeeds, and the original value is restored immediately
+		let umask = unsafe {
+			let umask = libc::umask(0o022);
+			libc::umask(umask);
+			umask
+		};
+		// mode_t is a u16 on macOS
+		#[allow(clippy::useless_conversion)]
+		UMASK.store(u32::from(umask), Ordering::SeqCst);
+	});
+
+	UMASK.load(Ordering::SeqCst)
+}
+
+// Replaces the sub folder with the staging folder. If the sub folder already exists, it is first
+// renamed to a backup folder, which is restored if the staging folder can't be moved into place.
+fn swap_into_place(staging_path: &Path, sub_folder_path: &Path) -> io::Result<()> {
+	let backup_path = sibling_path(sub_folder_path, BACKUP_SUFFIX);
+
+	if !sub_folder_path.exists() {
+		return fs::rename(staging_path, sub_folder_path);
+	}
+
+	if backup_path.exists() {
+		fs::remove_dir_all(&backup_path)?;
+	}
+
+	fs::rename(sub_folder_path, &backup_path)?;
+
+	if let Err(e) = fs::rename(staging_path, sub_folder_path) {
+		// Roll back to the previous extraction
+		fs::rename(&backup_path, sub_folder_path)?;
+		return Err(e);
+	}
+
+	// Logs, webview data etc. are moved from the previous extraction into the new one.
+	// The backup is only deleted if all of them could be moved.
+	match carry_over_user_files(&backup_path, sub_folder_path) {
+		Ok(()) => {
+			if let Err(e) = fs::remove_dir_all(&backup_path) {
+				println!("Warning - Failed to remove previous extraction {:?}: {}", backup_path, e);
+			}
+		}
+		Err(e) => println!(
+			"Warning - Failed to move some files from previous extraction {:?} - it will not be deleted: {}",
+			backup_path, e
+		),
+	}
+
+	Ok(())
+}
+
+// Moves the CARRIED_OVER_FILES which exist in 'from' but not in 'to'
+fn carry_over_user_files(from: &Path, to: &Path) -> io::Result<()> {
+	for file_name in CARRIED_OVER_FILES {
+		let source = from.join(file_name);
+		let destination = to.join(file_name);
+		if fs::symlink_metadata(&source).is_ok() && !destination.exists() {
+			fs::rename(source, destination)?;
+		}
+	}
+
+	Ok(())
+}
+
+// If the program was closed partway through swap_into_place(), the sub folder may be missing while the
+// backup still exists. In that case the backup is restored, so the previous extraction can still be used.
+fn recover_interrupted_swap(sub_folder_path: &Path) {
+	let backup_path = sibling_path(sub_folder_path, BACKUP_SUFFIX);
+
+	if backup_path.exists() && !sub_folder_path.exists() {
+		println!("Restoring previous extraction from {:?}", backup_path);
+		if let Err(e) = fs::rename(&backup_path, sub_folder_path) {
+			println!("Warning - Failed to restore previous extraction: {}", e);
+		}
+	}
+}
+
+// Returns a path next to 'path', with 'suffix' appended to its filename
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+	let mut file_name = path
+		.file_name()
+		.map(|file_name| file_name.to_os_string())
+		.unwrap_or_default();
+	file_name.push(suffix);
+	path.with_file_name(file_name)
+}
+
+// Verifies the extracted files against the manifest. If any files are damaged, only those files are
+// extracted again from the archive, then verified a second time.
+// If 'extracted_at' is set, the first check only compares each file's size and modification time
+// (see find_modified_files()) instead of hashing it. Restored files are always hashed.
+fn verify_and_repair(
+	sub_folder_path: &Path,
+	payload: &Payload,
+	extracted_at: Option<SystemTime>,
+	cancel_requested: &AtomicBool,
+	progress_update: &Sender<Result<ExtractionReport, String>>,
+) -> Result<(), ExtractionFailure> {
+	let manifest = match &payload.manifest {
+		Some(manifest) => manifest,
+		None => {
+			println!("Warning: {} has no manifest - extracted files will not be verified", payload.description);
+			return Ok(());
+		}
+	};
+
+	let damaged_files = match extracted_at {
+		Some(extracted_at) => find_modified_files(sub_folder_path, manifest, extracted_at, cancel_requested),
+		None => find_damaged_files(sub_folder_path, manifest, cancel_requested),
+	}
+	.ok_or(ExtractionFailure::Cancelled)?;
+	if damaged_files.is_empty() {
+		return Ok(());
+	}
+
+	println!(
+		"Warning: {} extracted files are missing or modified - restoring them: {:?}",
+		damaged_files.len(),
+		damaged_files
+	);
+	progress_update
+		.send(Ok(ExtractionReport::Repairing {
+			damaged_files: damaged_files.clone(),
+		}))
+		.expect("Failed to send progress update - aborting extraction");
+
+	let damaged_set: HashSet<&str> = damaged_files.iter().map(|path| path.as_str()).collect();
+	if let Err(e) = restore_files(sub_folder_path, payload, &damaged_set, cancel_requested) {
+		println!("Error while restoring damaged files: {}", e);
+	}
+
+	let still_damaged =
+		find_damaged_files(sub_folder_path, manifest, cancel_requested).ok_or(ExtractionFailure::Cancelled)?;
+	if still_damaged.is_empty() {
+		println!("Damaged files were restored successfully");
+		return Ok(());
+	}
+
+	Err(ExtractionFailure::Error(format!(
+		"The following installer files are missing or modified, and could not be restored:\n{}\n\n\
+This is usually caused by antivirus software deleting or quarantining the files.\n\
+Please add an exception for the folder [{}] to your antivirus, then try again.",
+		still_damaged.join("\n"),
+		sub_folder_path.display()
+	)))
+}
+
+// Returns the manifest path of each file which is missing, or whose size or hash doesn't match the manifest.
+// Returns None if cancellation was requested before every file was checked.
+fn find_damaged_files(
+	sub_folder_path: &Path,
+	manifest: &PayloadManifest,
+	cancel_requested: &AtomicBool,
+) -> Option<Vec<String>> {
+	files_failing_check(manifest, cancel_requested, |relative_path, entry| {
+		file_matches_manifest_entry(&sub_folder_path.join(relative_path), entry, cancel_requested)
+	})
+}
+
+// Like find_damaged_files(), but only hashes files which were modified after 'extracted_at'. Extracted
+// files keep the modification time from the archive, and the extraction lock is written after they are
+// verified, so normally only the file sizes need to be checked.
+fn find_modified_files(
+	sub_folder_path: &Path,
+	manifest: &PayloadManifest,
+	extracted_at: SystemTime,
+	cancel_requested: &AtomicBool,
+) -> Option<Vec<String>> {
+	files_failing_check(manifest, cancel_requested, |relative_path, entry| {
+		file_unmodified_since(&sub_folder_path.join(relative_path), entry, extracted_at, cancel_requested)
+	})
+}
+
+// Returns the manifest path of each file for which 'check' returns false, or None if cancellation was
+// requested. A file whose check was cancelled partway through may look damaged, so cancellation is
+// checked again once every file has been checked.
+fn files_failing_check<F: Fn(&str, &ManifestEntry) -> bool>(
+	manifest: &PayloadManifest,
+	cancel_requested: &AtomicBool,
+	check: F,
+) -> Option<Vec<String>> {
+	let mut failed_files = Vec::new();
+	for (relative_path, entry) in &manifest.files {
+		if cancel_requested.load(Ordering::SeqCst) {
+			return None;
+		}
+
+		if !check(relative_path, entry) {
+			failed_files.push(relative_path.clone());
+		}
+	}
+
+	if cancel_requested.load(Ordering::SeqCst) {
+		return None;
+	}
+
+	Some(failed_files)
+}
+
+fn file_unmodified_since(
+	path: &Path,
+	entry: &ManifestEntry,
+	extracted_at: SystemTime,
+	cancel_requested: &AtomicBool,
+) -> bool {
+	match fs::metadata(path) {
+		Ok(metadata) if metadata.is_file() && metadata.len() == entry.size => {
+			// The archive's modification times may be in the future if the user's clock is wrong, so
+			// newer files are hashed rather than assumed to be modified
+			match metadata.modified() {
+				Ok(modified) if modified <= extracted_at => true,
+				_ => file_matches_manifest_entry(path, entry, cancel_requested),
+			}
+		}
+		_ => false,
+	}
+}
+
+fn file_matches_manifest_entry(path: &Path, entry: &ManifestEntry, cancel_requested: &AtomicBool) -> bool {
+	match fs::metadata(path) {
+		Ok(metadata) if metadata.is_file() && metadata.len() == entry.size => {}
+		_ => return false,
+	}
+
+	match sha256_file(path, cancel_requested) {
+		Ok(hash) => hash.eq_ignore_ascii_case(&entry.sha256),
+		Err(_) => false,
+	}
+}
+
+// Hashes the file a block at a time, so large files can be cancelled partway through
+fn sha256_file(path: &Path, cancel_requested: &AtomicBool) -> io::Result<String> {
+	let mut file = fs::File::open(path)?;
+	let mut hasher = Sha256::new();
+	let mut buffer = vec![0; 1024 * 1024];
+	loop {
+		if cancel_requested.load(Ordering::SeqCst) {
+			return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+		}
+
+		match io::Read::read(&mut file, &mut buffer) {
+			Ok(0) => break,
+			Ok(read) => hasher.update(&buffer[..read]),
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Extracts only the archive entries whose paths are listed in 'files_to_restore'
+fn restore_files(
+	sub_folder_path: &Path,
+	payload: &Payload,
+	files_to_restore: &HashSet<&str>,
+	cancel_requested: &AtomicBool,
+) -> io::Result<()> {
+	let root = extraction_root(sub_folder_path)?;
+	let invalid_data = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+	for chunk in payload.chunks().map_err(invalid_data)? {
+		let decoder = CompressionFormat::detect(chunk)
+			.and_then(|format| format.decoder(chunk))
+			.map_err(invalid_data)?;
+		let mut archive = Archive::new(decoder);
+		for entry in archive.entries()? {
+			if cancel_requested.load(Ordering::SeqCst) {
+				return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+			}
+
+			let mut entry = entry?;
+			let validated_entry = validate_archive_entry(&entry)?;
+			if let ValidatedEntry::File(relative_path) = &validated_entry {
+				if files_to_restore.contains(manifest_path(relative_path).as_str()) {
+					// Remove the damaged file first, in case it has been made read-only
+					let _ = fs::remove_file(root.join(relative_path));
+					unpack_validated_entry(&mut entry, &root, &validated_entry)?;
+				}
+			}
+		}
+	}
+
+	Ok(())
+}
+
+// Converts a path within the archive to the '/' separated format used by the manifest
+fn manifest_path(path: &Path) -> String {
+	path.components()
+		.filter_map(|component| match component {
+			Component::Normal(part) => Some(part.to_string_lossy()),
+			_ => None,
+		})
+		.collect::<Vec<_>>()
+		.join("/")
+}
+
+// Returns true if the extraction lock matches this version of the installer, and the most important
+// extracted files are still present. Developer builds always re-extract, as their version never changes.
+fn extraction_is_up_to_date(sub_folder_path: &Path, saved_git_tag_path: &Path, payload_version: &str) -> bool {
+	if version::is_developer_build() {
+		return false;
+	}
+
+	match fs::read_to_string(saved_git_tag_path) {
+		Ok(saved_git_tag) if saved_git_tag.trim() == payload_version => {}
+		_ => return false,
+	}
+
+	REQUIRED_FILES
+		.iter()
+		.all(|required_file| sub_folder_path.join(required_file).is_file())
+}
+
+fn write_extraction_lock<P: AsRef<Path>>(saved_git_tag_path: P, payload_version: &str) {
+	fs::write(saved_git_tag_path, payload_version)
+		.unwrap_or_else(|e| println!("Warning - Failed to write loader extraction lock: {:?}", e))
+}
+
+// Measures how quickly files are being written, to estimate the remaining extraction time
+struct ThroughputTracker {
+	start_time: Instant,
+	bytes_written: u64,
+	bytes_total: u64,
+	entries_done: usize,
+	entries_total: usize,
+}
+
+impl ThroughputTracker {
+	pub fn new(payload: &Payload) -> ThroughputTracker {
+		ThroughputTracker {
+			start_time: Instant::now(),
+			bytes_written: 0,
+			bytes_total: payload.uncompressed_size().unwrap_or(0),
+			entries_done: 0,
+			entries_total: payload.manifest.as_ref().map_or(0, |manifest| manifest.files.len()),
+		}
+	}
+
+	// Tracks the extraction of only the listed files from the manifest
+	pub fn for_files<'a, I: Iterator<Item = &'a String>>(
+		manifest: &PayloadManifest,
+		relative_paths: I,
+	) -> ThroughputTracker {
+		let mut tracker = ThroughputTracker {
+			start_time: Instant::now(),
+			bytes_written: 0,
+			bytes_total: 0,
+			entries_done: 0,
+			entries_total: 0,
+		};
+
+		for relative_path in relative_paths {
+			tracker.bytes_total += manifest.files.get(relative_path).map_or(0, |entry| entry.size);
+			tracker.entries_total += 1;
+		}
+
+		tracker
+	}
+
+	pub fn entry_written(&mut self, entry_size: u64) {
+		self.bytes_written += entry_size;
+		self.entries_done += 1;
+	}
+
+	pub fn progress(&self, percentage: usize, current_entry: String) -> ExtractionProgress {
+		let elapsed_secs = self.start_time.elapsed().as_secs_f64();
+		let bytes_per_second = if elapsed_secs > 0.0 {
+			self.bytes_written as f64 / elapsed_secs
+		} else {
+			0.0
+		};
+
+		// If the payload has no manifest, the total size is unknown, so no estimate can be made
+		let eta = if bytes_per_second > 0.0 && self.bytes_total > 0 {
+			let bytes_remaining = self.bytes_total.saturating_sub(self.bytes_written);
+			Some(Duration::from_secs_f64(bytes_remaining as f64 / bytes_per_second))
+		} else {
+			None
+		};
+
+		ExtractionProgress {
+			percentage,
+			current_entry,
+			bytes_written: self.bytes_written,
+			bytes_total: self.bytes_total,
+			entries_done: self.entries_done,
+			entries_total: self.entries_total,
+			bytes_per_second,
+			eta,
+		}
+	}
+}
+
+struct ProgressCounter {
+	bytes_so_far: usize,
+	last_printed: usize,
+	// The last byte count when the bytes were printed
+	data_length: usize,
+	print_interval: usize,
+}
+
+impl ProgressCounter {
+	pub fn new(data_length: usize, print_interval: usize) -> ProgressCounter {
+		ProgressCounter {
+			bytes_so_far: 0,
+			last_printed: 0,
+			data_length,
+			print_interval,
+		}
+	}
+
+	pub fn update(&mut self, read: usize) -> Option<usize> {
+		self.bytes_so_far += read;
+
+		if (self.bytes_so_far - self.last_printed > self.print_interval)
+			|| (self.bytes_so_far == self.data_length)
+		{
+			self.last_printed = self.bytes_so_far;
+			Some(self.bytes_so_far * 100 / self.data_length)
+		} else {
+			None
+		}
+	}
+}
+
+#[cfg(all(test, feature = "xz"))]
+mod tests {
+	use super::*;
+	use std::io::Write;
+	use tar::Header;
+
+	struct TestEntry {
+		path: &'static str,
+		entry_type: EntryType,
+		link_name: &'static str,
+		data: &'static [u8],
+		mode: u32,
+	}
+
+	fn file(path: &'static str, data: &'static [u8]) -> TestEntry {
+		TestEntry {
+			path,
+			entry_type: EntryType::Regular,
+			link_name: "",
+			data,
+			mode: 0o644,
+		}
+	}
+
+	fn link(path: &'static str, entry_type: EntryType, link_name: &'static str) -> TestEntry {
+		TestEntry {
+			path,
+			entry_type,
+			link_name,
+			data: b"",
+			mode: 0o644,
+		}
+	}
+
+	// Builds a .tar.xz in memory. Paths are copied into the headers as-is, as tar::Builder refuses to
+	// write the absolute and '..' paths these tests need.
+	fn build_tar_xz(entries: &[TestEntry]) -> Vec<u8> {
+		let mut tar = Vec::new();
+
+		for entry in entries {
+			let mut header = Header::new_gnu();
+			header.as_old_mut().name[..entry.path.len()].copy_from_slice(entry.path.as_bytes());
+			header.as_old_mut().linkname[..entry.link_name.len()]
+				.copy_from_slice(entry.link_name.as_bytes());
+			header.set_entry_type(entry.entry_type);
+			header.set_size(entry.data.len() as u64);
+			header.set_mode(entry.mode);
+			header.set_cksum();
+
+			tar.extend_from_slice(header.as_bytes());
+			tar.extend_from_slice(entry.data);
+			let padding = (512 - tar.len() % 512) % 512;
+			tar.resize(tar.len() + padding, 0);
+		}
+		tar.extend_from_slice(&[0; 1024]);
+
+		let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+		encoder.write_all(&tar).unwrap();
+		encoder.finish().unwrap()
+	}
+
+	fn extract(entries: &[TestEntry], destination: &Path) -> io::Result<bool> {
+		let archive = build_tar_xz(entries);
+		let decoder = CompressionFormat::detect(&archive)
+			.and_then(|format| format.decoder(archive.as_slice()))
+			.unwrap();
+
+		unpack_cancellable(
+			Archive::new(decoder),
+			destination,
+			&HashSet::new(),
+			&AtomicBool::new(false),
+			|_, _| {},
+		)
+	}
+
+	fn assert_rejected(result: io::Result<bool>) {
+		match result {
+			Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData, "{}", e),
+			Ok(_) => panic!("the archive should have been rejected"),
+		}
+	}
+
+	#[test]
+	fn extracts_files_and_links_inside_the_root() {
+		let folder = tempfile::tempdir().unwrap();
+		let root = folder.path().join("root");
+
+		let result = extract(
+			&[
+				link("lib/", EntryType::Directory, ""),
+				file("lib/data.txt", b"data"),
+				link("lib/current", EntryType::Symlink, "data.txt"),
+				link("top", EntryType::Symlink, "lib/../lib/data.txt"),
+				link("hard.txt", EntryType::Link, "lib/data.txt"),
+			],
+			&root,
+		);
+
+		assert!(result.unwrap());
+		assert_eq!(fs::read(root.join("lib/current")).unwrap(), b"data");
+		assert_eq!(fs::read(root.join("top")).unwrap(), b"data");
+		assert_eq!(fs::read(root.join("hard.txt")).unwrap(), b"data");
+	}
+
+	#[test]
+	fn rejects_absolute_paths() {
+		let folder = tempfile::tempdir().unwrap();
+		let outside = folder.path().join("absolute.txt");
+		let path: &'static str = Box::leak(outside.to_str().unwrap().to_string().into_boxed_str());
+
+		assert_rejected(extract(&[file(path, b"evil")], &folder.path().join("root")));
+		assert!(!outside.exists());
+	}
+
+	#[test]
+	fn rejects_parent_directory_components() {
+		let folder = tempfile::tempdir().unwrap();
+
+		assert_rejected(extract(
+			&[file("../evil.txt", b"evil")],
+			&folder.path().join("root"),
+		));
+		assert!(!folder.path().join("evil.txt").exists());
+	}
+
+	#[test]
+	fn rejects_symlinks_which_escape_the_root() {
+		let folder = tempfile::tempdir().unwrap();
+		let root = folder.path().join("root");
+
+		assert_rejected(extract(&[link("escape", EntryType::Symlink, "../")], &root));
+		assert_rejected(extract(
+			&[link("lib/escape", EntryType::Symlink, "../../x")],
+			&root,
+		));
+		assert_rejected(extract(
+			&[link("escape", EntryType::Symlink, "/etc")],
+			&root,
+		));
+		assert!(fs::symlink_metadata(root.join("escape")).is_err());
+	}
+
+	#[test]
+	fn rejects_hard_links_which_escape_the_root() {
+		let folder = tempfile::tempdir().unwrap();
+		fs::write(folder.path().join("secret.txt"), b"secret").unwrap();
+
+		assert_rejected(extract(
+			&[link("hard.txt", EntryType::Link, "../secret.txt")],
+			&folder.path().join("root"),
+		));
+		assert!(!folder.path().join("root/hard.txt").exists());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn rejects_hard_links_through_an_extracted_symlink() {
+		let folder = tempfile::tempdir().unwrap();
+		let root = folder.path().join("root");
+		fs::create_dir_all(&root).unwrap();
+		fs::write(folder.path().join("secret.txt"), b"secret").unwrap();
+		// Left by another worker or an interrupted extraction, which wasn't checked against this archive
+		std::os::unix::fs::symlink(folder.path(), root.join("outside")).unwrap();
+
+		assert_rejected(extract(
+			&[link("hard.txt", EntryType::Link, "outside/secret.txt")],
+			&root,
+		));
+		assert_rejected(extract(&[file("outside/evil.txt", b"evil")], &root));
+		assert!(!root.join("hard.txt").exists());
+		assert!(!folder.path().join("evil.txt").exists());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn rejects_chained_symlinks_which_escape_the_root() {
+		let folder = tempfile::tempdir().unwrap();
+		let root = folder.path().join("root");
+
+		// 'c' -> 'b/..' looks like it points at the root, but 'b' is the root, so 'c' is its parent
+		assert_rejected(extract(
+			&[
+				link("b", EntryType::Symlink, "."),
+				link("c", EntryType::Symlink, "b/.."),
+				file("c/evil.txt", b"evil"),
+			],
+			&root,
+		));
+		assert!(!folder.path().join("evil.txt").exists());
+
+		// '..' after a component which doesn't exist yet could be redirected by a later symlink
+		assert_rejected(extract(
+			&[
+				link("d", EntryType::Symlink, "e/.."),
+				link("e", EntryType::Symlink, "."),
+			],
+			&folder.path().join("root2"),
+		));
+	}
+
+	#[test]
+	fn rejects_device_and_fifo_entries() {
+		let folder = tempfile::tempdir().unwrap();
+		let root = folder.path().join("root");
+
+		for entry_type in &[EntryType::Char, EntryType::Block, EntryType::Fifo] {
+			assert_rejected(extract(&[link("device", *entry_type, "")], &root));
+			assert!(fs::symlink_metadata(root.join("device")).is_err());
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn masks_file_modes_with_the_umask() {
+		assert_eq!(extracted_file_mode(0o4755, 0o022), 0o755);
+		assert_eq!(extracted_file_mode(0o6775, 0o022), 0o755);
+		assert_eq!(extracted_file_mode(0o6775, 0o002), 0o775);
+		assert_eq!(extracted_file_mode(0o755, 0o022), 0o755);
+		assert_eq!(extracted_file_mode(0o755, 0o077), 0o700);
+		assert_eq!(extracted_file_mode(0o644, 0o022), 0o644);
+		assert_eq!(extracted_file_mode(0o644, 0o027), 0o640);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn extracts_files_with_their_permissions() {
+		use std::os::unix::fs::PermissionsExt;
+
+		let folder = tempfile::tempdir().unwrap();
+		let root = folder.path().join("root");
+		let modes = [
+			("setuid", 0o4755),
+			("setgid", 0o6775),
+			("executable", 0o755),
+			("regular", 0o644),
+		];
+
+		let entries: Vec<TestEntry> = modes
+			.iter()
+			.map(|(path, mode)| TestEntry {
+				mode: *mode,
+				..file(path, b"data")
+			})
+			.collect();
+		assert!(extract(&entries, &root).unwrap());
+
+		let umask = process_umask();
+		for (path, mode) in &modes {
+			let extracted_mode =
+				fs::metadata(root.join(path)).unwrap().permissions().mode() & 0o7777;
+			assert_eq!(
+				extracted_mode,
+				extracted_file_mode(*mode, umask),
+				"{}",
+				path
+			);
+			assert_eq!(
+				extracted_mode & 0o7000,
+				0,
+				"{} has setuid, setgid or sticky bits",
+				path
+			);
+		}
+	}
+
+	#[test]
+	fn swap_keeps_only_user_files_from_the_previous_extraction() {
+		let folder = tempfile::tempdir().unwrap();
+		let sub_folder = folder.path().join("installer");
+		let staging = folder.path().join("installer.staging");
+
+		fs::create_dir_all(sub_folder.join("INSTALLER_LOGS")).unwrap();
+		fs::write(sub_folder.join("INSTALLER_LOGS/install.log"), b"log").unwrap();
+		fs::write(sub_folder.join("launcher-state.json"), b"{}").unwrap();
+		fs::write(sub_folder.join("main.py"), b"old").unwrap();
+		fs::write(sub_folder.join("removed_in_new_version.py"), b"old").unwrap();
+
+		fs::create_dir_all(&staging).unwrap();
+		fs::write(staging.join("main.py"), b"new").unwrap();
+
+		swap_into_place(&staging, &sub_folder).unwrap();
+
+		assert_eq!(fs::read(sub_folder.join("main.py")).unwrap(), b"new");
+		assert_eq!(
+			fs::read(sub_folder.join("INSTALLER_LOGS/install.log")).unwrap(),
+			b"log"
+		);
+		assert!(sub_folder.join("launcher-state.json").exists());
+		assert!(!sub_folder.join("removed_in_new_version.py").exists());
+		assert!(!staging.exists());
+		assert!(!sibling_path(&sub_folder, BACKUP_SUFFIX).exists());
+	}
+
+	// Runs an extraction until it finishes, fails or is cancelled
+	fn run_extraction(mut extractor: ArchiveExtractor, sub_folder: &Path) -> ExtractionStatus {
+		extractor.start_extraction(sub_folder);
+		loop {
+			match extractor.poll_status() {
+				ExtractionStatus::Started(_) | ExtractionStatus::Repairing(_) => {
+					thread::sleep(Duration::from_millis(10))
+				}
+				status => return status,
+			}
+		}
+	}
+
+	#[test]
+	fn restores_interrupted_swap_before_preflight_checks() {
+		let folder = tempfile::tempdir().unwrap();
+		let sub_folder = folder.path().join("installer");
+		let backup = sibling_path(&sub_folder, BACKUP_SUFFIX);
+
+		// The previous extraction was renamed to the backup, but the new one was never moved into place
+		fs::create_dir_all(backup.join("INSTALLER_LOGS")).unwrap();
+		fs::write(backup.join("INSTALLER_LOGS/install.log"), b"log").unwrap();
+		fs::write(backup.join("launcher-state.json"), b"{}").unwrap();
+		fs::write(backup.join("main.py"), b"old").unwrap();
+
+		let payload_path = folder.path().join("install_data.tar.xz");
+		fs::write(
+			&payload_path,
+			build_tar_xz(&[file("main.py", b"new"), file("cli_interactive.py", b"cli")]),
+		)
+		.unwrap();
+
+		// The checks don't create the missing sub folder, which would stop the backup being restored
+		let payload = PayloadSource::File(payload_path.clone()).load().unwrap();
+		preflight_checks(&sub_folder, &payload).unwrap();
+		assert!(!sub_folder.exists());
+
+		let mut extractor = ArchiveExtractor::new(false, PayloadSource::File(payload_path), None);
+		extractor.enable_preflight_checks();
+		assert!(matches!(
+			run_extraction(extractor, &sub_folder),
+			ExtractionStatus::Finished
+		));
+
+		assert_eq!(fs::read(sub_folder.join("main.py")).unwrap(), b"new");
+		assert_eq!(
+			fs::read(sub_folder.join("INSTALLER_LOGS/install.log")).unwrap(),
+			b"log"
+		);
+		assert!(sub_folder.join("launcher-state.json").exists());
+		assert!(!backup.exists());
+	}
+
+	#[test]
+	fn cancelled_extraction_removes_partial_output() {
+		let folder = tempfile::tempdir().unwrap();
+		let sub_folder = folder.path().join("installer");
+		let payload_path = folder.path().join("install_data.tar.xz");
+		fs::write(&payload_path, build_tar_xz(&[file("main.py", b"new")])).unwrap();
+
+		// Cancelled as soon as the extraction thread starts
+		let extractor = ArchiveExtractor::new(false, PayloadSource::File(payload_path), None);
+		extractor.cancel_requested.store(true, Ordering::SeqCst);
+		assert!(matches!(
+			run_extraction(extractor, &sub_folder),
+			ExtractionStatus::Cancelled
+		));
+
+		assert!(!sub_folder.exists());
+		assert!(!sibling_path(&sub_folder, STAGING_SUFFIX).exists());
+	}
+
+	#[test]
+	fn cancel_before_starting_never_starts() {
+		let mut extractor = ArchiveExtractor::new(false, PayloadSource::Default, None);
+		extractor.cancel();
+		assert!(matches!(
+			extractor.poll_status(),
+			ExtractionStatus::Cancelled
+		));
+	}
+
+	fn manifest_of(files: &[(&str, &[u8])]) -> PayloadManifest {
+		PayloadManifest {
+			version: None,
+			files: files
+				.iter()
+				.map(|(path, data)| {
+					let entry = ManifestEntry {
+						size: data.len() as u64,
+						sha256: format!("{:x}", Sha256::digest(data)),
+					};
+					(path.to_string(), entry)
+				})
+				.collect(),
+			delta: None,
+		}
+	}
+
+	#[test]
+	fn quick_check_finds_missing_and_resized_files() {
+		let folder = tempfile::tempdir().unwrap();
+		let manifest = manifest_of(&[
+			("main.py", b"main"),
+			("missing.py", b"missing"),
+			("resized.py", b"resized"),
+		]);
+		fs::write(folder.path().join("main.py"), b"main").unwrap();
+		fs::write(folder.path().join("resized.py"), b"resized!").unwrap();
+
+		let after_extraction = SystemTime::now() + Duration::from_secs(3600);
+		assert_eq!(
+			find_modified_files(
+				folder.path(),
+				&manifest,
+				after_extraction,
+				&AtomicBool::new(false)
+			)
+			.unwrap(),
+			vec!["missing.py".to_string(), "resized.py".to_string()]
+		);
+	}
+
+	#[test]
+	fn quick_check_only_hashes_files_modified_after_extraction() {
+		let folder = tempfile::tempdir().unwrap();
+		let manifest = manifest_of(&[("main.py", b"main")]);
+		// Same size as the manifest entry, but different contents
+		fs::write(folder.path().join("main.py"), b"MAIN").unwrap();
+
+		// Not modified since extraction, so it isn't hashed
+		let after_extraction = SystemTime::now() + Duration::from_secs(3600);
+		assert!(find_modified_files(
+			folder.path(),
+			&manifest,
+			after_extraction,
+			&AtomicBool::new(false)
+		)
+		.unwrap()
+		.is_empty());
+
+		// Modified since extraction, so the hash is checked
+		let before_extraction = SystemTime::UNIX_EPOCH;
+		assert_eq!(
+			find_modified_files(
+				folder.path(),
+				&manifest,
+				before_extraction,
+				&AtomicBool::new(false)
+			)
+			.unwrap(),
+			vec!["main.py".to_string()]
+		);
+
+		fs::write(folder.path().join("main.py"), b"main").unwrap();
+		assert!(find_modified_files(
+			folder.path(),
+			&manifest,
+			before_extraction,
+			&AtomicBool::new(false)
+		)
+		.unwrap()
+		.is_empty());
+	}
+
+	#[test]
+	fn verification_stops_when_cancelled() {
+		let folder = tempfile::tempdir().unwrap();
+		let manifest = manifest_of(&[("main.py", b"main")]);
+		fs::write(folder.path().join("main.py"), b"main").unwrap();
+
+		let cancel_requested = AtomicBool::new(false);
+		assert_eq!(
+			find_damaged_files(folder.path(), &manifest, &cancel_requested),
+			Some(Vec::new())
+		);
+
+		cancel_requested.store(true, Ordering::SeqCst);
+		assert_eq!(
+			find_damaged_files(folder.path(), &manifest, &cancel_requested),
+			None
+		);
+		assert_eq!(
+			find_modified_files(
+				folder.path(),
+				&manifest,
+				SystemTime::UNIX_EPOCH,
+				&cancel_requested
+			),
+			None
+		);
+		assert_eq!(
+			sha256_file(&folder.path().join("main.py"), &cancel_requested)
+				.unwrap_err()
+				.kind(),
+			io::ErrorKind::Interrupted
+		);
+	}
+
+	// Lays out a stamped payload the same way as stamp_payload.py (see payload.rs)
+	fn stamped_payload(archive: &[u8], manifest_json: &str) -> Vec<u8> {
+		let mut payload = archive.to_vec();
+		payload.extend_from_slice(manifest_json.as_bytes());
+		payload.extend_from_slice(b"7THPAYLD");
+		payload.extend_from_slice(&0u64.to_le_bytes());
+		payload.extend_from_slice(&(archive.len() as u64).to_le_bytes());
+		payload.extend_from_slice(&(manifest_json.len() as u64).to_le_bytes());
+		payload.extend_from_slice(&Sha256::digest(archive));
+		payload
+	}
+
+	fn manifest_entry_json(path: &str, data: &[u8]) -> String {
+		format!(
+			r#""{}": {{"size": {}, "sha256": "{:x}"}}"#,
+			path,
+			data.len(),
+			Sha256::digest(data)
+		)
+	}
+
+	#[test]
+	fn applies_delta_to_the_current_extraction() {
+		let folder = tempfile::tempdir().unwrap();
+		let sub_folder = folder.path().join("installer");
+
+		// The current extraction, of the delta's base version
+		fs::create_dir_all(sub_folder.join("python/lib")).unwrap();
+		fs::create_dir_all(sub_folder.join("INSTALLER_LOGS")).unwrap();
+		fs::write(sub_folder.join("main.py"), b"unchanged").unwrap();
+		fs::write(
+			sub_folder.join("python/lib/patched.py"),
+			b"print('old version')\n",
+		)
+		.unwrap();
+		fs::write(sub_folder.join("removed.py"), b"removed").unwrap();
+		fs::write(sub_folder.join("python/lib/removed.py"), b"removed").unwrap();
+		fs::write(sub_folder.join("INSTALLER_LOGS/install.log"), b"log").unwrap();
+		fs::write(sub_folder.join(EXTRACTION_LOCK_FILENAME), "1.0").unwrap();
+
+		// Keeps "print('" from the base file, then inserts the rest
+		let patched: &[u8] = b"print('new version')\n";
+		let inserted: &[u8] = b"new version')\n";
+		let mut patch = b"7THPATCH".to_vec();
+		patch.extend_from_slice(&(patched.len() as u64).to_le_bytes());
+		patch.push(0x00);
+		patch.extend_from_slice(&0u64.to_le_bytes());
+		patch.extend_from_slice(&7u64.to_le_bytes());
+		patch.push(0x01);
+		patch.extend_from_slice(&(inserted.len() as u64).to_le_bytes());
+		patch.extend_from_slice(inserted);
+		let patch: &'static [u8] = Box::leak(patch.into_boxed_slice());
+
+		let archive = build_tar_xz(&[
+			file("python/lib/patched.py", patch),
+			file("added.py", b"added"),
+		]);
+		let manifest_json = format!(
+			r#"{{"version": "2.0", "files": {{{}, {}, {}}}, "delta": {{"base_version": "1.0", "added": ["added.py"], "patched": ["python/lib/patched.py"], "removed": ["removed.py", "python/lib/removed.py"]}}}}"#,
+			manifest_entry_json("main.py", b"unchanged"),
+			manifest_entry_json("python/lib/patched.py", patched),
+			manifest_entry_json("added.py", b"added"),
+		);
+		let delta_path = folder.path().join("delta.payload");
+		fs::write(&delta_path, stamped_payload(&archive, &manifest_json)).unwrap();
+
+		let (sender, _receiver) = mpsc::channel();
+		update_from_delta_payload(&sub_folder, &delta_path, &AtomicBool::new(false), &sender)
+			.unwrap();
+
+		assert_eq!(fs::read(sub_folder.join("main.py")).unwrap(), b"unchanged");
+		assert_eq!(
+			fs::read(sub_folder.join("python/lib/patched.py")).unwrap(),
+			patched
+		);
+		assert_eq!(fs::read(sub_folder.join("added.py")).unwrap(), b"added");
+		assert!(!sub_folder.join("removed.py").exists());
+		assert!(!sub_folder.join("python/lib/removed.py").exists());
+		assert_eq!(
+			fs::read(sub_folder.join("INSTALLER_LOGS/install.log")).unwrap(),
+			b"log"
+		);
+		assert_eq!(
+			fs::read_to_string(sub_folder.join(EXTRACTION_LOCK_FILENAME)).unwrap(),
+			"2.0"
+		);
+		assert!(!sibling_path(&sub_folder, STAGING_SUFFIX).exists());
+		assert!(!sibling_path(&sub_folder, BACKUP_SUFFIX).exists());
+
+		// Applying it again finds the files are already up to date
+		update_from_delta_payload(&sub_folder, &delta_path, &AtomicBool::new(false), &sender)
+			.unwrap();
+
+		// A delta for another base version isn't applied, so a full extraction is done instead
+		fs::write(sub_folder.join(EXTRACTION_LOCK_FILENAME), "0.9").unwrap();
+		assert!(matches!(
+			update_from_delta_payload(&sub_folder, &delta_path, &AtomicBool::new(false), &sender),
+			Err(ExtractionFailure::Error(_))
+		));
+		assert_eq!(fs::read(sub_folder.join("added.py")).unwrap(), b"added");
+	}
+
+	#[test]
+	fn finished_extraction_reports_full_progress() {
+		let (sender, receiver) = mpsc::channel();
+		let mut extractor = ArchiveExtractor::new(false, PayloadSource::Default, None);
+		extractor.receiver = ExtractionStateMachine::Started(receiver);
+
+		let progress = ExtractionProgress {
+			percentage: 95,
+			entries_done: 10,
+			entries_total: 10,
+			eta: Some(Duration::from_secs(1)),
+			..ExtractionProgress::default()
+		};
+		sender
+			.send(Ok(ExtractionReport::InProgress(progress)))
+			.unwrap();
+		sender.send(Ok(ExtractionReport::Finished)).unwrap();
+
+		assert!(
+			matches!(extractor.poll_status(), ExtractionStatus::Started(Some(progress)) if progress.percentage == 95)
+		);
+		match extractor.poll_status() {
+			ExtractionStatus::Started(Some(progress)) => {
+				assert_eq!(progress.percentage, 100);
+				assert_eq!(progress.entries_done, 10);
+				assert!(progress.eta.is_none());
+			}
+			_ => panic!("Expected a final progress update"),
 		}
+		assert!(matches!(
+			extractor.poll_status(),
+			ExtractionStatus::Finished
+		));
 	}
 }
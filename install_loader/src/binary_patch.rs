@@ -0,0 +1,170 @@
+use std::convert::TryInto;
+use std::io;
+
+// A binary patch rebuilds a file from the previous version of that file. It is laid out as:
+// magic (8 bytes), target length (u64 little endian), then a sequence of operations until the
+// target length has been written:
+//   0x00, offset (u64), length (u64) - copy 'length' bytes from 'offset' in the base file
+//   0x01, length (u64), bytes        - insert 'length' new bytes
+// Patches are generated by make_delta_payload.py.
+const PATCH_MAGIC: &[u8; 8] = b"7THPATCH";
+const OP_COPY: u8 = 0x00;
+const OP_INSERT: u8 = 0x01;
+
+// Applies 'patch' to the contents of the base file, returning the contents of the new file
+pub fn apply_patch(base: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+	let mut reader = PatchReader { remaining: patch };
+
+	if reader.take(PATCH_MAGIC.len())? != PATCH_MAGIC {
+		return Err(invalid_patch("missing patch header"));
+	}
+
+	let target_length = reader.read_u64()?;
+	let mut target = Vec::with_capacity(target_length.min(patch.len() as u64 * 4) as usize);
+
+	while (target.len() as u64) < target_length {
+		match reader.take(1)?[0] {
+			OP_COPY => {
+				let offset = reader.read_u64()?;
+				let length = reader.read_u64()?;
+				let copied = offset
+					.checked_add(length)
+					.and_then(|end| base.get(offset as usize..end as usize))
+					.ok_or_else(|| invalid_patch("copy is outside the base file"))?;
+				target.extend_from_slice(copied);
+			}
+			OP_INSERT => {
+				let length = reader.read_u64()?;
+				target.extend_from_slice(reader.take(length as usize)?);
+			}
+			_ => return Err(invalid_patch("unknown patch operation")),
+		}
+	}
+
+	if target.len() as u64 != target_length || !reader.remaining.is_empty() {
+		return Err(invalid_patch("patch length doesn't match its header"));
+	}
+
+	Ok(target)
+}
+
+struct PatchReader<'a> {
+	remaining: &'a [u8],
+}
+
+impl<'a> PatchReader<'a> {
+	fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+		if length > self.remaining.len() {
+			return Err(invalid_patch("patch is truncated"));
+		}
+
+		let (taken, rest) = self.remaining.split_at(length);
+		self.remaining = rest;
+		Ok(taken)
+	}
+
+	fn read_u64(&mut self) -> io::Result<u64> {
+		Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+	}
+}
+
+fn invalid_patch(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, format!("Invalid binary patch: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// The patches in test_data/binary_patch were made with make_patch() from make_delta_payload.py
+	macro_rules! test_data {
+		($file_name:expr) => {
+			include_bytes!(concat!("../test_data/binary_patch/", $file_name))
+		};
+	}
+
+	const TEXT_BASE: &[u8] = test_data!("text.base");
+	const TEXT_TARGET: &[u8] = test_data!("text.target");
+	const TEXT_PATCH: &[u8] = test_data!("text.patch");
+
+	fn assert_invalid(result: io::Result<Vec<u8>>) {
+		match result {
+			Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+			Ok(_) => panic!("Expected the patch to be rejected"),
+		}
+	}
+
+	fn patch_header(target_length: u64) -> Vec<u8> {
+		let mut patch = PATCH_MAGIC.to_vec();
+		patch.extend_from_slice(&target_length.to_le_bytes());
+		patch
+	}
+
+	fn copy_op(offset: u64, length: u64) -> Vec<u8> {
+		let mut op = vec![OP_COPY];
+		op.extend_from_slice(&offset.to_le_bytes());
+		op.extend_from_slice(&length.to_le_bytes());
+		op
+	}
+
+	#[test]
+	fn applies_patches_from_make_delta_payload() {
+		assert_eq!(apply_patch(TEXT_BASE, TEXT_PATCH).unwrap(), TEXT_TARGET);
+		assert_eq!(
+			apply_patch(test_data!("binary.base"), test_data!("binary.patch")).unwrap(),
+			test_data!("binary.target")
+		);
+		assert_eq!(
+			apply_patch(b"", test_data!("new_file.patch")).unwrap(),
+			test_data!("new_file.target")
+		);
+	}
+
+	#[test]
+	fn applies_patch_which_empties_the_file() {
+		assert_eq!(apply_patch(TEXT_BASE, &patch_header(0)).unwrap(), b"");
+	}
+
+	#[test]
+	fn rejects_truncated_patches() {
+		for length in 0..TEXT_PATCH.len() {
+			assert_invalid(apply_patch(TEXT_BASE, &TEXT_PATCH[..length]));
+		}
+	}
+
+	#[test]
+	fn rejects_corrupt_patches() {
+		// Wrong magic
+		let mut patch = TEXT_PATCH.to_vec();
+		patch[0] ^= 0xFF;
+		assert_invalid(apply_patch(TEXT_BASE, &patch));
+
+		// Unknown operation
+		let mut patch = patch_header(1);
+		patch.push(0x02);
+		assert_invalid(apply_patch(TEXT_BASE, &patch));
+
+		// Data after the last operation
+		let mut patch = TEXT_PATCH.to_vec();
+		patch.push(0);
+		assert_invalid(apply_patch(TEXT_BASE, &patch));
+
+		// Operations write more than the target length
+		let mut patch = patch_header(4);
+		patch.extend(copy_op(0, 8));
+		assert_invalid(apply_patch(TEXT_BASE, &patch));
+	}
+
+	#[test]
+	fn rejects_copies_outside_the_base_file() {
+		let base_length = TEXT_BASE.len() as u64;
+		for (offset, length) in &[(base_length, 1), (base_length - 1, 2), (1, u64::MAX)] {
+			let mut patch = patch_header(*length);
+			patch.extend(copy_op(*offset, *length));
+			assert_invalid(apply_patch(TEXT_BASE, &patch));
+		}
+
+		// A patch for a different base file
+		assert_invalid(apply_patch(&TEXT_BASE[..100], TEXT_PATCH));
+	}
+}
@@ -0,0 +1,122 @@
+use std::io::Read;
+
+#[cfg(not(any(feature = "xz", feature = "zstd")))]
+compile_error!("At least one of the 'xz' or 'zstd' features must be enabled to decompress the installer archive");
+
+// Magic bytes at the start of each supported compressed stream
+const XZ_MAGIC: &[u8] = &[0xFD, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+
+// The compression used for the installer archive (.tar.xz or .tar.zst).
+// Which decoders are compiled in is chosen with the 'xz' and 'zstd' cargo features.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompressionFormat {
+	Xz,
+	Zstd,
+}
+
+impl CompressionFormat {
+	// Detects the compression format from the first few bytes of the archive
+	pub fn detect(archive_bytes: &[u8]) -> Result<CompressionFormat, String> {
+		if archive_bytes.starts_with(XZ_MAGIC) {
+			Ok(CompressionFormat::Xz)
+		} else if archive_bytes.starts_with(ZSTD_MAGIC) {
+			Ok(CompressionFormat::Zstd)
+		} else {
+			Err(String::from(
+				"The installer archive is not a .tar.xz or .tar.zst archive - it may be corrupt, please re-download the installer.",
+			))
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			CompressionFormat::Xz => "xz",
+			CompressionFormat::Zstd => "zstd",
+		}
+	}
+
+	// Returns an error if support for this format wasn't compiled into the loader
+	pub fn check_supported(&self) -> Result<(), String> {
+		let supported = match self {
+			CompressionFormat::Xz => cfg!(feature = "xz"),
+			CompressionFormat::Zstd => cfg!(feature = "zstd"),
+		};
+
+		if supported {
+			Ok(())
+		} else {
+			Err(format!(
+				"The installer archive uses {} compression, but this installer was built without {} support.",
+				self.name(),
+				self.name()
+			))
+		}
+	}
+
+	// Wraps the reader with a decoder for this format, or returns an error if support for this
+	// format wasn't compiled into the loader.
+	pub fn decoder<'a, R: Read + 'a>(&self, reader: R) -> Result<Box<dyn Read + 'a>, String> {
+		match self {
+			#[cfg(feature = "xz")]
+			CompressionFormat::Xz => Ok(Box::new(xz2::read::XzDecoder::new(reader))),
+			#[cfg(feature = "zstd")]
+			CompressionFormat::Zstd => zstd::stream::read::Decoder::new(reader)
+				.map(|decoder| Box::new(decoder) as Box<dyn Read + 'a>)
+				.map_err(|e| format!("Failed to start zstd decoder: {}", e)),
+			#[allow(unreachable_patterns)]
+			_ => self.check_supported().map(|_| unreachable!()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn detects_xz_and_zstd_archives() {
+		assert_eq!(
+			CompressionFormat::detect(&[0xFD, b'7', b'z', b'X', b'Z', 0x00, 0x00, 0x04]),
+			Ok(CompressionFormat::Xz)
+		);
+		assert_eq!(
+			CompressionFormat::detect(&[0x28, 0xB5, 0x2F, 0xFD, 0x04, 0x58]),
+			Ok(CompressionFormat::Zstd)
+		);
+	}
+
+	#[test]
+	fn rejects_other_and_truncated_archives() {
+		// gzip
+		assert!(CompressionFormat::detect(&[0x1F, 0x8B, 0x08, 0x00]).is_err());
+		// Uncompressed tar
+		assert!(CompressionFormat::detect(&[0u8; 512]).is_err());
+		assert!(CompressionFormat::detect(&[0xFD, b'7', b'z']).is_err());
+		assert!(CompressionFormat::detect(&[0x28, 0xB5]).is_err());
+		assert!(CompressionFormat::detect(&[]).is_err());
+	}
+
+	#[cfg(feature = "xz")]
+	#[test]
+	fn detects_xz_encoder_output() {
+		let mut compressed = Vec::new();
+		xz2::read::XzEncoder::new(&b"data"[..], 6)
+			.read_to_end(&mut compressed)
+			.unwrap();
+		assert_eq!(
+			CompressionFormat::detect(&compressed),
+			Ok(CompressionFormat::Xz)
+		);
+	}
+
+	#[cfg(feature = "zstd")]
+	#[test]
+	fn detects_zstd_encoder_output() {
+		let compressed = zstd::stream::encode_all(&b"data"[..], 3).unwrap();
+		assert_eq!(
+			CompressionFormat::detect(&compressed),
+			Ok(CompressionFormat::Zstd)
+		);
+	}
+}
@@ -1,8 +1,13 @@
+use crate::loader_config::{LoaderConfig, SettingSource, TempDirPolicy};
+use crate::payload::PayloadSource;
+use crate::session_token;
 use crate::windows_utilities;
 use imgui::ImString;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LaunchType {
 	TextMode,	// Launch the fallback text-mode installer (user interacts with installer via terminal)
 	Browser,	// Launch the python installer web server, and let the python script launch the web browser to view it
@@ -19,17 +24,45 @@ pub struct InstallerConfig {
 	pub server_info_path: PathBuf,
 	pub server_info_old: PathBuf,
 	pub webview_data_directory: PathBuf,
+	pub payload_source: PayloadSource,
+	pub delta_payload: Option<PathBuf>,
+	pub python_shutdown_timeout: Duration,
+	// Python interpreter to use if the bundled one is missing or broken (see python_interpreter::resolve())
+	pub python_override: Option<PathBuf>,
+	// Extra arguments for the installer script
+	pub python_args: Vec<String>,
+	pub default_launch_type: LaunchType,
+	// True if the launch type was chosen in the loader settings, so it is used instead of the one
+	// which worked last time (see LaunchPreferences)
+	pub launch_type_configured: bool,
+	pub temp_dir_policy: TempDirPolicy,
+	// Sent with every request to the python installer's web server (see session_token.rs)
+	pub session_token: String,
 }
 
 impl InstallerConfig {
-	pub fn new(root: &PathBuf, use_temp_dir: bool) -> InstallerConfig {
+	pub fn new(
+		root: &PathBuf,
+		use_temp_dir: bool,
+		payload_source: PayloadSource,
+		delta_payload: Option<PathBuf>,
+		loader_config: &LoaderConfig,
+	) -> InstallerConfig {
 		let sub_folder = PathBuf::from(root);
 		let sub_folder_display = ImString::new(windows_utilities::absolute_path_str(
 			&sub_folder,
 			"couldn't determine path",
 		));
-		let logs_folder = sub_folder.join("INSTALLER_LOGS");
-		let python_path = sub_folder.join("python/python.exe");
+		let logs_folder = loader_config
+			.logs_folder
+			.value
+			.clone()
+			.unwrap_or_else(|| sub_folder.join("INSTALLER_LOGS"));
+		let python_path = if cfg!(windows) {
+			sub_folder.join("python/python.exe")
+		} else {
+			sub_folder.join("python/bin/python3")
+		};
 		let server_info_path = sub_folder.join("server-info.json");
 		let server_info_old = sub_folder.join("server-info-old.json");
 		let webview_data_directory = sub_folder.join("webview");
@@ -42,7 +75,16 @@ impl InstallerConfig {
 			use_temp_dir,
 			server_info_path,
 			server_info_old,
-			webview_data_directory
+			webview_data_directory,
+			payload_source,
+			delta_payload,
+			python_shutdown_timeout: loader_config.python_shutdown_timeout.value,
+			python_override: loader_config.python.value.clone(),
+			python_args: loader_config.python_args.value.clone(),
+			default_launch_type: loader_config.launch_type.value,
+			launch_type_configured: loader_config.launch_type.source != SettingSource::Default,
+			temp_dir_policy: loader_config.temp_dir.value,
+			session_token: session_token::generate(),
 		}
 	}
 }
@@ -0,0 +1,210 @@
+use crate::archive_extractor::{self, ArchiveExtractor, ExtractionStatus};
+use crate::loader_config::LoaderConfig;
+use crate::payload::PayloadSource;
+use clap::ArgMatches;
+use serde_json::json;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Exit codes returned by the 'extract' subcommand
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+// Exit code 2 is used by clap for invalid command line arguments
+pub const EXIT_VERIFICATION_FAILED: i32 = 3;
+pub const EXIT_CANCELLED: i32 = 4;
+
+pub const ABOUT_MSG: &str = r#"Extracts the installer data without the GUI, for scripts and for debugging a user's install.
+Exit codes:
+  0: success
+  1: extraction failed
+  2: invalid command line arguments
+  3: --verify found missing or modified files
+  4: extraction was cancelled
+With --json-progress, each event is printed as a JSON object on its own line.
+Other log lines may be printed in between, so ignore any line which doesn't start with '{'."#;
+
+// Runs the 'extract' subcommand, returning the process exit code
+pub fn handle_extract_command(
+	matches: &ArgMatches,
+	payload_source: PayloadSource,
+	delta_payload: Option<PathBuf>,
+) -> i32 {
+	let output = EventOutput {
+		json: matches.is_present("json-progress"),
+	};
+
+	// Without --dest, use the same folder the GUI would extract to
+	let dest = match matches.value_of("dest") {
+		Some(dest) => PathBuf::from(dest),
+		None => match LoaderConfig::load(matches) {
+			Ok(loader_config) => loader_config.extraction_root.value,
+			Err(error) => return output.error(&error),
+		},
+	};
+
+	// --list and --verify only inspect the payload and the destination folder - nothing is extracted
+	if matches.is_present("list") || matches.is_present("verify") {
+		let mut exit_code = EXIT_SUCCESS;
+
+		if matches.is_present("list") {
+			exit_code = list_entries(&output, &payload_source);
+		}
+
+		if matches.is_present("verify") && exit_code == EXIT_SUCCESS {
+			exit_code = verify(&output, &dest, &payload_source);
+		}
+
+		return exit_code;
+	}
+
+	extract(
+		&output,
+		&dest,
+		matches.is_present("force"),
+		payload_source,
+		delta_payload,
+	)
+}
+
+fn list_entries(output: &EventOutput, payload_source: &PayloadSource) -> i32 {
+	let listings = match archive_extractor::list_archive_entries(payload_source) {
+		Ok(listings) => listings,
+		Err(error) => return output.error(&error),
+	};
+
+	for listing in &listings {
+		if output.json {
+			output.print_json(json!({
+				"event": "entry",
+				"path": listing.path,
+				"type": listing.entry_type,
+				"size": listing.size,
+			}));
+		} else {
+			println!("{:>12} {:<9} {}", listing.size, listing.entry_type, listing.path);
+		}
+	}
+
+	EXIT_SUCCESS
+}
+
+fn verify(output: &EventOutput, dest: &Path, payload_source: &PayloadSource) -> i32 {
+	let damaged_files = match archive_extractor::verify_extraction(dest, payload_source) {
+		Ok(damaged_files) => damaged_files,
+		Err(error) => return output.error(&error),
+	};
+
+	if output.json {
+		output.print_json(json!({
+			"event": "verified",
+			"damaged_files": damaged_files,
+		}));
+	} else if damaged_files.is_empty() {
+		println!("All files in [{}] match the manifest", dest.display());
+	} else {
+		println!("{} files in [{}] are missing or modified:", damaged_files.len(), dest.display());
+		for damaged_file in &damaged_files {
+			println!("  {}", damaged_file);
+		}
+	}
+
+	if damaged_files.is_empty() {
+		EXIT_SUCCESS
+	} else {
+		EXIT_VERIFICATION_FAILED
+	}
+}
+
+fn extract(
+	output: &EventOutput,
+	dest: &Path,
+	force_extraction: bool,
+	payload_source: PayloadSource,
+	delta_payload: Option<PathBuf>,
+) -> i32 {
+	let mut extractor = ArchiveExtractor::new(force_extraction, payload_source, delta_payload);
+	extractor.enable_preflight_checks();
+	extractor.start_extraction(dest);
+
+	loop {
+		match extractor.poll_status() {
+			ExtractionStatus::Started(Some(progress)) => {
+				if output.json {
+					output.print_json(json!({
+						"event": "progress",
+						"percentage": progress.percentage,
+						"entry": progress.current_entry,
+						"bytes_written": progress.bytes_written,
+						"bytes_total": progress.bytes_total,
+						"entries_done": progress.entries_done,
+						"entries_total": progress.entries_total,
+						"bytes_per_second": progress.bytes_per_second,
+						"eta_seconds": progress.eta.map(|eta| eta.as_secs()),
+					}));
+				} else {
+					println!("Extraction is {}% complete - {}", progress.percentage, progress.summary());
+				}
+			}
+			ExtractionStatus::Repairing(damaged_files) => {
+				if output.json {
+					output.print_json(json!({
+						"event": "repairing",
+						"damaged_files": damaged_files,
+					}));
+				} else {
+					println!("Restoring {} missing or modified files", damaged_files.len());
+				}
+			}
+			ExtractionStatus::Finished => {
+				if output.json {
+					output.print_json(json!({ "event": "finished" }));
+				} else {
+					println!("Extraction to [{}] finished", dest.display());
+				}
+				return EXIT_SUCCESS;
+			}
+			ExtractionStatus::Cancelled => {
+				if output.json {
+					output.print_json(json!({ "event": "cancelled" }));
+				} else {
+					println!("Extraction was cancelled");
+				}
+				return EXIT_CANCELLED;
+			}
+			ExtractionStatus::PreflightFailed(reason) | ExtractionStatus::Error(reason) => {
+				return output.error(&reason)
+			}
+			// Only wait when there are no more updates, so progress is printed as soon as it is received
+			ExtractionStatus::NotStarted | ExtractionStatus::Started(None) => {
+				std::thread::sleep(std::time::Duration::from_millis(50))
+			}
+		}
+	}
+}
+
+// Prints events either as JSON lines, or as plain text
+struct EventOutput {
+	json: bool,
+}
+
+impl EventOutput {
+	fn print_json(&self, event: serde_json::Value) {
+		let stdout = std::io::stdout();
+		let mut stdout = stdout.lock();
+		let _ = writeln!(stdout, "{}", event);
+		let _ = stdout.flush();
+	}
+
+	fn error(&self, message: &str) -> i32 {
+		if self.json {
+			self.print_json(json!({
+				"event": "error",
+				"message": message,
+			}));
+		} else {
+			eprintln!("Error: {}", message);
+		}
+
+		EXIT_ERROR
+	}
+}
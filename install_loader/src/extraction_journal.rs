@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+// The journal records each entry once it has been fully written to the staging folder, so an
+// extraction which was interrupted (by a crash, power loss, or the loader being killed) resumes
+// where it stopped instead of starting over. It is kept in the staging folder next to where the
+// extraction lock is written, and is removed before the staging folder is swapped into place.
+// Each line is a tab separated record:
+//   payload <payload id>                - always the first line
+//   file <chunk index> <manifest path>  - a file was written
+//   chunk <chunk index>                 - every entry of the chunk was written
+pub const EXTRACTION_JOURNAL_FILENAME: &str = "installer_loader_extraction_journal.txt";
+
+// The records read from a previous extraction's journal. After a power loss, files may be recorded
+// even though their data never reached the disk, so every file must be verified before it is skipped.
+pub struct JournalContents {
+	// Manifest path -> index of the chunk the file was extracted from
+	pub files: HashMap<String, usize>,
+	pub finished_chunks: HashSet<usize>,
+}
+
+pub struct ExtractionJournal {
+	file: File,
+}
+
+impl ExtractionJournal {
+	// Starts a new journal in the staging folder, replacing any existing journal.
+	// 'payload_id' identifies the payload being extracted, so a journal is only ever resumed with the same payload.
+	pub fn create(staging_path: &Path, payload_id: &str) -> io::Result<ExtractionJournal> {
+		let mut file = File::create(staging_path.join(EXTRACTION_JOURNAL_FILENAME))?;
+		writeln!(file, "payload\t{}", payload_id)?;
+		file.sync_all()?;
+		Ok(ExtractionJournal { file })
+	}
+
+	// Continues appending to the existing journal in the staging folder
+	pub fn resume(staging_path: &Path) -> io::Result<ExtractionJournal> {
+		let file = OpenOptions::new()
+			.append(true)
+			.open(staging_path.join(EXTRACTION_JOURNAL_FILENAME))?;
+		Ok(ExtractionJournal { file })
+	}
+
+	// Reads the journal in the staging folder. Returns None if there is no journal, or if it was
+	// written while extracting a different payload.
+	pub fn read(staging_path: &Path, payload_id: &str) -> Option<JournalContents> {
+		let file = File::open(staging_path.join(EXTRACTION_JOURNAL_FILENAME)).ok()?;
+		let mut lines = BufReader::new(file).lines();
+
+		let header = lines.next()?.ok()?;
+		if header != format!("payload\t{}", payload_id) {
+			return None;
+		}
+
+		let mut contents = JournalContents {
+			files: HashMap::new(),
+			finished_chunks: HashSet::new(),
+		};
+
+		// The last line may be incomplete if the loader was killed while writing it, so any line
+		// which can't be parsed is ignored. Files are verified before they are skipped anyway.
+		for line in lines {
+			let line = match line {
+				Ok(line) => line,
+				Err(_) => break,
+			};
+
+			let mut fields = line.splitn(3, '\t');
+			match (fields.next(), fields.next(), fields.next()) {
+				(Some("file"), Some(chunk_index), Some(manifest_path)) => {
+					if let Ok(chunk_index) = chunk_index.parse() {
+						contents
+							.files
+							.insert(manifest_path.to_string(), chunk_index);
+					}
+				}
+				(Some("chunk"), Some(chunk_index), None) => {
+					if let Ok(chunk_index) = chunk_index.parse() {
+						contents.finished_chunks.insert(chunk_index);
+					}
+				}
+				_ => {}
+			}
+		}
+
+		Some(contents)
+	}
+
+	// Deletes the journal from the staging folder, once the extraction has finished
+	pub fn remove(staging_path: &Path) -> io::Result<()> {
+		match fs::remove_file(staging_path.join(EXTRACTION_JOURNAL_FILENAME)) {
+			Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+			_ => Ok(()),
+		}
+	}
+
+	pub fn file_written(&mut self, chunk_index: usize, manifest_path: &str) -> io::Result<()> {
+		writeln!(self.file, "file\t{}\t{}", chunk_index, manifest_path)
+	}
+
+	pub fn chunk_finished(&mut self, chunk_index: usize) -> io::Result<()> {
+		writeln!(self.file, "chunk\t{}", chunk_index)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn resumes_recorded_files_and_chunks() {
+		let staging = tempfile::tempdir().unwrap();
+
+		let mut journal = ExtractionJournal::create(staging.path(), "payload-1").unwrap();
+		journal.file_written(0, "main.py").unwrap();
+		journal.file_written(1, "python/python.exe").unwrap();
+		journal.chunk_finished(1).unwrap();
+		drop(journal);
+
+		// Entries appended after resuming are read along with the earlier ones
+		let mut journal = ExtractionJournal::resume(staging.path()).unwrap();
+		journal
+			.file_written(0, "folder with spaces/file\tname.py")
+			.unwrap();
+		drop(journal);
+
+		let contents = ExtractionJournal::read(staging.path(), "payload-1").unwrap();
+		assert_eq!(contents.files.len(), 3);
+		assert_eq!(contents.files["main.py"], 0);
+		assert_eq!(contents.files["python/python.exe"], 1);
+		assert_eq!(contents.files["folder with spaces/file\tname.py"], 0);
+		assert_eq!(contents.finished_chunks, [1].iter().cloned().collect());
+	}
+
+	#[test]
+	fn ignores_journal_from_another_payload() {
+		let staging = tempfile::tempdir().unwrap();
+		ExtractionJournal::create(staging.path(), "payload-1").unwrap();
+
+		assert!(ExtractionJournal::read(staging.path(), "payload-2").is_none());
+		assert!(ExtractionJournal::read(staging.path(), "payload-1").is_some());
+	}
+
+	#[test]
+	fn ignores_missing_journal() {
+		let staging = tempfile::tempdir().unwrap();
+		assert!(ExtractionJournal::read(staging.path(), "payload-1").is_none());
+
+		ExtractionJournal::create(staging.path(), "payload-1").unwrap();
+		ExtractionJournal::remove(staging.path()).unwrap();
+		assert!(ExtractionJournal::read(staging.path(), "payload-1").is_none());
+		// Removing a journal which doesn't exist isn't an error
+		ExtractionJournal::remove(staging.path()).unwrap();
+	}
+
+	#[test]
+	fn ignores_incomplete_and_invalid_lines() {
+		let staging = tempfile::tempdir().unwrap();
+		fs::write(
+			staging.path().join(EXTRACTION_JOURNAL_FILENAME),
+			"payload\tpayload-1\nfile\t0\tmain.py\nfile\tx\tbad_index.py\nchunk\t0\nunknown\t1\nfile\t1",
+		)
+		.unwrap();
+
+		let contents = ExtractionJournal::read(staging.path(), "payload-1").unwrap();
+		assert_eq!(contents.files.len(), 1);
+		assert_eq!(contents.files["main.py"], 0);
+		assert_eq!(contents.finished_chunks, [0].iter().cloned().collect());
+	}
+}
@@ -0,0 +1,188 @@
+use serde::Deserialize;
+
+// The python installer reports its progress to the launcher with status lines, written to stderr
+// (see printLauncherStatus() in commandLineParser.py). Stderr is used as the installer redirects its
+// own stderr to its log, so nothing else is written there once it has started.
+// Status lines are only sent if STATUS_LINES_ENV_VAR is set to "1", which ProcessRunner always does.
+//
+// Each status line is STATUS_LINE_PREFIX followed by a JSON object, which is one of:
+//   {"type": "progress", "phase": "Extracting", "percentage": 45, "currentFile": "graphics.7z"}
+//     The overall progress of the install. "percentage" (0 to 100) and "currentFile" may be null.
+//   {"type": "error", "message": "Download failed"}
+//     An error the user should see, like the exception which stopped the install.
+//
+// Status lines aren't echoed to the console, logged, or shown in the installer output viewer.
+// Lines with the prefix which can't be parsed are ignored.
+pub const STATUS_LINES_ENV_VAR: &str = "INSTALL_LOADER_STATUS_LINES";
+pub const STATUS_LINE_PREFIX: &str = "<<<LAUNCHER_STATUS>>>";
+
+// Only the most recent errors are kept
+const MAX_ERRORS: usize = 5;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum StatusMessage {
+	#[serde(rename_all = "camelCase")]
+	Progress {
+		phase: String,
+		percentage: Option<f64>,
+		current_file: Option<String>,
+	},
+	Error {
+		message: String,
+	},
+}
+
+// The install progress most recently reported by the python installer
+#[derive(Clone, Default)]
+pub struct InstallStatus {
+	// None until the installer has reported its progress
+	pub phase: Option<String>,
+	pub percentage: Option<usize>,
+	pub current_file: Option<String>,
+	// Oldest first
+	pub errors: Vec<String>,
+}
+
+impl InstallStatus {
+	// Updates the status from a status line. Returns false if the line isn't a status line, so it is
+	// normal output.
+	pub fn update_from_line(&mut self, line: &str) -> bool {
+		let json = match line.strip_prefix(STATUS_LINE_PREFIX) {
+			Some(json) => json,
+			None => return false,
+		};
+
+		match serde_json::from_str(json) {
+			Ok(StatusMessage::Progress {
+				phase,
+				percentage,
+				current_file,
+			}) => {
+				self.phase = Some(phase);
+				self.percentage =
+					percentage.map(|percentage| percentage.clamp(0.0, 100.0) as usize);
+				self.current_file = current_file;
+			}
+			Ok(StatusMessage::Error { message }) => {
+				if self.errors.len() >= MAX_ERRORS {
+					self.errors.remove(0);
+				}
+				self.errors.push(message);
+			}
+			Err(e) => println!("Ignoring invalid status line [{}]: {}", line, e),
+		}
+
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn status_line(json: &str) -> String {
+		format!("{}{}", STATUS_LINE_PREFIX, json)
+	}
+
+	#[test]
+	fn reads_progress_lines() {
+		let mut status = InstallStatus::default();
+		assert!(status.update_from_line(&status_line(
+			r#"{"type": "progress", "phase": "Extracting", "percentage": 45.5, "currentFile": "graphics.7z"}"#
+		)));
+
+		assert_eq!(status.phase.as_deref(), Some("Extracting"));
+		assert_eq!(status.percentage, Some(45));
+		assert_eq!(status.current_file.as_deref(), Some("graphics.7z"));
+		assert!(status.errors.is_empty());
+	}
+
+	#[test]
+	fn reads_partial_progress_lines() {
+		let mut status = InstallStatus::default();
+		status.update_from_line(&status_line(
+			r#"{"type": "progress", "phase": "Downloading", "percentage": 10, "currentFile": "voices.7z"}"#,
+		));
+
+		// Missing or null fields clear the previous values
+		assert!(status.update_from_line(&status_line(
+			r#"{"type": "progress", "phase": "Finishing", "percentage": null}"#
+		)));
+		assert_eq!(status.phase.as_deref(), Some("Finishing"));
+		assert_eq!(status.percentage, None);
+		assert_eq!(status.current_file, None);
+
+		// Out of range percentages are clamped
+		status.update_from_line(&status_line(
+			r#"{"type": "progress", "phase": "a", "percentage": 150}"#,
+		));
+		assert_eq!(status.percentage, Some(100));
+		status.update_from_line(&status_line(
+			r#"{"type": "progress", "phase": "a", "percentage": -5}"#,
+		));
+		assert_eq!(status.percentage, Some(0));
+	}
+
+	#[test]
+	fn keeps_only_the_latest_errors() {
+		let mut status = InstallStatus::default();
+		for i in 0..MAX_ERRORS + 2 {
+			assert!(status.update_from_line(&status_line(&format!(
+				r#"{{"type": "error", "message": "Error {}"}}"#,
+				i
+			))));
+		}
+
+		let expected: Vec<String> = (2..MAX_ERRORS + 2)
+			.map(|i| format!("Error {}", i))
+			.collect();
+		assert_eq!(status.errors, expected);
+		assert_eq!(status.phase, None);
+	}
+
+	#[test]
+	fn ignores_malformed_status_lines() {
+		let mut status = InstallStatus::default();
+		status.update_from_line(&status_line(
+			r#"{"type": "progress", "phase": "Extracting", "percentage": 45, "currentFile": null}"#,
+		));
+
+		// These are status lines (so they aren't shown as output), but leave the status unchanged
+		for json in &[
+			r#"{"type": "progress", "phase": "Extract"#,
+			r#"{"type": "progress", "percentage": 50}"#,
+			r#"{"type": "unknown", "phase": "Extracting"}"#,
+			r#"{"type": "error"}"#,
+			r#"{"phase": "Extracting"}"#,
+			"",
+			"not json",
+		] {
+			assert!(status.update_from_line(&status_line(json)), "{}", json);
+		}
+
+		assert_eq!(status.phase.as_deref(), Some("Extracting"));
+		assert_eq!(status.percentage, Some(45));
+		assert!(status.errors.is_empty());
+	}
+
+	#[test]
+	fn ignores_normal_output() {
+		let mut status = InstallStatus::default();
+		for line in &[
+			"Downloading graphics.7z",
+			r#"{"type": "error", "message": "Not a status line"}"#,
+			"",
+			// The prefix must be at the start of the line
+			&format!(
+				" {}{{\"type\": \"error\", \"message\": \"Indented\"}}",
+				STATUS_LINE_PREFIX
+			),
+		] {
+			assert!(!status.update_from_line(line), "{}", line);
+		}
+
+		assert_eq!(status.phase, None);
+		assert!(status.errors.is_empty());
+	}
+}
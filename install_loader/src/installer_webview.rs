@@ -2,9 +2,16 @@ use std::{fs, thread, sync::mpsc::{Sender, self}, path::{Path, PathBuf}};
 
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
-use wry::{application::{window::Window, dpi::{PhysicalSize, PhysicalPosition}, platform::windows::EventLoopExtWindows, event_loop::EventLoopProxy, error::NotSupportedError}, webview::WebContext};
+use wry::{application::{window::Window, dpi::{PhysicalSize, PhysicalPosition}, event_loop::EventLoopProxy, error::NotSupportedError}, webview::WebContext};
+// The webview's event loop is run on its own thread, which needs new_any_thread()
+#[cfg(windows)]
+use wry::application::platform::windows::EventLoopExtWindows;
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+use wry::application::platform::unix::EventLoopExtUnix;
 
-use crate::{config::InstallerConfig, resources};
+use crate::{config::InstallerConfig, resources, session_token};
+
+pub const WINDOW_TITLE: &str = "07th-Mod Installer";
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ServerInfo {
@@ -13,23 +20,36 @@ struct ServerInfo {
     page: String,
 }
 
-pub fn get_url(config: &InstallerConfig) -> Result<String>
+fn read_server_info(config: &InstallerConfig) -> Result<ServerInfo>
 {
     let server_info_contents = fs::read_to_string(&config.server_info_path)?;
 
-    let server_info : ServerInfo = serde_json::from_str(server_info_contents.as_str())?;
+    Ok(serde_json::from_str(server_info_contents.as_str())?)
+}
+
+pub fn get_url(config: &InstallerConfig) -> Result<String>
+{
+    let server_info = read_server_info(config)?;
 
     // NOTE: We ignore the ip address in the .json as we should never be connecting to a host other than localhost
-    Ok(format!("http://127.0.0.1:{}/{}", server_info.port, server_info.page))
+    let url = format!("http://127.0.0.1:{}/{}", server_info.port, server_info.page);
+    Ok(session_token::add_to_url(&url, &config.session_token))
+}
+
+// The port the python installer's web server is listening on (always on localhost, like get_url())
+pub fn get_server_port(config: &InstallerConfig) -> Result<usize>
+{
+    Ok(read_server_info(config)?.port)
 }
 
 #[derive(Debug)]
 pub enum UserEvent {
     NavigateToURL(String),
     SetVisible(bool),
+    SetTitle(String),
 }
 
-pub fn launch<P: AsRef<Path>>(url: String, data_directory: P) -> Result<EventLoopProxy<UserEvent>> {
+pub fn launch<P: AsRef<Path>>(url: String, data_directory: P, token: String) -> Result<EventLoopProxy<UserEvent>> {
     let (tx, rx) = mpsc::channel();
     let data_directory = PathBuf::from(data_directory.as_ref());
 
@@ -37,14 +57,14 @@ pub fn launch<P: AsRef<Path>>(url: String, data_directory: P) -> Result<EventLoo
     // the whole program (not just this thread, but the entire program). So the returned
     // JoinHandle isn't very useful.
     thread::spawn(move || {
-        launch_inner(&url.to_string(), Some(data_directory), tx)
+        launch_inner(&url.to_string(), Some(data_directory), token, tx)
     });
 
     Ok(rx.recv()?)
 }
 
 
-fn launch_inner(url: &str, data_directory: Option<PathBuf>, tx: Sender<EventLoopProxy<UserEvent>>) -> Result<()> {
+fn launch_inner(url: &str, data_directory: Option<PathBuf>, token: String, tx: Sender<EventLoopProxy<UserEvent>>) -> Result<()> {
     use wry::{
         application::{
             event::{Event, StartCause, WindowEvent},
@@ -59,7 +79,7 @@ fn launch_inner(url: &str, data_directory: Option<PathBuf>, tx: Sender<EventLoop
     tx.send(event_loop.create_proxy())?;
 
     let window = WindowBuilder::new()
-        .with_title("07th-Mod Installer")
+        .with_title(WINDOW_TITLE)
         .build(&event_loop)?;
 
     let (window_position, window_size) = window_position_size(&window);
@@ -77,7 +97,17 @@ fn launch_inner(url: &str, data_directory: Option<PathBuf>, tx: Sender<EventLoop
 
     let webview = WebViewBuilder::new(window)?
         .with_web_context(&mut web_context)
-        .with_new_window_req_handler(|url| { webbrowser::open(&url).is_err() })
+        // Links which open a new window are opened in the web browser instead. The browser doesn't have the
+        // webview's session cookie, so installer pages are given the session token, or they would be refused.
+        // If the browser can't be opened, the link is opened in a new webview window.
+        .with_new_window_req_handler(move |url| {
+            let url = if session_token::is_installer_url(&url) {
+                session_token::add_to_url(&url, &token)
+            } else {
+                url
+            };
+            webbrowser::open(&url).is_err()
+        })
         .with_url(url)?;
 
     #[cfg(debug_assertions)]
@@ -95,6 +125,7 @@ fn launch_inner(url: &str, data_directory: Option<PathBuf>, tx: Sender<EventLoop
         match event {
             Event::UserEvent(UserEvent::NavigateToURL(url)) => webview.load_url(url.as_str()),
             Event::UserEvent(UserEvent::SetVisible(visible)) => webview.window().set_visible(visible),
+            Event::UserEvent(UserEvent::SetTitle(title)) => webview.window().set_title(&title),
             Event::NewEvents(StartCause::Init) => println!("Wry has started!"),
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
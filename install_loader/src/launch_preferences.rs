@@ -0,0 +1,275 @@
+use crate::config::{InstallerConfig, LaunchType};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// Kept in the extraction folder, so it is carried over when a new version is extracted
+const STATE_FILE_NAME: &str = "launcher-state.json";
+
+// The order launch types are tried in, if nothing is known about them
+const LAUNCH_TYPE_ORDER: [LaunchType; 3] = [
+	LaunchType::WebView,
+	LaunchType::Browser,
+	LaunchType::TextMode,
+];
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct LaunchState {
+	// The launch type which most recently worked
+	last_working: Option<LaunchType>,
+	// Launch types which failed, and haven't worked since
+	failed: Vec<LaunchType>,
+}
+
+// Remembers which launch types worked and which failed on previous runs, so users whose webview doesn't
+// work don't have to restart the installer in their web browser every time. A launch type works once
+// the installer has started (its web server started, or the text mode installer exited cleanly).
+// It failed if python kept crashing with it, the webview couldn't be opened, or the user restarted
+// the installer in another mode.
+pub struct LaunchPreferences {
+	path: PathBuf,
+	state: LaunchState,
+}
+
+impl LaunchPreferences {
+	// Missing or invalid state files are ignored, so nothing is remembered
+	pub fn load(config: &InstallerConfig) -> LaunchPreferences {
+		LaunchPreferences::load_from(config.sub_folder.join(STATE_FILE_NAME))
+	}
+
+	fn load_from(path: PathBuf) -> LaunchPreferences {
+		let state = match fs::read_to_string(&path) {
+			Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+				println!("Ignoring invalid launcher state file {:?}: {}", path, e);
+				LaunchState::default()
+			}),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => LaunchState::default(),
+			Err(e) => {
+				println!("Failed to read launcher state file {:?}: {}", path, e);
+				LaunchState::default()
+			}
+		};
+
+		LaunchPreferences { path, state }
+	}
+
+	// The launch type which last worked. Otherwise 'default', unless it failed, in which case the
+	// first launch type which hasn't failed.
+	pub fn preferred_launch_type(&self, default: LaunchType) -> LaunchType {
+		if let Some(last_working) = self.state.last_working {
+			return last_working;
+		}
+
+		if !self.state.failed.contains(&default) {
+			return default;
+		}
+
+		LAUNCH_TYPE_ORDER
+			.iter()
+			.copied()
+			.find(|launch_type| !self.state.failed.contains(launch_type))
+			.unwrap_or(default)
+	}
+
+	pub fn record_working(&mut self, launch_type: LaunchType) {
+		// The last working launch type is never in the failed list
+		if self.state.last_working == Some(launch_type) {
+			return;
+		}
+
+		println!("Remembering that {:?} mode works", launch_type);
+		self.state.last_working = Some(launch_type);
+		self.state.failed.retain(|failed| *failed != launch_type);
+		self.save();
+	}
+
+	pub fn record_failed(&mut self, launch_type: LaunchType) {
+		if self.state.failed.contains(&launch_type) {
+			return;
+		}
+
+		println!("Remembering that {:?} mode failed", launch_type);
+		if self.state.last_working == Some(launch_type) {
+			self.state.last_working = None;
+		}
+		self.state.failed.push(launch_type);
+		self.save();
+	}
+
+	// Forget everything, so the default launch type is used again
+	pub fn reset(&mut self) {
+		self.state = LaunchState::default();
+		if let Err(e) = fs::remove_file(&self.path) {
+			if e.kind() != io::ErrorKind::NotFound {
+				println!(
+					"Failed to remove launcher state file {:?}: {}",
+					self.path, e
+				);
+			}
+		}
+	}
+
+	// A short description of what is remembered, or None if nothing is
+	pub fn summary(&self) -> Option<String> {
+		let failed = self
+			.state
+			.failed
+			.iter()
+			.map(|launch_type| format!("{:?}", launch_type))
+			.collect::<Vec<_>>()
+			.join(", ");
+
+		match (self.state.last_working, failed.is_empty()) {
+			(None, true) => None,
+			(Some(last_working), true) => Some(format!("{:?} mode worked last time", last_working)),
+			(None, false) => Some(format!("Failed modes: {}", failed)),
+			(Some(last_working), false) => Some(format!(
+				"{:?} mode worked last time (failed modes: {})",
+				last_working, failed
+			)),
+		}
+	}
+
+	fn save(&self) {
+		let result = serde_json::to_string_pretty(&self.state)
+			.map_err(|e| e.to_string())
+			.and_then(|json| fs::write(&self.path, json).map_err(|e| e.to_string()));
+
+		if let Err(e) = result {
+			println!("Failed to save launcher state file {:?}: {}", self.path, e);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::LaunchType::*;
+
+	fn state_file(folder: &tempfile::TempDir) -> PathBuf {
+		folder.path().join(STATE_FILE_NAME)
+	}
+
+	fn load_with(folder: &tempfile::TempDir, contents: &str) -> LaunchPreferences {
+		fs::write(state_file(folder), contents).unwrap();
+		LaunchPreferences::load_from(state_file(folder))
+	}
+
+	#[test]
+	fn missing_state_file_uses_default() {
+		let folder = tempfile::tempdir().unwrap();
+		let preferences = LaunchPreferences::load_from(state_file(&folder));
+
+		assert_eq!(preferences.preferred_launch_type(WebView), WebView);
+		assert_eq!(preferences.preferred_launch_type(TextMode), TextMode);
+		assert_eq!(preferences.summary(), None);
+	}
+
+	#[test]
+	fn remembers_launch_types_after_reloading() {
+		let folder = tempfile::tempdir().unwrap();
+		let mut preferences = LaunchPreferences::load_from(state_file(&folder));
+		preferences.record_failed(WebView);
+		preferences.record_working(Browser);
+
+		let reloaded = LaunchPreferences::load_from(state_file(&folder));
+		assert_eq!(reloaded.preferred_launch_type(WebView), Browser);
+		assert_eq!(
+			reloaded.summary(),
+			Some(String::from(
+				"Browser mode worked last time (failed modes: WebView)"
+			))
+		);
+	}
+
+	#[test]
+	fn reads_state_file() {
+		let folder = tempfile::tempdir().unwrap();
+		let preferences = load_with(
+			&folder,
+			r#"{"lastWorking": "TextMode", "failed": ["WebView", "Browser"]}"#,
+		);
+		assert_eq!(preferences.preferred_launch_type(WebView), TextMode);
+
+		// Settings missing from the file are left empty
+		let preferences = load_with(&folder, r#"{"failed": ["WebView"]}"#);
+		assert_eq!(preferences.preferred_launch_type(WebView), Browser);
+		assert_eq!(preferences.preferred_launch_type(TextMode), TextMode);
+	}
+
+	#[test]
+	fn skips_failed_launch_types() {
+		let folder = tempfile::tempdir().unwrap();
+		let preferences = load_with(&folder, r#"{"failed": ["WebView", "Browser"]}"#);
+		assert_eq!(preferences.preferred_launch_type(WebView), TextMode);
+
+		// If every launch type failed, the default is tried again
+		let preferences = load_with(&folder, r#"{"failed": ["WebView", "Browser", "TextMode"]}"#);
+		assert_eq!(preferences.preferred_launch_type(Browser), Browser);
+	}
+
+	#[test]
+	fn failing_launch_type_forgets_that_it_worked() {
+		let folder = tempfile::tempdir().unwrap();
+		let mut preferences = load_with(&folder, r#"{"lastWorking": "WebView"}"#);
+		preferences.record_failed(WebView);
+		assert_eq!(preferences.preferred_launch_type(WebView), Browser);
+
+		preferences.record_working(WebView);
+		let reloaded = LaunchPreferences::load_from(state_file(&folder));
+		assert_eq!(reloaded.preferred_launch_type(TextMode), WebView);
+		assert_eq!(
+			reloaded.summary(),
+			Some(String::from("WebView mode worked last time"))
+		);
+	}
+
+	#[test]
+	fn ignores_invalid_state_files() {
+		let folder = tempfile::tempdir().unwrap();
+		for contents in &[
+			"",
+			"{\"lastWorking\": \"Browser\"",
+			"\"Browser\"",
+			// Written by a loader with a launch type this one doesn't know about
+			r#"{"lastWorking": "Terminal", "failed": ["WebView"]}"#,
+			r#"{"lastWorking": "Browser", "failed": "WebView"}"#,
+		] {
+			let preferences = load_with(&folder, contents);
+			assert_eq!(
+				preferences.preferred_launch_type(WebView),
+				WebView,
+				"{}",
+				contents
+			);
+			assert_eq!(preferences.summary(), None, "{}", contents);
+		}
+	}
+
+	#[test]
+	fn ignores_unreadable_state_file() {
+		let folder = tempfile::tempdir().unwrap();
+		fs::create_dir(state_file(&folder)).unwrap();
+
+		let preferences = LaunchPreferences::load_from(state_file(&folder));
+		assert_eq!(preferences.preferred_launch_type(Browser), Browser);
+	}
+
+	#[test]
+	fn reset_forgets_everything() {
+		let folder = tempfile::tempdir().unwrap();
+		let mut preferences = load_with(
+			&folder,
+			r#"{"lastWorking": "Browser", "failed": ["WebView"]}"#,
+		);
+		preferences.reset();
+
+		assert!(!state_file(&folder).exists());
+		assert_eq!(preferences.preferred_launch_type(WebView), WebView);
+		assert_eq!(preferences.summary(), None);
+		// Resetting again when there is no state file is fine
+		preferences.reset();
+	}
+}
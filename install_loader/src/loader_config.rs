@@ -0,0 +1,560 @@
+use crate::config::LaunchType;
+use crate::windows_utilities;
+use clap::{Arg, ArgMatches};
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// The loader's settings are layered. Each setting is read from the first of these which sets it:
+//  - the command line, as --<name>
+//  - an environment variable, SEVENTH_MOD_<NAME> (e.g. SEVENTH_MOD_EXTRACTION_ROOT)
+//  - loader.toml next to the loader .exe, as <name> (e.g. extraction-root = "D:/07th-mod")
+//  - the default
+// Relative paths in loader.toml are relative to the .exe, and others are relative to the current
+// directory the loader was started in.
+pub const CONFIG_FILE_NAME: &str = "loader.toml";
+const ENV_VAR_PREFIX: &str = "SEVENTH_MOD_";
+
+const DEFAULT_EXTRACTION_ROOT: &str = "07th-mod_installer";
+
+// How long python is given to exit after being asked to shut down, before it is killed
+const DEFAULT_PYTHON_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+const EXTRACTION_ROOT: &str = "extraction-root";
+const LOGS_FOLDER: &str = "logs-folder";
+const PYTHON: &str = "python";
+const LAUNCH_TYPE: &str = "launch-type";
+const PYTHON_ARGS: &str = "python-args";
+const TEMP_DIR: &str = "temp-dir";
+const PYTHON_SHUTDOWN_TIMEOUT: &str = "python-shutdown-timeout";
+
+// When the installer is extracted to a temporary folder, instead of the extraction root
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TempDirPolicy {
+	// Only if the user chooses to restart the installer in a temporary folder
+	OnFailure,
+	// The installer is always extracted to a temporary folder
+	Always,
+	// The installer is never extracted to a temporary folder
+	Never,
+}
+
+impl fmt::Display for TempDirPolicy {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			TempDirPolicy::OnFailure => write!(f, "on-failure"),
+			TempDirPolicy::Always => write!(f, "always"),
+			TempDirPolicy::Never => write!(f, "never"),
+		}
+	}
+}
+
+// Where the value of a setting came from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SettingSource {
+	Default,
+	ConfigFile,
+	Environment,
+	CommandLine,
+}
+
+#[derive(Clone)]
+pub struct Setting<T> {
+	pub value: T,
+	pub source: SettingSource,
+}
+
+#[derive(Clone)]
+pub struct LoaderConfig {
+	// The loader.toml file which was read, or would have been read if it existed
+	pub config_file: PathBuf,
+	pub config_file_found: bool,
+	// Folder the installer is extracted to
+	pub extraction_root: Setting<PathBuf>,
+	// Where the launcher logs the installer's output (see ProcessRunner). None to use INSTALLER_LOGS
+	// in the extraction root, which is also where the python installer writes its own logs.
+	pub logs_folder: Setting<Option<PathBuf>>,
+	// Python interpreter to use if the bundled one is missing or broken (see python_interpreter::resolve())
+	pub python: Setting<Option<PathBuf>>,
+	// How the installer is launched once extraction has finished
+	pub launch_type: Setting<LaunchType>,
+	// Extra arguments for the installer script (main.py or cli_interactive.py), like "--asset-os windows"
+	pub python_args: Setting<Vec<String>>,
+	pub temp_dir: Setting<TempDirPolicy>,
+	// How long python is given to exit after being asked to shut down, before it is killed
+	pub python_shutdown_timeout: Setting<Duration>,
+}
+
+// The contents of loader.toml. Every setting is optional.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+	extraction_root: Option<PathBuf>,
+	logs_folder: Option<PathBuf>,
+	python: Option<PathBuf>,
+	launch_type: Option<String>,
+	python_args: Option<Vec<String>>,
+	temp_dir: Option<String>,
+	python_shutdown_timeout: Option<u64>,
+}
+
+// The command line flags for each setting. They are global, so they also apply to 'config --print'.
+pub fn args() -> Vec<Arg<'static>> {
+	vec![
+		Arg::with_name(EXTRACTION_ROOT)
+			.long(EXTRACTION_ROOT)
+			.takes_value(true)
+			.value_name("DIR")
+			.global(true)
+			.help("Folder to extract the installer to (default: 07th-mod_installer)"),
+		Arg::with_name(LOGS_FOLDER)
+			.long(LOGS_FOLDER)
+			.takes_value(true)
+			.value_name("DIR")
+			.global(true)
+			.help("Folder the launcher logs the installer's output to, opened by 'Show Installer Logs' (default: INSTALLER_LOGS in the extraction folder)"),
+		Arg::with_name(PYTHON)
+			.long(PYTHON)
+			.takes_value(true)
+			.value_name("PATH")
+			.global(true)
+			.help("Python interpreter to run the installer with, if the bundled one is missing or broken (like in the Linux bundle). If neither works, python3 on the PATH is used"),
+		Arg::with_name(LAUNCH_TYPE)
+			.long(LAUNCH_TYPE)
+			.takes_value(true)
+			.value_name("TYPE")
+			.global(true)
+			.help("How to launch the installer: webview, browser or text (default: webview)"),
+		Arg::with_name(PYTHON_ARGS)
+			.long(PYTHON_ARGS)
+			.takes_value(true)
+			.value_name("ARGS")
+			.allow_hyphen_values(true)
+			.global(true)
+			.help("Extra arguments for the installer script, separated by spaces. Quote arguments which contain spaces (e.g. \"--asset-os windows --launcher-path 'C:/Program Files/launcher.exe'\")"),
+		Arg::with_name(TEMP_DIR)
+			.long(TEMP_DIR)
+			.takes_value(true)
+			.value_name("POLICY")
+			.global(true)
+			.help("When to extract the installer to a temporary folder: on-failure (offered if the installer fails), always or never (default: on-failure)"),
+		Arg::with_name(PYTHON_SHUTDOWN_TIMEOUT)
+			.long(PYTHON_SHUTDOWN_TIMEOUT)
+			.takes_value(true)
+			.value_name("SECONDS")
+			.global(true)
+			.help("How long to wait for the Python installer to exit after asking it to shut down, before killing it (default: 10)"),
+	]
+}
+
+impl LoaderConfig {
+	// Must be called before the current directory is changed, as paths on the command line and in
+	// environment variables may be relative to it
+	pub fn load(matches: &ArgMatches) -> Result<LoaderConfig, String> {
+		let exe_folder = env::current_exe()
+			.ok()
+			.and_then(|exe_path| exe_path.parent().map(Path::to_path_buf))
+			.unwrap_or_default();
+		let config_file = exe_folder.join(CONFIG_FILE_NAME);
+
+		let (file, config_file_found) = match fs::read_to_string(&config_file) {
+			Ok(text) => (
+				toml::from_str::<ConfigFile>(&text)
+					.map_err(|e| format!("{} is invalid: {}", config_file.display(), e))?,
+				true,
+			),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => (ConfigFile::default(), false),
+			Err(e) => return Err(format!("Couldn't read {}: {}", config_file.display(), e)),
+		};
+
+		let file_path = |path: PathBuf| exe_folder.join(path);
+
+		Ok(LoaderConfig {
+			extraction_root: layered(
+				matches,
+				EXTRACTION_ROOT,
+				file.extraction_root.map(file_path),
+				|value| Ok(command_line_path(value)),
+			)?
+			.unwrap_or_else(|| Setting::from_default(PathBuf::from(DEFAULT_EXTRACTION_ROOT))),
+			logs_folder: layered(
+				matches,
+				LOGS_FOLDER,
+				file.logs_folder.map(file_path),
+				|value| Ok(command_line_path(value)),
+			)?
+			.map_or_else(|| Setting::from_default(None), Setting::some),
+			python: layered(
+				matches,
+				PYTHON,
+				file.python.map(|path| python_path(path, &exe_folder)),
+				|value| {
+					Ok(python_path(
+						PathBuf::from(value),
+						&env::current_dir().unwrap_or_default(),
+					))
+				},
+			)?
+			.map_or_else(|| Setting::from_default(None), Setting::some),
+			launch_type: layered(
+				matches,
+				LAUNCH_TYPE,
+				file.launch_type
+					.as_deref()
+					.map(parse_launch_type)
+					.transpose()
+					.map_err(|e| invalid_in_file(LAUNCH_TYPE, e))?,
+				parse_launch_type,
+			)?
+			.unwrap_or_else(|| Setting::from_default(LaunchType::WebView)),
+			python_args: layered(matches, PYTHON_ARGS, file.python_args, split_arguments)?
+				.unwrap_or_else(|| Setting::from_default(Vec::new())),
+			temp_dir: layered(
+				matches,
+				TEMP_DIR,
+				file.temp_dir
+					.as_deref()
+					.map(parse_temp_dir_policy)
+					.transpose()
+					.map_err(|e| invalid_in_file(TEMP_DIR, e))?,
+				parse_temp_dir_policy,
+			)?
+			.unwrap_or_else(|| Setting::from_default(TempDirPolicy::OnFailure)),
+			python_shutdown_timeout: layered(
+				matches,
+				PYTHON_SHUTDOWN_TIMEOUT,
+				file.python_shutdown_timeout.map(Duration::from_secs),
+				|value| {
+					value
+						.parse()
+						.map(Duration::from_secs)
+						.map_err(|_| format!("[{}] isn't a number of seconds", value))
+				},
+			)?
+			.unwrap_or_else(|| Setting::from_default(DEFAULT_PYTHON_SHUTDOWN_TIMEOUT)),
+			config_file,
+			config_file_found,
+		})
+	}
+
+	// Prints each setting's value, and where it came from (for 'config --print')
+	pub fn print(&self) {
+		println!(
+			"Configuration file: {} ({})",
+			self.config_file.display(),
+			if self.config_file_found {
+				"found"
+			} else {
+				"not found"
+			}
+		);
+		println!();
+
+		print_setting(
+			EXTRACTION_ROOT,
+			self.extraction_root.value.display(),
+			self.extraction_root.source,
+		);
+		print_setting(
+			LOGS_FOLDER,
+			match &self.logs_folder.value {
+				Some(logs_folder) => logs_folder.display().to_string(),
+				None => String::from("INSTALLER_LOGS in the extraction folder"),
+			},
+			self.logs_folder.source,
+		);
+		print_setting(
+			PYTHON,
+			match &self.python.value {
+				Some(python) => python.display().to_string(),
+				None => String::from("(not set)"),
+			},
+			self.python.source,
+		);
+		print_setting(
+			LAUNCH_TYPE,
+			launch_type_name(self.launch_type.value),
+			self.launch_type.source,
+		);
+		print_setting(
+			PYTHON_ARGS,
+			format!("{:?}", self.python_args.value),
+			self.python_args.source,
+		);
+		print_setting(TEMP_DIR, self.temp_dir.value, self.temp_dir.source);
+		print_setting(
+			PYTHON_SHUTDOWN_TIMEOUT,
+			format!("{} seconds", self.python_shutdown_timeout.value.as_secs()),
+			self.python_shutdown_timeout.source,
+		);
+	}
+}
+
+impl<T> Setting<T> {
+	fn from_default(value: T) -> Setting<T> {
+		Setting {
+			value,
+			source: SettingSource::Default,
+		}
+	}
+
+	fn some(setting: Setting<T>) -> Setting<Option<T>> {
+		Setting {
+			value: Some(setting.value),
+			source: setting.source,
+		}
+	}
+}
+
+// The environment variable for a setting, e.g. SEVENTH_MOD_EXTRACTION_ROOT for "extraction-root"
+fn env_var_name(name: &str) -> String {
+	format!(
+		"{}{}",
+		ENV_VAR_PREFIX,
+		name.to_uppercase().replace('-', "_")
+	)
+}
+
+// Reads a setting from the command line or its environment variable (which are parsed with 'parse'),
+// or from loader.toml. Returns None if none of them set it.
+fn layered<T>(
+	matches: &ArgMatches,
+	name: &str,
+	from_file: Option<T>,
+	parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Option<Setting<T>>, String> {
+	if let Some(value) = matches.value_of(name) {
+		return parse(value)
+			.map(|value| {
+				Some(Setting {
+					value,
+					source: SettingSource::CommandLine,
+				})
+			})
+			.map_err(|e| format!("Invalid --{}: {}", name, e));
+	}
+
+	let env_var = env_var_name(name);
+	if let Some(value) = env::var(&env_var).ok().filter(|value| !value.is_empty()) {
+		return parse(&value)
+			.map(|value| {
+				Some(Setting {
+					value,
+					source: SettingSource::Environment,
+				})
+			})
+			.map_err(|e| format!("Invalid {}: {}", env_var, e));
+	}
+
+	Ok(from_file.map(|value| Setting {
+		value,
+		source: SettingSource::ConfigFile,
+	}))
+}
+
+fn invalid_in_file(name: &str, error: String) -> String {
+	format!("Invalid {} in {}: {}", name, CONFIG_FILE_NAME, error)
+}
+
+fn command_line_path(value: &str) -> PathBuf {
+	windows_utilities::absolute_path(value).unwrap_or_else(|_| PathBuf::from(value))
+}
+
+// Names without a directory (like "python3.11") are looked up on the PATH when python is started,
+// so are left as they are
+fn python_path(path: PathBuf, relative_to: &Path) -> PathBuf {
+	if path.components().count() > 1 {
+		relative_to.join(path)
+	} else {
+		path
+	}
+}
+
+// Splits --python-args or SEVENTH_MOD_PYTHON_ARGS into separate arguments, like a shell would.
+// Arguments may be quoted with ' or " to include spaces. Backslashes aren't escape characters,
+// so Windows paths can be written as they are.
+fn split_arguments(value: &str) -> Result<Vec<String>, String> {
+	let mut arguments = Vec::new();
+	let mut current: Option<String> = None;
+	let mut quote = None;
+
+	for c in value.chars() {
+		match quote {
+			Some(q) if c == q => quote = None,
+			Some(_) => current.get_or_insert_with(String::new).push(c),
+			None if c == '\'' || c == '"' => {
+				quote = Some(c);
+				// An empty quoted argument ("") is still an argument
+				current.get_or_insert_with(String::new);
+			}
+			None if c.is_whitespace() => arguments.extend(current.take()),
+			None => current.get_or_insert_with(String::new).push(c),
+		}
+	}
+
+	if let Some(q) = quote {
+		return Err(format!("[{}] has an unclosed {} quote", value, q));
+	}
+	arguments.extend(current);
+	Ok(arguments)
+}
+
+fn parse_launch_type(value: &str) -> Result<LaunchType, String> {
+	match value {
+		"webview" => Ok(LaunchType::WebView),
+		"browser" => Ok(LaunchType::Browser),
+		"text" => Ok(LaunchType::TextMode),
+		_ => Err(format!(
+			"unknown launch type [{}] - expected webview, browser or text",
+			value
+		)),
+	}
+}
+
+fn launch_type_name(launch_type: LaunchType) -> &'static str {
+	match launch_type {
+		LaunchType::WebView => "webview",
+		LaunchType::Browser => "browser",
+		LaunchType::TextMode => "text",
+	}
+}
+
+fn parse_temp_dir_policy(value: &str) -> Result<TempDirPolicy, String> {
+	match value {
+		"on-failure" => Ok(TempDirPolicy::OnFailure),
+		"always" => Ok(TempDirPolicy::Always),
+		"never" => Ok(TempDirPolicy::Never),
+		_ => Err(format!(
+			"unknown temp dir policy [{}] - expected on-failure, always or never",
+			value
+		)),
+	}
+}
+
+fn print_setting(name: &str, value: impl fmt::Display, source: SettingSource) {
+	let source = match source {
+		SettingSource::Default => String::from("default"),
+		SettingSource::ConfigFile => String::from(CONFIG_FILE_NAME),
+		SettingSource::Environment => env_var_name(name),
+		SettingSource::CommandLine => format!("--{}", name),
+	};
+
+	println!("{:<25} = {}  ({})", name, value, source);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::App;
+
+	// Each test uses its own setting name, so the environment variables don't affect other tests
+	// running at the same time
+	fn matches_for(name: &'static str, command_line: &[&str]) -> ArgMatches {
+		App::new("loader")
+			.arg(Arg::with_name(name).long(name).takes_value(true))
+			.get_matches_from(std::iter::once("loader").chain(command_line.iter().copied()))
+	}
+
+	fn parse_number(value: &str) -> Result<u32, String> {
+		value
+			.parse()
+			.map_err(|_| format!("[{}] isn't a number", value))
+	}
+
+	fn value_and_source(setting: Option<Setting<u32>>) -> Option<(u32, SettingSource)> {
+		setting.map(|setting| (setting.value, setting.source))
+	}
+
+	#[test]
+	fn command_line_overrides_environment_and_file() {
+		let name = "test-command-line";
+		env::set_var(env_var_name(name), "2");
+		let matches = matches_for(name, &["--test-command-line", "1"]);
+		let setting = layered(&matches, name, Some(3), parse_number);
+		env::remove_var(env_var_name(name));
+
+		assert_eq!(
+			value_and_source(setting.unwrap()),
+			Some((1, SettingSource::CommandLine))
+		);
+	}
+
+	#[test]
+	fn environment_overrides_file() {
+		let name = "test-environment";
+		env::set_var(env_var_name(name), "2");
+		let setting = layered(&matches_for(name, &[]), name, Some(3), parse_number);
+		env::remove_var(env_var_name(name));
+
+		assert_eq!(
+			value_and_source(setting.unwrap()),
+			Some((2, SettingSource::Environment))
+		);
+	}
+
+	#[test]
+	fn empty_environment_variable_is_ignored() {
+		let name = "test-empty-environment";
+		env::set_var(env_var_name(name), "");
+		let setting = layered(&matches_for(name, &[]), name, Some(3), parse_number);
+		env::remove_var(env_var_name(name));
+
+		assert_eq!(
+			value_and_source(setting.unwrap()),
+			Some((3, SettingSource::ConfigFile))
+		);
+	}
+
+	#[test]
+	fn unset_setting_is_none() {
+		let name = "test-unset";
+		let setting = layered(&matches_for(name, &[]), name, None, parse_number);
+
+		assert_eq!(value_and_source(setting.unwrap()), None);
+	}
+
+	#[test]
+	fn invalid_values_name_where_they_came_from() {
+		let name = "test-invalid";
+		let matches = matches_for(name, &["--test-invalid", "one"]);
+		assert_eq!(
+			layered(&matches, name, None, parse_number).err(),
+			Some(String::from("Invalid --test-invalid: [one] isn't a number"))
+		);
+
+		env::set_var(env_var_name(name), "two");
+		let setting = layered(&matches_for(name, &[]), name, None, parse_number);
+		env::remove_var(env_var_name(name));
+		assert_eq!(
+			setting.err(),
+			Some(String::from(
+				"Invalid SEVENTH_MOD_TEST_INVALID: [two] isn't a number"
+			))
+		);
+	}
+
+	#[test]
+	fn splits_arguments_like_a_shell() {
+		assert_eq!(
+			split_arguments("  --asset-os   windows ").unwrap(),
+			vec!["--asset-os", "windows"]
+		);
+		assert_eq!(
+			split_arguments(r#"--launcher-path "C:\Program Files\launcher.exe" 'a "b"' x""y"#)
+				.unwrap(),
+			vec![
+				"--launcher-path",
+				r"C:\Program Files\launcher.exe",
+				r#"a "b""#,
+				"xy"
+			]
+		);
+		assert_eq!(split_arguments(r#"'' """#).unwrap(), vec!["", ""]);
+		assert!(split_arguments("").unwrap().is_empty());
+		assert!(split_arguments("--launcher-path 'C:/Program Files").is_err());
+	}
+}
@@ -1,26 +1,46 @@
 #![warn(clippy::all)]
 
+use crate::loader_config::LoaderConfig;
+use crate::payload::PayloadSource;
 use crate::program_instance_lock::ProgramInstanceLock;
+#[cfg(windows)]
 use crate::windows_message_box::{IconType, MessageBoxButtons, MessageBoxResult};
 use clap::{App, Arg, ArgMatches};
 use std::error::Error;
 use std::path::PathBuf;
 
 mod archive_extractor;
+mod binary_patch;
+mod compression;
 mod config;
+mod extract_command;
+mod extraction_journal;
+mod install_status;
+mod launch_preferences;
+mod loader_config;
 mod panic_handler;
+mod payload;
 mod process_runner;
 mod program_instance_lock;
+mod python_health_check;
+mod python_interpreter;
 mod python_launcher;
+mod python_shutdown;
+mod python_supervisor;
+mod rotating_log;
+mod session_token;
 mod support; // This module is copied from the imgui-rs examples
 mod ui;
 mod version;
+#[cfg(windows)]
 mod windows_dialog;
+#[cfg(windows)]
 mod windows_message_box;
 mod windows_utilities;
 mod installer_webview;
 mod resources;
 
+#[cfg(windows)]
 fn handle_open_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
 	// Expect filters to be given as description1, filter1, description2, filter2
 	let filters: Vec<(&str, &str)> = match matches.values_of("filters") {
@@ -44,6 +64,52 @@ fn handle_open_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
 	}
 }
 
+// The python installer only uses the native file chooser on Windows (see askPathWindowsLauncher() in httpGUI.py)
+#[cfg(not(windows))]
+fn handle_open_command(_matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+	Err("The open command is only supported on Windows".into())
+}
+
+// Asks the user whether to continue, if another copy of the installer seems to be running.
+// Returns None if the user couldn't be asked.
+#[cfg(windows)]
+fn confirm_start_while_running() -> Option<bool> {
+	let user_choice = windows_message_box::create(
+		"07th-Mod Installer Already Running",
+		r#"Warning: The installer is already running. It's recommended that you close installer before starting it again.
+
+Continue anyway?
+(Please also make sure the current folder is writeable)"#,
+		IconType::Info,
+		MessageBoxButtons::YesNo,
+	);
+
+	match user_choice.unwrap_or(MessageBoxResult::Unknown) {
+		MessageBoxResult::Yes => Some(true),
+		MessageBoxResult::No => Some(false),
+		_ => None,
+	}
+}
+
+// There's no message box elsewhere, so the lock error is shown in the console instead
+#[cfg(not(windows))]
+fn confirm_start_while_running() -> Option<bool> {
+	None
+}
+
+// Payload paths must be resolved before the current directory is changed, as they may be relative
+fn payload_sources(matches: &ArgMatches) -> (PayloadSource, Option<PathBuf>) {
+	let resolve_path = |path: &str| std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+
+	let payload_source = match matches.value_of("payload") {
+		Some(path) => PayloadSource::File(resolve_path(path)),
+		None => PayloadSource::Default,
+	};
+	let delta_payload = matches.value_of("delta-payload").map(resolve_path);
+
+	(payload_source, delta_payload)
+}
+
 fn fix_cwd() -> Result<PathBuf, Box<dyn Error>> {
 	let exe_path = std::env::current_exe()?;
 	let containing_path = exe_path.parent().ok_or("Invalid Path")?;
@@ -53,7 +119,6 @@ fn fix_cwd() -> Result<PathBuf, Box<dyn Error>> {
 
 fn main() -> Result<(), Box<dyn Error>> {
 	let no_launcher_gui = option_env!("NO_LAUNCHER_GUI").is_some();
-	panic_handler::set_hook(String::from("07th-mod_crash.log"));
 
 	//////////////////////////// Begin file chooser code ///////////////////////////////////////////
 	let open_about_msg = r#"Shows an open dialog and:
@@ -64,20 +129,124 @@ fn main() -> Result<(), Box<dyn Error>> {
 	let open_help_msg = r#"Sets the description and filters to use - defaults to all files.
 For example, open "text and pdf" "*.txt;*.pdf" "main c file" "main.c""#;
 
+	let payload_help_msg = r#"Load the installer data from this file instead of the data appended to or compiled into the .exe.
+Either a payload file created by stamp_payload.py, or a plain .tar.xz/.tar.zst archive (which can't be verified after extraction)"#;
+
+	let delta_payload_help_msg = r#"Update the previous extraction using this delta payload (created by make_delta_payload.py).
+If the delta doesn't apply to the previous extraction, the full installer data is extracted instead"#;
+
+	let config_about_msg = r#"Shows the loader's settings. Each setting is taken from the command line, then SEVENTH_MOD_* environment variables,
+then loader.toml next to the loader, then its default"#;
+
 	let matches = App::new("07th-mod Installer Loader")
 		.version(version::travis_tag())
 		.about("Loader which extracts and starts the Python-based 07th-mod Installer.")
+		.arg(
+			Arg::with_name("payload")
+				.long("payload")
+				.takes_value(true)
+				.value_name("FILE")
+				.global(true)
+				.help(payload_help_msg),
+		)
+		.arg(
+			Arg::with_name("delta-payload")
+				.long("delta-payload")
+				.takes_value(true)
+				.value_name("FILE")
+				.global(true)
+				.help(delta_payload_help_msg),
+		)
+		.args(loader_config::args())
 		.subcommand(
 			App::new("open")
 				.about(open_about_msg)
 				.arg(Arg::with_name("filters").help(open_help_msg).multiple(true)),
 		)
+		.subcommand(
+			App::new("config")
+				.about(config_about_msg)
+				.arg(
+					Arg::with_name("print")
+						.long("print")
+						.required(true)
+						.help("Print the value of each setting, and where it came from"),
+				),
+		)
+		.subcommand(
+			App::new("extract")
+				.about(extract_command::ABOUT_MSG)
+				.arg(
+					Arg::with_name("dest")
+						.long("dest")
+						.takes_value(true)
+						.value_name("DIR")
+						.help("Folder to extract to, or to verify with --verify (default: the extraction root, see 'config --print')"),
+				)
+				.arg(
+					Arg::with_name("verify")
+						.long("verify")
+						.help("Only verify the files in --dest against the manifest, without extracting or repairing them"),
+				)
+				.arg(
+					Arg::with_name("list")
+						.long("list")
+						.help("Only list the contents of the installer data, without extracting it"),
+				)
+				.arg(
+					Arg::with_name("json-progress")
+						.long("json-progress")
+						.help("Print progress and results as JSON lines"),
+				)
+				.arg(
+					Arg::with_name("force")
+						.long("force")
+						.help("Extract even if this version of the installer data was already extracted"),
+				),
+		)
 		.get_matches();
 
 	if let Some(matches) = matches.subcommand_matches("open") {
 		return handle_open_command(matches);
 	}
 
+	// The extract command runs in the current directory, without the GUI or the crash handler's fallback installer
+	if let Some(matches) = matches.subcommand_matches("extract") {
+		let (payload_source, delta_payload) = payload_sources(matches);
+		std::process::exit(extract_command::handle_extract_command(
+			matches,
+			payload_source,
+			delta_payload,
+		));
+	}
+
+	if let Some(matches) = matches.subcommand_matches("config") {
+		let loader_config = LoaderConfig::load(matches)?;
+		loader_config.print();
+		return Ok(());
+	}
+
+	let (payload_source, delta_payload) = payload_sources(&matches);
+
+	// Loaded before the current directory is changed, as paths in the settings may be relative to it
+	let loader_config = match LoaderConfig::load(&matches) {
+		Ok(loader_config) => loader_config,
+		Err(e) => {
+			panic_handler::pause(&format!(
+				"Invalid loader settings: {}\n\nPlease fix the settings, then press ENTER to quit",
+				e
+			));
+			return Err(e.into());
+		}
+	};
+
+	panic_handler::set_hook(
+		String::from("07th-mod_crash.log"),
+		payload_source.clone(),
+		delta_payload.clone(),
+		loader_config.clone(),
+	);
+
 	//////////////////////////// Begin normal installer code ///////////////////////////////////////
 	// Change current directory to .exe path, if current .exe path is known
 	let old_cwd = std::env::current_dir();
@@ -94,7 +263,11 @@ For example, open "text and pdf" "*.txt;*.pdf" "main c file" "main.c""#;
 	}
 
 	// _maybe_job must be kept in scope for the remainder of the program!
+	#[cfg(windows)]
 	let (_maybe_job, register_job_result) = windows_utilities::new_job_kill_on_job_close();
+	// Elsewhere, ProcessRunner kills python and the processes it started itself (see process_runner.rs)
+	#[cfg(not(windows))]
+	let register_job_result: Result<(), Box<dyn Error>> = Ok(());
 
 	// Hide the console to make the installer less scary
 	if !no_launcher_gui {
@@ -108,20 +281,10 @@ For example, open "text and pdf" "*.txt;*.pdf" "main c file" "main.c""#;
 			let current_dir_string = std::env::current_dir()
 				.map_or("Can't determine CWD".into(), |path| format!("{:?}", path));
 
-			let user_choice = windows_message_box::create(
-				"07th-Mod Installer Already Running",
-				r#"Warning: The installer is already running. It's recommended that you close installer before starting it again.
-
-Continue anyway?
-(Please also make sure the current folder is writeable)"#,
-				IconType::Info,
-				MessageBoxButtons::YesNo,
-			);
-
-			match user_choice.unwrap_or(MessageBoxResult::Unknown) {
-				MessageBoxResult::Yes => {}
-				MessageBoxResult::No => return Ok(()),
-				_ => {
+			match confirm_start_while_running() {
+				Some(true) => {}
+				Some(false) => return Ok(()),
+				None => {
 					panic_handler::pause(&format!(
 						r#"Failed to create lock file: {:?}
 
@@ -136,17 +299,17 @@ Please check the installer not already running, and folder [{}] is writeable"#,
 	};
 
 	if no_launcher_gui {
-		return panic_handler::fallback_installer_pause();
+		return panic_handler::fallback_installer_pause(&payload_source, &delta_payload, &loader_config);
 	} else if register_job_result.is_ok() {
 		// This function blocks forever until the user quits the graphical installer
-		ui::ui_loop();
+		ui::ui_loop(payload_source, delta_payload, loader_config);
 	} else {
 		// If job object not registered properly, use fallback/console installer
 		// This ensures that everything is cleaned up properly as windows will automatically
 		// clean up child processes when the console window is closed.
 		println!("Warning: Failed to register job object! You're probably using Windows 7!");
 		println!("Don't worry - you can use the terminal based installer below");
-		return panic_handler::fallback_installer_pause();
+		return panic_handler::fallback_installer_pause(&payload_source, &delta_payload, &loader_config);
 	}
 
 	Ok(())
@@ -9,7 +9,9 @@ use std::panic::PanicInfo;
 use crate::archive_extractor;
 use crate::archive_extractor::ExtractionStatus;
 use crate::config::{InstallerConfig, LaunchType};
-use crate::python_launcher;
+use crate::loader_config::LoaderConfig;
+use crate::payload::PayloadSource;
+use crate::{python_health_check, python_interpreter, python_launcher};
 use crate::version;
 use crate::windows_utilities;
 use std::path::PathBuf;
@@ -94,10 +96,14 @@ Please help us by reporting the error and submitting the crash log
 	expl
 }
 
-pub fn fallback_installer_pause() -> Result<(), Box<dyn Error>> {
+pub fn fallback_installer_pause(
+	payload_source: &PayloadSource,
+	delta_payload: &Option<PathBuf>,
+	loader_config: &LoaderConfig,
+) -> Result<(), Box<dyn Error>> {
 	windows_utilities::show_console_window();
 
-	if let Err(error) = fallback_installer() {
+	if let Err(error) = fallback_installer(payload_source, delta_payload, loader_config) {
 		println!("Fallback Installer has failed with: {:?}", error);
 		println!(
 			"
@@ -112,7 +118,11 @@ Please help us by taking a screenshot of the error and uploading it:
 	Ok(())
 }
 
-fn fallback_installer() -> Result<(), Box<dyn Error>> {
+fn fallback_installer(
+	payload_source: &PayloadSource,
+	delta_payload: &Option<PathBuf>,
+	loader_config: &LoaderConfig,
+) -> Result<(), Box<dyn Error>> {
 	eprintln!("\n------------- NOTE: 'Fallback Mode' is available ----------");
 
 	// Check if the installer is being run from a temporary folder
@@ -181,20 +191,62 @@ Please make sure it's installed.
 			Some(x) if x == "0" => LaunchType::WebView,
 			Some(x) if x == "1" => LaunchType::Browser,
 			Some(x) if x == "2" => LaunchType::TextMode,
-			_ => LaunchType::WebView,
+			// Otherwise use the configured launch type (WebView by default)
+			_ => loader_config.launch_type.value,
 		}
 	};
 
-	let config = InstallerConfig::new(&PathBuf::from("07th-mod_installer"), false);
-	let mut extractor = archive_extractor::ArchiveExtractor::new();
+	let config = InstallerConfig::new(
+		&loader_config.extraction_root.value,
+		false,
+		payload_source.clone(),
+		delta_payload.clone(),
+		loader_config,
+	);
+
+	let new_extractor = || {
+		archive_extractor::ArchiveExtractor::new(
+			false,
+			config.payload_source.clone(),
+			config.delta_payload.clone(),
+		)
+	};
+
+	// The extraction thread first checks there is enough disk space, and the extraction folder is writeable
+	let mut extractor = new_extractor();
+	extractor.enable_preflight_checks();
 	extractor.start_extraction(&config.sub_folder);
 
 	loop {
 		match extractor.poll_status() {
+			ExtractionStatus::PreflightFailed(reason) => {
+				loop_until_valid_input(
+					&format!(
+						"Warning: {}\n\n> (If you wish to continue anyway, type 'y' and press ENTER)\n",
+						reason
+					),
+					vec!["y"],
+				);
+
+				extractor = new_extractor();
+				extractor.start_extraction(&config.sub_folder);
+			}
 			ExtractionStatus::Started(Some(progress)) => {
-				println!("Extraction is {}% complete", progress);
+				println!(
+					"Extraction is {}% complete - {} - {}",
+					progress.percentage,
+					progress.summary(),
+					progress.current_entry
+				);
 			}
-			ExtractionStatus::Finished => {
+			ExtractionStatus::Repairing(damaged_files) => {
+				println!(
+					"Restoring {} files which are missing or modified (possibly removed by antivirus): {:?}",
+					damaged_files.len(),
+					damaged_files
+				);
+			}
+			ExtractionStatus::Finished | ExtractionStatus::Cancelled => {
 				break;
 			}
 			ExtractionStatus::Error(err) => {
@@ -208,7 +260,14 @@ Please make sure it's installed.
 
 	println!("Extraction Complete - Please wait while installer starts in your browser...");
 
-	let launch_result = python_launcher::launch_python_script(&config, launch_type);
+	let python = python_interpreter::resolve(&config)?;
+
+	// The fallback installer is the last resort, so it is started even if the health check fails
+	for problem in python_health_check::run(&python, &config) {
+		println!("\nWarning: {}\n{}", problem, problem.remediation());
+	}
+
+	let launch_result = python_launcher::launch_python_script(&config, &python, launch_type);
 
 	let mut process_runner = match launch_result {
 		Ok(process_runner) => process_runner,
@@ -239,7 +298,12 @@ Please check if any antivirus software or permission issues are stopping the ins
 /// log it to the specified file.
 /// The function will wait until the user presses "Enter" before terminating, so the user can read
 /// the error message.
-pub fn set_hook(log_filename: String) {
+pub fn set_hook(
+	log_filename: String,
+	payload_source: PayloadSource,
+	delta_payload: Option<PathBuf>,
+	loader_config: LoaderConfig,
+) {
 	std::panic::set_hook(Box::new(move |info: &PanicInfo| {
 		// Console window might have been hidden previously - forcibly show it so user can read it
 		windows_utilities::show_console_window();
@@ -264,7 +328,7 @@ pub fn set_hook(log_filename: String) {
 			eprintln!("Error: Crash log could not be written!");
 		}
 
-		if let Err(error) = fallback_installer_pause() {
+		if let Err(error) = fallback_installer_pause(&payload_source, &delta_payload, &loader_config) {
 			println!("Fallback Installer Error: {}", error);
 		};
 	}));
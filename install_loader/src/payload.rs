@@ -0,0 +1,314 @@
+use crate::version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// A stamped payload is laid out as [archive][manifest .json][footer], where the footer is:
+// magic (8 bytes), archive offset, archive length, manifest length (u64 little endian),
+// then the SHA-256 of the archive (32 bytes).
+// The footer is always at the very end of the file, so the payload can be appended to the
+// loader .exe after it has been compiled (see stamp_payload.py).
+const FOOTER_MAGIC: &[u8; 8] = b"7THPAYLD";
+const FOOTER_LENGTH: u64 = 8 + 8 * 3 + 32;
+
+// A chunked archive is laid out as: magic (8 bytes), chunk count (u32 little endian), the length of
+// each chunk (u64 little endian), then the chunks themselves. Each chunk is an independent .tar.xz
+// or .tar.zst archive, so the chunks can be decompressed in parallel.
+const CHUNKED_ARCHIVE_MAGIC: &[u8; 8] = b"7THCHUNK";
+
+// Where the installer archive (.tar.xz or .tar.zst) should be loaded from
+#[derive(Debug, Clone)]
+pub enum PayloadSource {
+	// Use a payload appended to this executable if there is one, otherwise use the compiled-in payload
+	Default,
+	// A payload file given on the command line with --payload. This can either be a stamped
+	// payload (with a footer), or a plain .tar.xz/.tar.zst archive (which can't be verified after extraction).
+	File(PathBuf),
+}
+
+// The manifest is generated by the build script at the same time as the installer archive.
+// It lists the size and SHA-256 hash of every file in the archive, using '/' separated relative paths.
+#[derive(Deserialize)]
+pub struct PayloadManifest {
+	pub version: Option<String>,
+	pub files: BTreeMap<String, ManifestEntry>,
+	// Only present in delta payloads
+	pub delta: Option<DeltaManifest>,
+}
+
+#[derive(Deserialize)]
+pub struct ManifestEntry {
+	pub size: u64,
+	pub sha256: String,
+}
+
+// A delta payload updates an extraction of its base version to the version in the manifest. Its
+// archive only contains the added files, and a binary patch for each patched file - every other
+// file in the manifest is unchanged from the base version. The delta is generated by make_delta_payload.py.
+#[derive(Deserialize)]
+pub struct DeltaManifest {
+	pub base_version: String,
+	pub added: Vec<String>,
+	pub patched: Vec<String>,
+	pub removed: Vec<String>,
+}
+
+pub struct Payload {
+	pub archive: Cow<'static, [u8]>,
+	// If there is no manifest, the extracted files can't be verified
+	pub manifest: Option<PayloadManifest>,
+	// Identifies the payload contents - written to the extraction lock after extraction
+	pub version: String,
+	// Describes where the payload was loaded from, for logging
+	pub description: String,
+}
+
+impl Payload {
+	// Total uncompressed size of the files in the archive, if known
+	pub fn uncompressed_size(&self) -> Option<u64> {
+		self.manifest
+			.as_ref()
+			.map(|manifest| manifest.files.values().map(|entry| entry.size).sum())
+	}
+
+	// Splits a chunked archive into its independently compressed chunks.
+	// A plain archive is returned as a single chunk.
+	pub fn chunks(&self) -> Result<Vec<&[u8]>, String> {
+		let archive: &[u8] = &self.archive;
+		if !archive.starts_with(CHUNKED_ARCHIVE_MAGIC) {
+			return Ok(vec![archive]);
+		}
+
+		let invalid_chunks = || {
+			String::from("The installer archive's chunk table is invalid - please re-download the installer.")
+		};
+
+		let header = &archive[CHUNKED_ARCHIVE_MAGIC.len()..];
+		let chunk_count = header
+			.get(0..4)
+			.map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+			.ok_or_else(invalid_chunks)?;
+		let lengths = &header[4..];
+		let mut remaining = lengths
+			.get(chunk_count.checked_mul(8).ok_or_else(invalid_chunks)?..)
+			.ok_or_else(invalid_chunks)?;
+
+		let mut chunks = Vec::with_capacity(chunk_count);
+		for length_bytes in lengths.chunks_exact(8).take(chunk_count) {
+			let length = u64::from_le_bytes(length_bytes.try_into().unwrap());
+			if length > remaining.len() as u64 {
+				return Err(invalid_chunks());
+			}
+			let (chunk, rest) = remaining.split_at(length as usize);
+			chunks.push(chunk);
+			remaining = rest;
+		}
+
+		if chunks.is_empty() || !remaining.is_empty() {
+			return Err(invalid_chunks());
+		}
+
+		Ok(chunks)
+	}
+}
+
+impl PayloadSource {
+	pub fn load(&self) -> Result<Payload, String> {
+		match self {
+			PayloadSource::Default => {
+				let exe_path = std::env::current_exe()
+					.map_err(|e| format!("Couldn't determine path of the installer .exe: {}", e))?;
+
+				match read_stamped_payload(&exe_path) {
+					Ok(Some(payload)) => return Ok(payload),
+					Ok(None) => {}
+					Err(e) => {
+						return Err(format!(
+							"The installer data appended to [{}] is damaged - please re-download the installer.\nError: {}",
+							exe_path.display(),
+							e
+						))
+					}
+				}
+
+				embedded_payload()
+			}
+			PayloadSource::File(path) => {
+				let read_error = |e: io::Error| {
+					format!("Couldn't read installer payload [{}]: {}", path.display(), e)
+				};
+
+				if let Some(payload) = read_stamped_payload(path).map_err(read_error)? {
+					return Ok(payload);
+				}
+
+				let archive = std::fs::read(path).map_err(read_error)?;
+				let version = format!("file-{}", &sha256_hex(&archive)[..16]);
+				Ok(Payload {
+					archive: Cow::Owned(archive),
+					manifest: None,
+					version,
+					description: format!("payload file [{}] (no manifest)", path.display()),
+				})
+			}
+		}
+	}
+}
+
+// The build script places install_data.tar.xz (or .tar.zst, or a chunked install_data.chunks) and
+// install_data_manifest.json next to this source file. build.rs only enables the 'embedded_payload' cfg
+// if they exist, so the loader can still be built without them (and stamped with a payload later).
+// build.rs sets EMBEDDED_PAYLOAD_ARCHIVE to whichever archive the enabled decoders can read.
+#[cfg(embedded_payload)]
+fn embedded_payload() -> Result<Payload, String> {
+	let manifest: PayloadManifest =
+		serde_json::from_str(include_str!("install_data_manifest.json"))
+			.map_err(|e| format!("Installer archive manifest is invalid: {}", e))?;
+
+	Ok(Payload {
+		archive: Cow::Borrowed(include_bytes!(env!("EMBEDDED_PAYLOAD_ARCHIVE"))),
+		version: manifest
+			.version
+			.clone()
+			.unwrap_or_else(|| version::travis_tag().to_string()),
+		manifest: Some(manifest),
+		description: String::from("payload compiled into the installer"),
+	})
+}
+
+#[cfg(not(embedded_payload))]
+fn embedded_payload() -> Result<Payload, String> {
+	Err(format!(
+		"This installer [{}] was built without any installer data, and none was appended to it.\n\
+Please use --payload <file> to specify the installer data.",
+		version::travis_tag()
+	))
+}
+
+// Reads a payload stamped with a footer from the end of the file.
+// Returns Ok(None) if the file doesn't end with a payload footer.
+fn read_stamped_payload(path: &Path) -> io::Result<Option<Payload>> {
+	let mut file = File::open(path)?;
+	let file_length = file.metadata()?.len();
+	if file_length < FOOTER_LENGTH {
+		return Ok(None);
+	}
+
+	let mut footer = [0u8; FOOTER_LENGTH as usize];
+	file.seek(SeekFrom::Start(file_length - FOOTER_LENGTH))?;
+	file.read_exact(&mut footer)?;
+
+	if &footer[0..8] != FOOTER_MAGIC {
+		return Ok(None);
+	}
+
+	let read_u64 = |start: usize| u64::from_le_bytes(footer[start..start + 8].try_into().unwrap());
+	let archive_offset = read_u64(8);
+	let archive_length = read_u64(16);
+	let manifest_length = read_u64(24);
+	let expected_sha256 = &footer[32..64];
+
+	let payload_end = archive_offset
+		.checked_add(archive_length)
+		.and_then(|end| end.checked_add(manifest_length));
+	if payload_end != Some(file_length - FOOTER_LENGTH) {
+		return Err(invalid_data("payload footer offsets don't match the file size"));
+	}
+
+	let mut archive = vec![0u8; archive_length as usize];
+	file.seek(SeekFrom::Start(archive_offset))?;
+	file.read_exact(&mut archive)?;
+
+	if Sha256::digest(&archive).as_slice() != expected_sha256 {
+		return Err(invalid_data("payload archive hash doesn't match the footer"));
+	}
+
+	let mut manifest_json = String::new();
+	file.take(manifest_length).read_to_string(&mut manifest_json)?;
+	let manifest: PayloadManifest = serde_json::from_str(&manifest_json)
+		.map_err(|e| invalid_data(&format!("payload manifest is invalid: {}", e)))?;
+
+	let version = manifest
+		.version
+		.clone()
+		.unwrap_or_else(|| format!("stamped-{}", &sha256_hex(&archive)[..16]));
+
+	Ok(Some(Payload {
+		archive: Cow::Owned(archive),
+		manifest: Some(manifest),
+		version,
+		description: format!("payload appended to [{}]", path.display()),
+	}))
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+	format!("{:x}", Sha256::digest(data))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn payload(archive: Vec<u8>) -> Payload {
+		Payload {
+			archive: Cow::Owned(archive),
+			manifest: None,
+			version: String::from("test"),
+			description: String::from("test payload"),
+		}
+	}
+
+	fn chunked_archive(chunk_count: u32, lengths: &[u64], data: &[u8]) -> Vec<u8> {
+		let mut archive = CHUNKED_ARCHIVE_MAGIC.to_vec();
+		archive.extend_from_slice(&chunk_count.to_le_bytes());
+		for length in lengths {
+			archive.extend_from_slice(&length.to_le_bytes());
+		}
+		archive.extend_from_slice(data);
+		archive
+	}
+
+	#[test]
+	fn plain_archive_is_a_single_chunk() {
+		let payload = payload(vec![0xFD, b'7', b'z', b'X', b'Z', 0x00, 1, 2, 3]);
+		assert_eq!(payload.chunks().unwrap(), vec![&payload.archive[..]]);
+	}
+
+	#[test]
+	fn splits_chunked_archive() {
+		let payload = payload(chunked_archive(3, &[2, 0, 3], b"aabbb"));
+		let chunks = payload.chunks().unwrap();
+		assert_eq!(chunks, vec![&b"aa"[..], &b""[..], &b"bbb"[..]]);
+	}
+
+	#[test]
+	fn rejects_invalid_chunk_tables() {
+		let invalid_archives = vec![
+			// Truncated chunk count
+			b"7THCHUNK\x01\x00".to_vec(),
+			// No chunks
+			chunked_archive(0, &[], b""),
+			// Missing chunk lengths
+			chunked_archive(2, &[1], b"a"),
+			// Chunk longer than the remaining data
+			chunked_archive(1, &[10], b"aaaa"),
+			// Data left over after the last chunk
+			chunked_archive(1, &[2], b"aaaa"),
+			// Chunk count which would overflow the chunk length table size
+			chunked_archive(u32::MAX, &[1], b"a"),
+		];
+
+		for archive in invalid_archives {
+			assert!(payload(archive).chunks().is_err());
+		}
+	}
+}
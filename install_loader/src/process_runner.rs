@@ -1,22 +1,76 @@
+use crate::install_status::{InstallStatus, STATUS_LINES_ENV_VAR};
+use crate::rotating_log::RotatingLog;
+#[cfg(windows)]
 use crate::windows_utilities;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+#[cfg(windows)]
 use std::os::windows::io::AsRawHandle;
 use std::path::Path;
-use std::process::{Child, Command};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+#[cfg(windows)]
 use win32job::Job;
 
-//spawn a thread to monitor the stdout and stderr
+// Log files for the process output are named '<LOG_FILE_PREFIX>_<date>_<time>.log'
+const LOG_FILE_PREFIX: &str = "loader_python_output";
+
+// The number of lines of output kept in memory, to be shown in the installer window
+const OUTPUT_HISTORY_LINES: usize = 1000;
+
+// Runs a process with its stdout and stderr piped through reader threads, which write the output to
+// rotating log files, and echo it to the console if the console is visible. The most recent lines
+// are also kept in memory (see output_history()). Status lines the process writes to stderr are
+// parsed instead (see install_status.rs and install_status()).
+// The process and any processes it starts (like aria2c and 7z) are killed when the ProcessRunner is
+// dropped. On Windows, they are put in a job object which kills them when it is closed. On Unix, the
+// process is started in a new session, so the whole process group can be killed.
 pub struct ProcessRunner {
 	child: Child,
+	#[cfg(windows)]
 	job: Option<Job>,
+	// Set once the process has been waited on. Its id may then be reused by another process, so its
+	// process group must not be killed any more.
+	#[cfg(unix)]
+	waited: bool,
+	output_history: Arc<Mutex<OutputHistory>>,
+	install_status: Arc<Mutex<InstallStatus>>,
+}
+
+// The last OUTPUT_HISTORY_LINES lines of output from both stdout and stderr, in the order they were read
+pub struct OutputHistory {
+	lines: VecDeque<String>,
+}
+
+impl OutputHistory {
+	fn new() -> OutputHistory {
+		OutputHistory {
+			lines: VecDeque::with_capacity(OUTPUT_HISTORY_LINES),
+		}
+	}
+
+	fn push(&mut self, line: String) {
+		if self.lines.len() >= OUTPUT_HISTORY_LINES {
+			self.lines.pop_front();
+		}
+		self.lines.push_back(line);
+	}
+
+	pub fn lines(&self) -> impl Iterator<Item = &String> {
+		self.lines.iter()
+	}
 }
 
 impl ProcessRunner {
 	pub fn new<I, S>(
 		full_executable_path: &Path,
 		working_directory: &Path,
+		logs_folder: &Path,
 		arguments: I,
+		environment: &[(&str, &str)],
 	) -> Result<ProcessRunner, Box<dyn Error>>
 	where
 		I: IntoIterator<Item = S> + std::fmt::Debug,
@@ -27,35 +81,587 @@ impl ProcessRunner {
 			full_executable_path, working_directory, arguments
 		);
 
-		let child = Command::new(full_executable_path)
+		// stdin is inherited, so the text mode installer can still read input from the console
+		let mut command = Command::new(full_executable_path);
+		command
 			.current_dir(working_directory)
 			.args(arguments)
-			.spawn()?;
+			.env(STATUS_LINES_ENV_VAR, "1")
+			.envs(environment.iter().copied())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped());
+
+		#[cfg(unix)]
+		start_in_new_session(&mut command);
+
+		let mut child = command.spawn()?;
 
-		let (job, register_job_result) =
-			windows_utilities::new_job_kill_on_job_close_id(child.as_raw_handle());
+		// The process is still run if its output can't be logged
+		let log = match RotatingLog::new(logs_folder, LOG_FILE_PREFIX) {
+			Ok(log) => Some(log),
+			Err(e) => {
+				println!("Failed to create log file in {:?} - process output won't be logged: {}", logs_folder, e);
+				None
+			}
+		};
+		let output_history = Arc::new(Mutex::new(OutputHistory::new()));
+		let install_status = Arc::new(Mutex::new(InstallStatus::default()));
+		let capture = OutputCapture {
+			log: Arc::new(Mutex::new(log)),
+			history: Arc::clone(&output_history),
+			install_status: Arc::clone(&install_status),
+		};
 
-		if let Err(e) = register_job_result {
-			println!("Failed to create job object: {}", e);
+		if let Some(stdout) = child.stdout.take() {
+			spawn_output_forwarder(stdout, OutputStream::Stdout, capture.clone());
 		}
+		if let Some(stderr) = child.stderr.take() {
+			spawn_output_forwarder(stderr, OutputStream::Stderr, capture);
+		}
+
+		#[cfg(windows)]
+		let job = {
+			let (job, register_job_result) =
+				windows_utilities::new_job_kill_on_job_close_id(child.as_raw_handle());
+
+			if let Err(e) = register_job_result {
+				println!("Failed to create job object: {}", e);
+			}
+
+			job
+		};
+
+		Ok(ProcessRunner {
+			child,
+			#[cfg(windows)]
+			job,
+			#[cfg(unix)]
+			waited: false,
+			output_history,
+			install_status,
+		})
+	}
+
+	// The most recent output of the process. This is still updated after the ProcessRunner is dropped,
+	// until the process' output pipes are closed.
+	pub fn output_history(&self) -> Arc<Mutex<OutputHistory>> {
+		Arc::clone(&self.output_history)
+	}
 
-		Ok(ProcessRunner { child, job })
+	// The install progress most recently reported by the process' status lines
+	pub fn install_status(&self) -> InstallStatus {
+		lock_ignoring_poison(&self.install_status).clone()
 	}
 
-	// Kill the process and wait for it to terminate
+	// Kill the process and any processes it started, and wait for it to terminate.
+	// If the process has already exited, any processes it started are still killed.
 	pub fn kill_wait(&mut self) -> Result<(), Box<dyn Error>> {
-		self.child.kill()?;
-		self.child.wait()?;
+		self.kill_process_tree()?;
+		self.wait()
+	}
+
+	#[cfg(windows)]
+	fn kill_process_tree(&mut self) -> io::Result<()> {
+		if self.child.try_wait()?.is_none() {
+			self.child.kill()?;
+		}
+
+		// Closing the job kills any other processes in it
 		self.job = None;
 		Ok(())
 	}
 
+	// Once the process has been waited on, the rest of its group was already killed (see wait() and try_wait())
+	#[cfg(unix)]
+	fn kill_process_tree(&mut self) -> io::Result<()> {
+		if self.waited {
+			return Ok(());
+		}
+
+		kill_process_group(self.child.id())
+	}
+
 	pub fn wait(&mut self) -> Result<(), Box<dyn Error>> {
+		#[cfg(unix)]
+		self.kill_group_once_exited(true)?;
+
 		self.child.wait()?;
+
+		#[cfg(unix)]
+		{
+			self.waited = true;
+		}
 		Ok(())
 	}
 
 	pub fn try_wait(&mut self) -> std::io::Result<Option<std::process::ExitStatus>> {
-		self.child.try_wait()
+		#[cfg(unix)]
+		self.kill_group_once_exited(false)?;
+
+		let status = self.child.try_wait()?;
+
+		#[cfg(unix)]
+		{
+			self.waited |= status.is_some();
+		}
+		Ok(status)
+	}
+
+	// Kills any processes left running in the group once the process has exited, before it is waited on.
+	// Until then its id can't be reused, so only the processes it started are killed.
+	// If block is true, waits for the process to exit.
+	#[cfg(unix)]
+	fn kill_group_once_exited(&mut self, block: bool) -> io::Result<()> {
+		if !self.waited && wait_for_exit_without_reaping(self.child.id(), block)? {
+			kill_process_group(self.child.id())?;
+		}
+
+		Ok(())
+	}
+}
+
+// On Windows, the process tree is killed when the job object is dropped
+#[cfg(unix)]
+impl Drop for ProcessRunner {
+	fn drop(&mut self) {
+		let _ = self.kill_process_tree();
+	}
+}
+
+// Starts the process in a new session, which also puts it in a new process group with the same id
+// as the process. Processes it starts stay in the group, so they can all be killed with killpg().
+// The session has no controlling terminal, so the text mode installer can still read from the
+// inherited stdin without being stopped by job control.
+// On Linux, the process is also killed if the loader dies without cleaning up (e.g. if it's killed).
+// Processes it started aren't sent the death signal, so they may be left running in that case -
+// panics still unwind and drop the ProcessRunner, which kills the whole group.
+// The death signal is sent when the *thread* which started the process exits, so the ProcessRunner
+// must be created on a thread which lives as long as the loader (like the UI thread).
+#[cfg(unix)]
+fn start_in_new_session(command: &mut Command) {
+	use std::os::unix::process::CommandExt;
+
+	#[cfg(target_os = "linux")]
+	let loader_pid = std::process::id();
+
+	// SAFETY: only async-signal-safe functions are called between fork() and exec()
+	unsafe {
+		command.pre_exec(move || {
+			if libc::setsid() == -1 {
+				return Err(io::Error::last_os_error());
+			}
+
+			#[cfg(target_os = "linux")]
+			{
+				if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) == -1 {
+					return Err(io::Error::last_os_error());
+				}
+
+				// The loader may have died before the death signal was set up
+				if libc::getppid() as u32 != loader_pid {
+					return Err(io::Error::from_raw_os_error(libc::ESRCH));
+				}
+			}
+
+			Ok(())
+		});
+	}
+}
+
+// Kills every process in the process group started by start_in_new_session()
+#[cfg(unix)]
+fn kill_process_group(process_id: u32) -> io::Result<()> {
+	if unsafe { libc::killpg(process_id as libc::pid_t, libc::SIGKILL) } == -1 {
+		let error = io::Error::last_os_error();
+
+		// All processes in the group have already exited
+		if error.raw_os_error() != Some(libc::ESRCH) {
+			return Err(error);
+		}
+	}
+
+	Ok(())
+}
+
+// Returns whether the process has exited, without reaping it (so its id isn't freed).
+// If block is true, waits for the process to exit.
+#[cfg(unix)]
+fn wait_for_exit_without_reaping(process_id: u32, block: bool) -> io::Result<bool> {
+	let options = libc::WEXITED | libc::WNOWAIT | if block { 0 } else { libc::WNOHANG };
+
+	loop {
+		// si_pid is left as 0 if the process hasn't exited yet
+		let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+		if unsafe { libc::waitid(libc::P_PID, process_id as libc::id_t, &mut info, options) } == -1
+		{
+			let error = io::Error::last_os_error();
+			if error.kind() == io::ErrorKind::Interrupted {
+				continue;
+			}
+			return Err(error);
+		}
+
+		return Ok(unsafe { info.si_pid() } != 0);
+	}
+}
+
+// Output is only echoed to the console if the user can see it
+#[cfg(windows)]
+fn console_is_visible() -> bool {
+	windows_utilities::console_window_is_visible()
+}
+
+#[cfg(unix)]
+fn console_is_visible() -> bool {
+	unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[derive(Copy, Clone)]
+enum OutputStream {
+	Stdout,
+	Stderr,
+}
+
+impl OutputStream {
+	fn name(&self) -> &'static str {
+		match self {
+			OutputStream::Stdout => "stdout",
+			OutputStream::Stderr => "stderr",
+		}
+	}
+
+	// Status lines are only read from stderr
+	fn carries_status_lines(&self) -> bool {
+		matches!(self, OutputStream::Stderr)
+	}
+
+	fn echo(&self, data: &[u8]) {
+		let _ = match self {
+			OutputStream::Stdout => write_and_flush(io::stdout(), data),
+			OutputStream::Stderr => write_and_flush(io::stderr(), data),
+		};
+	}
+}
+
+fn write_and_flush<W: Write>(mut console: W, data: &[u8]) -> io::Result<()> {
+	console.write_all(data)?;
+	console.flush()
+}
+
+// Where each complete line of output is recorded, shared by the stdout and stderr reader threads
+#[derive(Clone)]
+struct OutputCapture {
+	log: Arc<Mutex<Option<RotatingLog>>>,
+	history: Arc<Mutex<OutputHistory>>,
+	install_status: Arc<Mutex<InstallStatus>>,
+}
+
+impl OutputCapture {
+	// Writes a line of output to the log and the history, unless it's a status line, which updates the
+	// install status instead. Returns false for status lines.
+	// If writing to the log fails, logging is stopped, as the disk is probably full.
+	fn record_line(&self, stream: OutputStream, line: &[u8]) -> bool {
+		let line = String::from_utf8_lossy(line);
+		let line = line.trim_end_matches('\r');
+
+		if stream.carries_status_lines() && lock_ignoring_poison(&self.install_status).update_from_line(line) {
+			return false;
+		}
+
+		let mut log = lock_ignoring_poison(&self.log);
+		if let Some(rotating_log) = log.as_mut() {
+			if let Err(e) = rotating_log.write_line(stream.name(), line) {
+				println!("Failed to write to log file - process output will no longer be logged: {}", e);
+				*log = None;
+			}
+		}
+
+		lock_ignoring_poison(&self.history).push(line.to_string());
+		true
+	}
+}
+
+// A reader thread can only panic between lines, so the data is still valid if the mutex is poisoned
+fn lock_ignoring_poison<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+	match mutex.lock() {
+		Ok(guard) => guard,
+		Err(poisoned) => poisoned.into_inner(),
+	}
+}
+
+// Spawns a thread which reads the process output until the pipe is closed (when the process exits).
+// Stdout is echoed as soon as it's read, so prompts which don't end with a newline are still shown,
+// but only complete lines are recorded. Stderr is echoed a line at a time, so status lines can be left out.
+fn spawn_output_forwarder<R: Read + Send + 'static>(
+	mut pipe: R,
+	stream: OutputStream,
+	capture: OutputCapture,
+) {
+	thread::spawn(move || {
+		let mut buffer = [0u8; 4096];
+		let mut line = Vec::new();
+
+		loop {
+			let data = match pipe.read(&mut buffer) {
+				Ok(0) => break,
+				Ok(count) => &buffer[..count],
+				// The read was interrupted by a signal before any data was read
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(_) => break,
+			};
+
+			if !stream.carries_status_lines() && console_is_visible() {
+				stream.echo(data);
+			}
+
+			for &byte in data {
+				line.push(byte);
+				if byte == b'\n' {
+					forward_line(stream, &capture, &line);
+					line.clear();
+				}
+			}
+		}
+
+		if !line.is_empty() {
+			forward_line(stream, &capture, &line);
+		}
+	});
+}
+
+// Records a line of output (which may end with a newline), echoing it if it wasn't echoed as it was read
+fn forward_line(stream: OutputStream, capture: &OutputCapture, line: &[u8]) {
+	let is_output = capture.record_line(stream, line.strip_suffix(b"\n").unwrap_or(line));
+
+	if is_output && stream.carries_status_lines() && console_is_visible() {
+		stream.echo(line);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::install_status::STATUS_LINE_PREFIX;
+
+	fn capture_without_log() -> OutputCapture {
+		OutputCapture {
+			log: Arc::new(Mutex::new(None)),
+			history: Arc::new(Mutex::new(OutputHistory::new())),
+			install_status: Arc::new(Mutex::new(InstallStatus::default())),
+		}
+	}
+
+	fn history_lines(capture: &OutputCapture) -> Vec<String> {
+		lock_ignoring_poison(&capture.history)
+			.lines()
+			.cloned()
+			.collect()
+	}
+
+	#[test]
+	fn history_keeps_the_latest_lines() {
+		let mut history = OutputHistory::new();
+		for i in 0..OUTPUT_HISTORY_LINES + 5 {
+			history.push(i.to_string());
+		}
+
+		let lines: Vec<&String> = history.lines().collect();
+		assert_eq!(lines.len(), OUTPUT_HISTORY_LINES);
+		assert_eq!(lines[0], "5");
+		assert_eq!(
+			lines[OUTPUT_HISTORY_LINES - 1],
+			&(OUTPUT_HISTORY_LINES + 4).to_string()
+		);
+	}
+
+	#[test]
+	fn records_lines_without_line_endings() {
+		let capture = capture_without_log();
+		assert!(capture.record_line(OutputStream::Stdout, b"windows line\r"));
+		assert!(capture.record_line(OutputStream::Stderr, b"invalid utf-8 \xff"));
+
+		assert_eq!(
+			history_lines(&capture),
+			vec!["windows line", "invalid utf-8 \u{FFFD}"]
+		);
+	}
+
+	#[test]
+	fn status_lines_are_only_read_from_stderr() {
+		let capture = capture_without_log();
+		let status_line = format!(
+			r#"{}{{"type": "progress", "phase": "Downloading", "percentage": 10}}"#,
+			STATUS_LINE_PREFIX
+		);
+
+		assert!(!capture.record_line(OutputStream::Stderr, status_line.as_bytes()));
+		assert!(history_lines(&capture).is_empty());
+		assert_eq!(
+			lock_ignoring_poison(&capture.install_status)
+				.phase
+				.as_deref(),
+			Some("Downloading")
+		);
+
+		// On stdout it's normal output
+		assert!(capture.record_line(OutputStream::Stdout, status_line.as_bytes()));
+		assert_eq!(history_lines(&capture), vec![status_line]);
+	}
+
+	#[test]
+	fn records_lines_in_the_log() {
+		let logs = tempfile::tempdir().unwrap();
+		let capture = capture_without_log();
+		*lock_ignoring_poison(&capture.log) = Some(RotatingLog::new(logs.path(), "test").unwrap());
+
+		capture.record_line(OutputStream::Stdout, b"first line");
+		capture.record_line(OutputStream::Stderr, b"second line");
+		drop(capture);
+
+		let log_file = std::fs::read_dir(logs.path())
+			.unwrap()
+			.next()
+			.unwrap()
+			.unwrap();
+		let contents = std::fs::read_to_string(log_file.path()).unwrap();
+		let lines: Vec<&str> = contents.lines().collect();
+		assert_eq!(lines.len(), 2);
+		assert!(lines[0].ends_with(" stdout] first line"), "{}", lines[0]);
+		assert!(lines[1].ends_with(" stderr] second line"), "{}", lines[1]);
+	}
+
+	#[test]
+	fn forwards_split_and_unterminated_lines() {
+		let capture = capture_without_log();
+		let output: &[u8] = b"first\nsecond line\r\nno newline at the end";
+
+		// Reads a few bytes at a time, so lines are split across reads
+		struct SlowReader<'a>(&'a [u8]);
+		impl Read for SlowReader<'_> {
+			fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+				let count = self.0.len().min(buffer.len()).min(3);
+				buffer[..count].copy_from_slice(&self.0[..count]);
+				self.0 = &self.0[count..];
+				Ok(count)
+			}
+		}
+
+		let history = Arc::clone(&capture.history);
+		spawn_output_forwarder(SlowReader(output), OutputStream::Stderr, capture);
+
+		// The forwarder releases its capture once the pipe is closed
+		while Arc::strong_count(&history) > 1 {
+			thread::sleep(std::time::Duration::from_millis(10));
+		}
+		let lines: Vec<String> = lock_ignoring_poison(&history).lines().cloned().collect();
+		assert_eq!(lines, vec!["first", "second line", "no newline at the end"]);
+	}
+
+	#[test]
+	fn retries_interrupted_reads() {
+		let capture = capture_without_log();
+
+		struct InterruptedReader {
+			interrupted: bool,
+			data: &'static [u8],
+		}
+		impl Read for InterruptedReader {
+			fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+				if !self.interrupted {
+					self.interrupted = true;
+					return Err(io::ErrorKind::Interrupted.into());
+				}
+				let count = self.data.len().min(buffer.len());
+				buffer[..count].copy_from_slice(&self.data[..count]);
+				self.data = &self.data[count..];
+				Ok(count)
+			}
+		}
+
+		let history = Arc::clone(&capture.history);
+		let reader = InterruptedReader {
+			interrupted: false,
+			data: b"after the interruption\n",
+		};
+		spawn_output_forwarder(reader, OutputStream::Stderr, capture);
+
+		while Arc::strong_count(&history) > 1 {
+			thread::sleep(std::time::Duration::from_millis(10));
+		}
+		let lines: Vec<String> = lock_ignoring_poison(&history).lines().cloned().collect();
+		assert_eq!(lines, vec!["after the interruption"]);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn only_kills_the_process_group_until_waited_on() {
+		let logs = tempfile::tempdir().unwrap();
+		let mut runner = ProcessRunner::new(
+			Path::new("sh"),
+			logs.path(),
+			logs.path(),
+			["-c", "exit 3"],
+			&[],
+		)
+		.unwrap();
+
+		let status = loop {
+			if let Some(status) = runner.try_wait().unwrap() {
+				break status;
+			}
+			thread::sleep(std::time::Duration::from_millis(10));
+		};
+		assert_eq!(status.code(), Some(3));
+		assert!(runner.waited);
+
+		// The process id may have been reused, so killing the group again does nothing
+		runner.kill_wait().unwrap();
+	}
+
+	// Zombies are also treated as not running, as they may not be reaped in containers
+	#[cfg(target_os = "linux")]
+	fn process_is_running(process_id: libc::pid_t) -> bool {
+		match std::fs::read_to_string(format!("/proc/{}/stat", process_id)) {
+			// The state follows the command name in brackets, which may contain spaces
+			Ok(stat) => !stat
+				.rsplit(')')
+				.next()
+				.unwrap_or_default()
+				.trim_start()
+				.starts_with('Z'),
+			Err(_) => false,
+		}
+	}
+
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn kills_processes_left_running_once_the_process_exits() {
+		let logs = tempfile::tempdir().unwrap();
+		let pid_file = logs.path().join("pid");
+		let script = format!("sleep 30 & echo $! > '{}'", pid_file.display());
+		let mut runner = ProcessRunner::new(
+			Path::new("sh"),
+			logs.path(),
+			logs.path(),
+			["-c", &script],
+			&[],
+		)
+		.unwrap();
+		runner.wait().unwrap();
+
+		// The background process was in the same group, so it was killed when the shell was waited on
+		let sleep_pid: libc::pid_t = std::fs::read_to_string(&pid_file)
+			.unwrap()
+			.trim()
+			.parse()
+			.unwrap();
+		let started = std::time::Instant::now();
+		while process_is_running(sleep_pid) {
+			assert!(
+				started.elapsed().as_secs() < 10,
+				"the background process is still running"
+			);
+			thread::sleep(std::time::Duration::from_millis(10));
+		}
 	}
 }
@@ -0,0 +1,75 @@
+# Self-test run by the launcher with the python runtime, before the installer is started (see
+# python_health_check.rs). It is run in the installer folder, and prints a JSON object for each
+# problem found:
+#   {"check": "import", "name": "ssl", "error": "ImportError: DLL load failed..."}
+#   {"check": "certificates", "name": "default", "error": "..."}
+#   {"check": "writable", "name": "/path/to/installer/folder", "error": "PermissionError: ..."}
+import json
+import os
+import sys
+
+# The standard library modules imported by main.py and the modules it uses. Compiled modules (like
+# ssl and hashlib) are the ones most often removed by antivirus software.
+REQUIRED_MODULES = [
+	'argparse', 'collections', 'concurrent.futures', 'datetime', 'glob', 'hashlib', 'html.parser',
+	'http.server', 'io', 'itertools', 'locale', 'platform', 'pprint', 'queue', 're', 'shutil',
+	'socket', 'ssl', 'subprocess', 'tempfile', 'threading', 'traceback', 'urllib.error',
+	'urllib.parse', 'urllib.request', 'webbrowser', 'xml.etree.ElementTree', 'zipfile', 'zlib',
+]
+
+if sys.platform == 'win32':
+	REQUIRED_MODULES.append('winreg')
+
+
+def report(check, name, error):
+	print(json.dumps({'check': check, 'name': name, 'error': error}))
+
+
+def describe(exception):
+	return '{}: {}'.format(type(exception).__name__, exception)
+
+
+def check_imports():
+	for module in REQUIRED_MODULES:
+		try:
+			__import__(module)
+		except Exception as e:
+			report('import', module, describe(e))
+
+
+def check_certificates():
+	try:
+		import ssl
+	except Exception:
+		# Already reported by check_imports()
+		return
+
+	try:
+		context = ssl.create_default_context()
+		if context.cert_store_stats()['x509_ca'] > 0:
+			return
+
+		# OpenSSL only loads the certificates in a directory when they are needed, so they aren't counted above
+		paths = ssl.get_default_verify_paths()
+		if paths.capath and os.path.isdir(paths.capath) and os.listdir(paths.capath):
+			return
+
+		report('certificates', 'default', 'No CA certificates were found')
+	except Exception as e:
+		report('certificates', 'default', describe(e))
+
+
+def check_writable():
+	folder = os.getcwd()
+	test_path = os.path.join(folder, 'launcher-write-test.tmp')
+	try:
+		with open(test_path, 'w') as test_file:
+			test_file.write('test')
+		os.remove(test_path)
+	except Exception as e:
+		report('writable', folder, describe(e))
+
+
+check_imports()
+check_certificates()
+check_writable()
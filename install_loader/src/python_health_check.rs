@@ -0,0 +1,281 @@
+use crate::config::InstallerConfig;
+use crate::python_interpreter::{self, InterpreterSource, PythonInterpreter};
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+const HEALTH_CHECK_SCRIPT: &str = include_str!("python_health_check.py");
+
+// Importing every module can be slow the first time, while antivirus software scans the runtime
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+// A problem reported by the health check script
+#[derive(Deserialize)]
+struct CheckFailure {
+	check: String,
+	name: String,
+	error: String,
+}
+
+// A problem with the python runtime which would stop the installer from working
+#[derive(Debug, PartialEq, Eq)]
+pub enum HealthProblem {
+	// Modules which couldn't be imported, and why. 'bundled' is false if a system python was used.
+	MissingModules {
+		modules: Vec<(String, String)>,
+		bundled: bool,
+	},
+	NoCertificates(String),
+	FolderNotWritable {
+		folder: String,
+		error: String,
+	},
+	// The health check script couldn't be run, or crashed
+	CheckFailed(String),
+}
+
+impl HealthProblem {
+	// What the user can do to fix the problem
+	pub fn remediation(&self) -> String {
+		match self {
+			HealthProblem::MissingModules { bundled: true, .. } => String::from(
+				"Files were probably removed from the installer's Python runtime by antivirus software. Add an exception for the installer's folder to your antivirus, then use 'Force Re-Extraction' under Advanced Tools.",
+			),
+			HealthProblem::MissingModules { bundled: false, .. } => String::from(
+				"Your system's Python is missing some standard modules. Install them with your package manager (or choose another Python with --python), then restart the installer.",
+			),
+			HealthProblem::NoCertificates(_) => String::from(
+				"Downloads will fail without SSL certificates. Check your system date and time are correct, and update your system's root certificates (on Windows, run Windows Update).",
+			),
+			HealthProblem::FolderNotWritable { .. } => String::from(
+				"Move the installer to a folder you can write to, like Downloads. On Windows, also check 'Controlled folder access' in Windows Security isn't blocking the installer.",
+			),
+			HealthProblem::CheckFailed(_) => String::from(
+				"The Python runtime may have been damaged by antivirus software. Add an exception for the installer's folder to your antivirus, then use 'Force Re-Extraction' under Advanced Tools.",
+			),
+		}
+	}
+}
+
+impl fmt::Display for HealthProblem {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			HealthProblem::MissingModules { modules, .. } => {
+				write!(f, "Python modules couldn't be loaded:")?;
+				for (module, error) in modules {
+					write!(f, "\n - {} ({})", module, error)?;
+				}
+				Ok(())
+			}
+			HealthProblem::NoCertificates(error) => {
+				write!(f, "Python couldn't load any SSL certificates ({})", error)
+			}
+			HealthProblem::FolderNotWritable { folder, error } => {
+				write!(
+					f,
+					"The installer folder [{}] isn't writable ({})",
+					folder, error
+				)
+			}
+			HealthProblem::CheckFailed(error) => {
+				write!(f, "The Python health check couldn't be run ({})", error)
+			}
+		}
+	}
+}
+
+// Runs a self-test with the python runtime before the installer is started, to check the modules the
+// installer needs can be imported, SSL certificates can be loaded, and the installer folder is
+// writable. Antivirus software often silently removes files from the runtime, which would otherwise
+// only show up as the installer crashing.
+// The check runs on another thread, as importing every module can take a while the first time (while
+// antivirus software scans the runtime). Call poll() every frame until it returns the problems found.
+pub struct HealthCheck {
+	receiver: Receiver<Vec<HealthProblem>>,
+}
+
+impl HealthCheck {
+	pub fn start(python: &PythonInterpreter, config: &InstallerConfig) -> HealthCheck {
+		let python_path = python.path.clone();
+		let bundled = python.source == InterpreterSource::Bundled;
+		let working_directory = config.sub_folder.clone();
+		let (sender, receiver) = mpsc::channel();
+
+		thread::spawn(move || {
+			let _ = sender.send(run(python_path, working_directory, bundled));
+		});
+
+		HealthCheck { receiver }
+	}
+
+	// Returns None while the check is still running, otherwise the problems found (if any)
+	pub fn poll(&self) -> Option<Vec<HealthProblem>> {
+		match self.receiver.try_recv() {
+			Ok(problems) => Some(problems),
+			Err(TryRecvError::Empty) => None,
+			Err(TryRecvError::Disconnected) => Some(vec![HealthProblem::CheckFailed(String::from(
+				"the health check stopped unexpectedly",
+			))]),
+		}
+	}
+}
+
+fn run(python_path: PathBuf, working_directory: PathBuf, bundled: bool) -> Vec<HealthProblem> {
+	println!("Checking the python runtime [{}]...", python_path.display());
+
+	let output = match python_interpreter::run_script(
+		&python_path,
+		&working_directory,
+		HEALTH_CHECK_SCRIPT,
+		&[],
+		HEALTH_CHECK_TIMEOUT,
+	) {
+		Ok(Some(output)) => output,
+		Ok(None) => {
+			return vec![HealthProblem::CheckFailed(format!(
+				"it didn't finish within {} seconds",
+				HEALTH_CHECK_TIMEOUT.as_secs()
+			))]
+		}
+		Err(e) => return vec![HealthProblem::CheckFailed(e.to_string())],
+	};
+
+	if !output.exit_status.success() {
+		let last_error_line = output.stderr.lines().last().unwrap_or_default();
+		return vec![HealthProblem::CheckFailed(format!(
+			"{}: {}",
+			output.exit_status, last_error_line
+		))];
+	}
+
+	let problems = parse_output(&output.stdout, bundled);
+	if problems.is_empty() {
+		println!("The python runtime passed the health check");
+	}
+
+	problems
+}
+
+// Converts the failures printed by the health check script into problems. 'bundled' is false if a
+// system python was checked.
+fn parse_output(stdout: &str, bundled: bool) -> Vec<HealthProblem> {
+	let mut missing_modules = Vec::new();
+	let mut problems = Vec::new();
+
+	for line in stdout.lines() {
+		let failure: CheckFailure = match serde_json::from_str(line) {
+			Ok(failure) => failure,
+			Err(e) => {
+				println!("Ignoring invalid health check output [{}]: {}", line, e);
+				continue;
+			}
+		};
+
+		println!(
+			"Health check failed: {} [{}]: {}",
+			failure.check, failure.name, failure.error
+		);
+
+		match failure.check.as_str() {
+			"import" => missing_modules.push((failure.name, failure.error)),
+			"certificates" => problems.push(HealthProblem::NoCertificates(failure.error)),
+			"writable" => problems.push(HealthProblem::FolderNotWritable {
+				folder: failure.name,
+				error: failure.error,
+			}),
+			_ => {}
+		}
+	}
+
+	if !missing_modules.is_empty() {
+		problems.insert(
+			0,
+			HealthProblem::MissingModules {
+				modules: missing_modules,
+				bundled,
+			},
+		);
+	}
+
+	problems
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn no_output_means_no_problems() {
+		assert!(parse_output("", true).is_empty());
+	}
+
+	#[test]
+	fn parses_each_kind_of_failure() {
+		let stdout = concat!(
+			r#"{"check": "writable", "name": "C:\\installer", "error": "PermissionError: denied"}"#,
+			"\n",
+			r#"{"check": "import", "name": "ssl", "error": "ImportError: DLL load failed"}"#,
+			"\n",
+			r#"{"check": "certificates", "name": "default", "error": "No CA certificates were found"}"#,
+			"\n",
+			r#"{"check": "import", "name": "sqlite3", "error": "ModuleNotFoundError: No module named '_sqlite3'"}"#,
+			"\n",
+		);
+
+		assert_eq!(
+			parse_output(stdout, true),
+			vec![
+				HealthProblem::MissingModules {
+					modules: vec![
+						(
+							String::from("ssl"),
+							String::from("ImportError: DLL load failed")
+						),
+						(
+							String::from("sqlite3"),
+							String::from("ModuleNotFoundError: No module named '_sqlite3'")
+						),
+					],
+					bundled: true,
+				},
+				HealthProblem::FolderNotWritable {
+					folder: String::from("C:\\installer"),
+					error: String::from("PermissionError: denied"),
+				},
+				HealthProblem::NoCertificates(String::from("No CA certificates were found")),
+			]
+		);
+	}
+
+	#[test]
+	fn records_whether_the_bundled_python_was_checked() {
+		let stdout = r#"{"check": "import", "name": "ssl", "error": "ImportError"}"#;
+		assert_eq!(
+			parse_output(stdout, false),
+			vec![HealthProblem::MissingModules {
+				modules: vec![(String::from("ssl"), String::from("ImportError"))],
+				bundled: false,
+			}]
+		);
+	}
+
+	#[test]
+	fn ignores_invalid_lines_and_unknown_checks() {
+		let stdout = concat!(
+			"Some unexpected warning printed by python\n",
+			r#"{"check": "import", "name": "ssl""#,
+			"\n",
+			r#"{"check": "future_check", "name": "x", "error": "y"}"#,
+			"\n",
+			r#"{"check": "certificates", "name": "default", "error": "none"}"#,
+		);
+
+		assert_eq!(
+			parse_output(stdout, true),
+			vec![HealthProblem::NoCertificates(String::from("none"))]
+		);
+	}
+}
@@ -0,0 +1,374 @@
+use crate::config::InstallerConfig;
+use crate::windows_utilities;
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Older versions don't have http.server.ThreadingHTTPServer, which the web GUI needs to load reliably
+// (see start_server() in httpGUI.py)
+const MIN_PYTHON_VERSION: (u32, u32) = (3, 7);
+
+// Stdlib modules which some python builds (like a minimal system python) are missing
+const REQUIRED_MODULES: &[&str] = &["ssl", "sqlite3"];
+
+// How long a candidate has to answer the probe. A broken interpreter (or the Windows Store's python3
+// placeholder) may hang, rather than exit with an error.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Prints the python version, then the required modules which failed to import.
+// Kept compatible with python 2, so old interpreters are rejected with a clear reason.
+const PROBE_SCRIPT: &str = r#"
+import sys
+missing = []
+for module in sys.argv[1:]:
+	try:
+		__import__(module)
+	except Exception:
+		missing.append(module)
+print('%d.%d.%d' % tuple(sys.version_info[:3]))
+print(','.join(missing))
+"#;
+
+// Where a candidate interpreter came from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InterpreterSource {
+	// The python runtime extracted with the installer
+	Bundled,
+	// Chosen with --python or SEVENTH_MOD_PYTHON
+	Override,
+	// Found on the PATH
+	System,
+}
+
+impl fmt::Display for InterpreterSource {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			InterpreterSource::Bundled => write!(f, "bundled python"),
+			InterpreterSource::Override => write!(f, "python override"),
+			InterpreterSource::System => write!(f, "system python"),
+		}
+	}
+}
+
+// A python interpreter which passed the probe
+#[derive(Clone)]
+pub struct PythonInterpreter {
+	pub path: PathBuf,
+	pub source: InterpreterSource,
+}
+
+// The result of a script run with run_script()
+pub struct ScriptOutput {
+	pub exit_status: ExitStatus,
+	pub stdout: String,
+	pub stderr: String,
+}
+
+// Every candidate was rejected
+#[derive(Debug)]
+pub struct NoUsableInterpreter {
+	// Each candidate, and why it was rejected
+	rejected: Vec<(InterpreterSource, PathBuf, String)>,
+}
+
+impl fmt::Display for NoUsableInterpreter {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"Couldn't find a usable Python {}.{}+ interpreter (with the {} modules). Tried:",
+			MIN_PYTHON_VERSION.0,
+			MIN_PYTHON_VERSION.1,
+			REQUIRED_MODULES.join(" and ")
+		)?;
+
+		for (source, path, reason) in &self.rejected {
+			write!(f, "\n - {} [{}]: {}", source, path.display(), reason)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl Error for NoUsableInterpreter {}
+
+// Finds the python interpreter to run the installer with. The candidates are tried in this order:
+//  - the bundled runtime extracted with the installer
+//  - the interpreter chosen with --python or the SEVENTH_MOD_PYTHON environment variable, if any.
+//    This is for bundles without a python runtime (like the Linux bundle), or where it doesn't work.
+//  - python3 on the PATH
+// Each candidate is run with a short probe script, to check its version and that it has the
+// required stdlib modules. The first candidate which passes is used.
+pub fn resolve(config: &InstallerConfig) -> Result<PythonInterpreter, NoUsableInterpreter> {
+	resolve_candidates(candidates(config), &config.sub_folder)
+}
+
+// Resolves the interpreter (see resolve()) on another thread, as probing each candidate can take several
+// seconds, which would freeze the UI. Call poll() every frame until it returns the result.
+pub struct InterpreterResolver {
+	receiver: Receiver<Result<PythonInterpreter, NoUsableInterpreter>>,
+}
+
+impl InterpreterResolver {
+	pub fn start(config: &InstallerConfig) -> InterpreterResolver {
+		let candidates = candidates(config);
+		let working_directory = config.sub_folder.clone();
+		let (sender, receiver) = mpsc::channel();
+
+		thread::spawn(move || {
+			let _ = sender.send(resolve_candidates(candidates, &working_directory));
+		});
+
+		InterpreterResolver { receiver }
+	}
+
+	// Returns None while the candidates are still being probed
+	pub fn poll(&self) -> Option<Result<PythonInterpreter, Box<dyn Error>>> {
+		match self.receiver.try_recv() {
+			Ok(result) => Some(result.map_err(|e| e.into())),
+			Err(TryRecvError::Empty) => None,
+			Err(TryRecvError::Disconnected) => {
+				Some(Err("The python interpreter search stopped unexpectedly".into()))
+			}
+		}
+	}
+}
+
+fn candidates(config: &InstallerConfig) -> Vec<(InterpreterSource, PathBuf)> {
+	let mut candidates = vec![(InterpreterSource::Bundled, config.python_path.clone())];
+	if let Some(python_override) = &config.python_override {
+		candidates.push((InterpreterSource::Override, python_override.clone()));
+	}
+	candidates.push((InterpreterSource::System, PathBuf::from("python3")));
+	candidates
+}
+
+fn resolve_candidates(
+	candidates: Vec<(InterpreterSource, PathBuf)>,
+	working_directory: &Path,
+) -> Result<PythonInterpreter, NoUsableInterpreter> {
+	let mut rejected = Vec::new();
+
+	for (source, path) in candidates {
+		// Relative paths must be made absolute, as the installer is run in a different directory
+		let path = if path.components().count() > 1 {
+			windows_utilities::absolute_path(&path).unwrap_or(path)
+		} else {
+			path
+		};
+
+		match probe(&path, working_directory) {
+			Ok(version) => {
+				println!("Using {} [{}] (Python {})", source, path.display(), version);
+				return Ok(PythonInterpreter { path, source });
+			}
+			Err(reason) => {
+				println!("Rejected {} [{}]: {}", source, path.display(), reason);
+				rejected.push((source, path, reason.to_string()));
+			}
+		}
+	}
+
+	Err(NoUsableInterpreter { rejected })
+}
+
+// Runs the probe script with the interpreter, and returns its version if it is usable
+fn probe(path: &Path, working_directory: &Path) -> Result<String, Box<dyn Error>> {
+	// Only check files which should exist, so PATH lookups (like "python3") are left to the OS
+	if path.is_absolute() && !path.exists() {
+		return Err("not found".into());
+	}
+
+	let output = run_script(
+		path,
+		working_directory,
+		PROBE_SCRIPT,
+		REQUIRED_MODULES,
+		PROBE_TIMEOUT,
+	)
+	.map_err(|e| format!("couldn't be started ({})", e))?
+	.ok_or_else(|| format!("didn't respond within {} seconds", PROBE_TIMEOUT.as_secs()))?;
+
+	if !output.exit_status.success() {
+		return Err(format!("the probe failed ({})", output.exit_status).into());
+	}
+
+	Ok(parse_probe_output(&output.stdout)?)
+}
+
+// Reads the version and missing modules printed by PROBE_SCRIPT, and returns the version if the
+// interpreter is usable, otherwise why it isn't
+fn parse_probe_output(stdout: &str) -> Result<String, String> {
+	let mut lines = stdout.lines().map(str::trim);
+	let version = lines.next().unwrap_or_default().to_string();
+	let missing_modules = lines.next().unwrap_or_default();
+
+	let mut version_numbers = version.split('.').map(|number| number.parse::<u32>());
+	let major_minor = match (version_numbers.next(), version_numbers.next()) {
+		(Some(Ok(major)), Some(Ok(minor))) => (major, minor),
+		_ => return Err(format!("unexpected probe output [{}]", stdout.trim())),
+	};
+
+	if major_minor < MIN_PYTHON_VERSION {
+		return Err(format!(
+			"Python {} is too old (Python {}.{} or newer is needed)",
+			version, MIN_PYTHON_VERSION.0, MIN_PYTHON_VERSION.1
+		));
+	}
+
+	if !missing_modules.is_empty() {
+		return Err(format!(
+			"Python {} is missing the [{}] modules",
+			version, missing_modules
+		));
+	}
+
+	Ok(version)
+}
+
+// Runs a python script with the interpreter (in isolated mode, like the installer), and returns its
+// output, or None if it didn't exit before the timeout. Only for short scripts with little output, as
+// the output is read once the script has exited.
+pub fn run_script(
+	path: &Path,
+	working_directory: &Path,
+	script: &str,
+	args: &[&str],
+	timeout: Duration,
+) -> Result<Option<ScriptOutput>, Box<dyn Error>> {
+	let mut child = Command::new(path)
+		.current_dir(working_directory)
+		.arg("-E")
+		.arg("-c")
+		.arg(script)
+		.args(args)
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()?;
+
+	let started = Instant::now();
+	let exit_status = loop {
+		if let Some(exit_status) = child.try_wait()? {
+			break exit_status;
+		}
+
+		if started.elapsed() > timeout {
+			let _ = child.kill();
+			let _ = child.wait();
+			return Ok(None);
+		}
+
+		thread::sleep(Duration::from_millis(10));
+	};
+
+	let mut stdout = String::new();
+	if let Some(mut child_stdout) = child.stdout.take() {
+		child_stdout.read_to_string(&mut stdout)?;
+	}
+	let mut stderr = String::new();
+	if let Some(mut child_stderr) = child.stderr.take() {
+		child_stderr.read_to_string(&mut stderr)?;
+	}
+
+	Ok(Some(ScriptOutput {
+		exit_status,
+		stdout,
+		stderr,
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rejects_missing_interpreters() {
+		let folder = tempfile::tempdir().unwrap();
+		let missing_python = folder.path().join("python/python.exe");
+
+		let error = resolve_candidates(
+			vec![(InterpreterSource::Bundled, missing_python.clone())],
+			folder.path(),
+		)
+		.err()
+		.unwrap();
+
+		assert_eq!(
+			error.rejected,
+			vec![(
+				InterpreterSource::Bundled,
+				missing_python,
+				String::from("not found")
+			)]
+		);
+	}
+
+	#[test]
+	fn accepts_supported_versions() {
+		assert_eq!(parse_probe_output("3.7.0\n\n"), Ok(String::from("3.7.0")));
+		assert_eq!(
+			parse_probe_output("3.12.1\r\n\r\n"),
+			Ok(String::from("3.12.1"))
+		);
+		// Python 4 would be newer, rather than older, than 3.7
+		assert_eq!(parse_probe_output("4.0.0\n\n"), Ok(String::from("4.0.0")));
+		// The missing modules line may be left out entirely
+		assert_eq!(parse_probe_output("3.8.10\n"), Ok(String::from("3.8.10")));
+	}
+
+	#[test]
+	fn rejects_old_versions() {
+		let error = parse_probe_output("3.6.15\n\n").unwrap_err();
+		assert_eq!(
+			error,
+			"Python 3.6.15 is too old (Python 3.7 or newer is needed)"
+		);
+
+		// The probe script also runs with python 2
+		let error = parse_probe_output("2.7.18\n\n").unwrap_err();
+		assert_eq!(
+			error,
+			"Python 2.7.18 is too old (Python 3.7 or newer is needed)"
+		);
+
+		// Minor versions are compared as numbers, not text
+		assert!(parse_probe_output("3.10.0\n\n").is_ok());
+		assert!(parse_probe_output("3.1.0\n\n").is_err());
+	}
+
+	#[test]
+	fn rejects_missing_modules() {
+		assert_eq!(
+			parse_probe_output("3.11.4\nssl,sqlite3\n"),
+			Err(String::from(
+				"Python 3.11.4 is missing the [ssl,sqlite3] modules"
+			))
+		);
+		assert_eq!(
+			parse_probe_output("3.11.4\nsqlite3\n"),
+			Err(String::from(
+				"Python 3.11.4 is missing the [sqlite3] modules"
+			))
+		);
+	}
+
+	#[test]
+	fn rejects_unexpected_output() {
+		for output in &[
+			"",
+			"\n",
+			"Python 3.11.4\n\n",
+			"3\n\n",
+			"three.eleven\n\n",
+			"Traceback (most recent call last):\n  File \"<string>\", line 1\n",
+		] {
+			let error = parse_probe_output(output).unwrap_err();
+			assert!(error.starts_with("unexpected probe output ["), "{}", error);
+		}
+	}
+}
@@ -3,9 +3,12 @@ use std::ffi::OsStr;
 
 use crate::config::{InstallerConfig, LaunchType};
 use crate::process_runner::ProcessRunner;
+use crate::python_interpreter::PythonInterpreter;
+use crate::session_token::SESSION_TOKEN_ENV_VAR;
 
 pub fn launch_python_script(
 	config: &InstallerConfig,
+	python: &PythonInterpreter,
 	launch_type: LaunchType,
 ) -> Result<ProcessRunner, Box<dyn Error>> {
 	let mut args = vec!["-u", "-E"];
@@ -32,5 +35,13 @@ pub fn launch_python_script(
 		}
 	}
 
-	ProcessRunner::new(&config.python_path, &config.sub_folder, &args)
+	args.extend(config.python_args.iter().map(OsStr::new));
+
+	ProcessRunner::new(
+		&python.path,
+		&config.sub_folder,
+		&config.logs_folder,
+		&args,
+		&[(SESSION_TOKEN_ENV_VAR, config.session_token.as_str())],
+	)
 }
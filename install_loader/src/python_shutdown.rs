@@ -0,0 +1,395 @@
+use crate::config::{InstallerConfig, LaunchType};
+use crate::installer_webview;
+use crate::process_runner::ProcessRunner;
+use crate::session_token::SESSION_TOKEN_HEADER;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long to wait to connect to the python installer's web server, and for it to answer the shutdown request
+const SHUTDOWN_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+// The request the web page sends when the user closes the installer
+const SHUTDOWN_REQUEST_BODY: &str = r#"{"requestType":"shutdown","requestData":{}}"#;
+
+// How the python installer was stopped
+pub enum StopOutcome {
+	// Python exited after being asked to shut down
+	ShutDown,
+	// Python had already exited, so it wasn't asked to shut down
+	AlreadyExited,
+	// Python was killed, for the given reason
+	Killed(String),
+}
+
+impl fmt::Display for StopOutcome {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			StopOutcome::ShutDown => write!(f, "Python installer shut down cleanly"),
+			StopOutcome::AlreadyExited => write!(f, "Python installer had already exited"),
+			StopOutcome::Killed(reason) => write!(f, "Python installer was killed, as {}", reason),
+		}
+	}
+}
+
+// Stops the python installer without blocking the UI. Python is first asked to shut down through its
+// web server (the same request the web page sends when it is closed), and is only killed if the
+// request fails, or if python hasn't exited before the timeout. Call poll() every frame until it
+// returns the outcome.
+pub struct PythonStopper {
+	python_monitor: ProcessRunner,
+	// Receives the result of the shutdown request, which is sent on another thread. None if no request was sent.
+	shutdown_request: Option<Receiver<Result<(), String>>>,
+	// Set once python should be killed instead of waiting for it to exit
+	kill_reason: Option<String>,
+	started: Instant,
+	timeout: Duration,
+}
+
+impl PythonStopper {
+	pub fn start(
+		mut python_monitor: ProcessRunner,
+		launch_type: LaunchType,
+		config: &InstallerConfig,
+	) -> PythonStopper {
+		let mut shutdown_request = None;
+		let mut kill_reason = None;
+
+		if let Ok(Some(_)) = python_monitor.try_wait() {
+			// Nothing to ask - poll() just cleans up any processes python left running
+		} else if launch_type == LaunchType::TextMode {
+			kill_reason = Some(String::from(
+				"the text mode installer can't be asked to shut down",
+			));
+		} else {
+			match installer_webview::get_server_port(config) {
+				Ok(port) => {
+					println!(
+						"Asking the python installer on port {} to shut down...",
+						port
+					);
+					shutdown_request =
+						Some(send_shutdown_request(port, config.session_token.clone()));
+				}
+				Err(e) => {
+					kill_reason = Some(format!("its web server couldn't be found ({})", e));
+				}
+			}
+		}
+
+		PythonStopper {
+			python_monitor,
+			shutdown_request,
+			kill_reason,
+			started: Instant::now(),
+			timeout: config.python_shutdown_timeout,
+		}
+	}
+
+	// Kill python on the next poll(), instead of waiting for it to shut down
+	pub fn stop_now(&mut self) {
+		if self.kill_reason.is_none() {
+			self.kill_reason = Some(String::from("the user chose to stop it immediately"));
+		}
+	}
+
+	// How long until python is killed, if it hasn't exited by then
+	pub fn time_remaining(&self) -> Duration {
+		self.timeout.saturating_sub(self.started.elapsed())
+	}
+
+	// Returns None while still waiting for python to exit, otherwise how python was stopped.
+	// Any processes python started (like aria2c) are killed once python has stopped.
+	pub fn poll(&mut self) -> Option<Result<StopOutcome, Box<dyn Error>>> {
+		let exited = matches!(self.python_monitor.try_wait(), Ok(Some(_)));
+
+		if !exited && self.kill_reason.is_none() {
+			if let Some(Ok(Err(e))) = self
+				.shutdown_request
+				.as_ref()
+				.map(|request| request.try_recv())
+			{
+				self.kill_reason = Some(format!("the shutdown request failed ({})", e));
+			} else if self.time_remaining() == Duration::from_secs(0) {
+				self.kill_reason = Some(format!(
+					"it didn't exit within {} seconds of being asked to shut down",
+					self.timeout.as_secs()
+				));
+			} else {
+				return None;
+			}
+		}
+
+		let outcome = match (exited, self.kill_reason.take()) {
+			(true, _) if self.shutdown_request.is_some() => StopOutcome::ShutDown,
+			(true, _) => StopOutcome::AlreadyExited,
+			(false, reason) => StopOutcome::Killed(reason.unwrap_or_default()),
+		};
+
+		if let Err(e) = self.python_monitor.kill_wait() {
+			return Some(Err(e));
+		}
+
+		Some(Ok(outcome))
+	}
+}
+
+fn send_shutdown_request(port: usize, session_token: String) -> Receiver<Result<(), String>> {
+	let (sender, receiver) = mpsc::channel();
+
+	thread::spawn(move || {
+		let _ = sender.send(post_shutdown_request(port, &session_token).map_err(|e| e.to_string()));
+	});
+
+	receiver
+}
+
+// Sends the shutdown request to the python installer's web server, and checks python accepted it
+fn post_shutdown_request(port: usize, session_token: &str) -> Result<(), Box<dyn Error>> {
+	let port = u16::try_from(port)?;
+	let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+
+	let mut stream = TcpStream::connect_timeout(&address, SHUTDOWN_REQUEST_TIMEOUT)?;
+	stream.set_read_timeout(Some(SHUTDOWN_REQUEST_TIMEOUT))?;
+	stream.set_write_timeout(Some(SHUTDOWN_REQUEST_TIMEOUT))?;
+
+	// The server closes the connection after responding to a HTTP/1.0 request, so the whole response can be read
+	write!(
+		stream,
+		"POST /installer_data HTTP/1.0\r\nHost: 127.0.0.1:{}\r\n{}: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+		port,
+		SESSION_TOKEN_HEADER,
+		session_token,
+		SHUTDOWN_REQUEST_BODY.len(),
+		SHUTDOWN_REQUEST_BODY
+	)?;
+
+	let mut response = String::new();
+	stream.read_to_string(&mut response)?;
+
+	let (head, body) = response
+		.split_once("\r\n\r\n")
+		.ok_or("the response was incomplete")?;
+	let status_line = head.lines().next().unwrap_or_default();
+	if status_line.split_whitespace().nth(1) != Some("200") {
+		return Err(format!("unexpected response status [{}]", status_line).into());
+	}
+
+	// Errors are also sent with a 200 status, so check the installer actually handled the request
+	let response_json: serde_json::Value = serde_json::from_str(body)?;
+	if response_json["responseType"] != "shutdown" {
+		return Err(format!("unexpected response [{}]", body).into());
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::net::TcpListener;
+	#[cfg(unix)]
+	use std::path::Path;
+	use std::thread::JoinHandle;
+
+	const TOKEN: &str = "test-token";
+
+	const SHUTDOWN_RESPONSE: &str =
+		"HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{\"responseType\":\"shutdown\",\"responseData\":{}}";
+
+	// Accepts one connection, reads the shutdown request and sends the response (if any).
+	// Returns the port and the request which was received.
+	fn serve_once(response: Option<&'static str>) -> (usize, JoinHandle<String>) {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		let port = listener.local_addr().unwrap().port() as usize;
+
+		let server = thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+			let mut request = Vec::new();
+			let mut buffer = [0; 1024];
+			while !request.ends_with(SHUTDOWN_REQUEST_BODY.as_bytes()) {
+				match stream.read(&mut buffer).unwrap() {
+					0 => break,
+					count => request.extend_from_slice(&buffer[..count]),
+				}
+			}
+
+			match response {
+				Some(response) => stream.write_all(response.as_bytes()).unwrap(),
+				// Keep the connection open without answering, until the client gives up
+				None => {
+					let _ = stream.read(&mut buffer);
+				}
+			}
+
+			String::from_utf8(request).unwrap()
+		});
+
+		(port, server)
+	}
+
+	// A port nothing is listening on
+	fn closed_port() -> usize {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		listener.local_addr().unwrap().port() as usize
+	}
+
+	#[test]
+	fn sends_shutdown_request_with_session_token() {
+		let (port, server) = serve_once(Some(SHUTDOWN_RESPONSE));
+
+		post_shutdown_request(port, TOKEN).unwrap();
+
+		let request = server.join().unwrap();
+		assert!(request.starts_with("POST /installer_data HTTP/1.0\r\n"));
+		assert!(request.contains(&format!("\r\n{}: {}\r\n", SESSION_TOKEN_HEADER, TOKEN)));
+		assert!(request.ends_with(&format!("\r\n\r\n{}", SHUTDOWN_REQUEST_BODY)));
+	}
+
+	#[test]
+	fn rejects_unsuccessful_responses() {
+		for response in &[
+			"HTTP/1.0 403 Forbidden\r\n\r\n",
+			// The installer reports errors with a 200 status
+			"HTTP/1.0 200 OK\r\n\r\n{\"responseType\":\"error\",\"responseData\":{}}",
+			"HTTP/1.0 200 OK\r\n\r\nnot json",
+			"HTTP/1.0 200 OK\r\n",
+		] {
+			let (port, server) = serve_once(Some(response));
+			assert!(post_shutdown_request(port, TOKEN).is_err(), "{}", response);
+			server.join().unwrap();
+		}
+	}
+
+	#[test]
+	fn fails_if_nothing_is_listening() {
+		assert!(post_shutdown_request(closed_port(), TOKEN).is_err());
+		assert!(post_shutdown_request(usize::from(u16::MAX) + 1, TOKEN).is_err());
+	}
+
+	#[test]
+	fn fails_if_the_server_never_answers() {
+		let (port, server) = serve_once(None);
+
+		let started = Instant::now();
+		assert!(post_shutdown_request(port, TOKEN).is_err());
+		assert!(started.elapsed() >= SHUTDOWN_REQUEST_TIMEOUT);
+		server.join().unwrap();
+	}
+
+	// The stopper is created directly, as start() needs the installer config
+	#[cfg(unix)]
+	fn start_stopper(
+		child_seconds: u32,
+		shutdown_port: usize,
+		timeout: Duration,
+		logs_folder: &Path,
+	) -> PythonStopper {
+		let python_monitor = ProcessRunner::new(
+			Path::new("sleep"),
+			logs_folder,
+			logs_folder,
+			&[child_seconds.to_string()],
+			&[],
+		)
+		.unwrap();
+
+		PythonStopper {
+			python_monitor,
+			shutdown_request: Some(send_shutdown_request(shutdown_port, TOKEN.to_string())),
+			kill_reason: None,
+			started: Instant::now(),
+			timeout,
+		}
+	}
+
+	#[cfg(unix)]
+	fn poll_until_stopped(stopper: &mut PythonStopper) -> StopOutcome {
+		let started = Instant::now();
+		loop {
+			if let Some(outcome) = stopper.poll() {
+				return outcome.unwrap();
+			}
+			assert!(
+				started.elapsed() < Duration::from_secs(20),
+				"python was never stopped"
+			);
+			thread::sleep(Duration::from_millis(10));
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn waits_for_python_to_exit_after_shutdown_request() {
+		let logs = tempfile::tempdir().unwrap();
+		let (port, server) = serve_once(Some(SHUTDOWN_RESPONSE));
+		let mut stopper = start_stopper(1, port, Duration::from_secs(20), logs.path());
+
+		assert!(matches!(
+			poll_until_stopped(&mut stopper),
+			StopOutcome::ShutDown
+		));
+		server.join().unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn kills_python_if_the_shutdown_request_fails() {
+		let logs = tempfile::tempdir().unwrap();
+
+		let (port, server) = serve_once(Some("HTTP/1.0 500 Internal Server Error\r\n\r\n"));
+		let mut stopper = start_stopper(30, port, Duration::from_secs(20), logs.path());
+		match poll_until_stopped(&mut stopper) {
+			StopOutcome::Killed(reason) => {
+				assert!(reason.contains("shutdown request failed"), "{}", reason)
+			}
+			outcome => panic!("python wasn't killed: {}", outcome),
+		}
+		server.join().unwrap();
+
+		let mut stopper = start_stopper(30, closed_port(), Duration::from_secs(20), logs.path());
+		match poll_until_stopped(&mut stopper) {
+			StopOutcome::Killed(reason) => {
+				assert!(reason.contains("shutdown request failed"), "{}", reason)
+			}
+			outcome => panic!("python wasn't killed: {}", outcome),
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn kills_python_if_it_does_not_exit_in_time() {
+		let logs = tempfile::tempdir().unwrap();
+		let (port, server) = serve_once(None);
+		let mut stopper = start_stopper(30, port, Duration::from_millis(200), logs.path());
+
+		match poll_until_stopped(&mut stopper) {
+			StopOutcome::Killed(reason) => {
+				assert!(reason.contains("didn't exit within"), "{}", reason)
+			}
+			outcome => panic!("python wasn't killed: {}", outcome),
+		}
+		server.join().unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn kills_python_immediately_when_requested() {
+		let logs = tempfile::tempdir().unwrap();
+		let (port, server) = serve_once(None);
+		let mut stopper = start_stopper(30, port, Duration::from_secs(20), logs.path());
+
+		assert!(stopper.poll().is_none());
+		stopper.stop_now();
+		match poll_until_stopped(&mut stopper) {
+			StopOutcome::Killed(reason) => assert!(reason.contains("the user chose"), "{}", reason),
+			outcome => panic!("python wasn't killed: {}", outcome),
+		}
+		server.join().unwrap();
+	}
+}
@@ -0,0 +1,365 @@
+use crate::config::LaunchType;
+use std::fmt;
+use std::process::ExitStatus;
+use std::time::{Duration, Instant};
+
+// How many times python is automatically restarted with the same launch type, before the next
+// launch type is tried. A crash during startup usually means the launch type doesn't work on this
+// computer, so it is only retried once. A crash after the server started means the launch type
+// works, so it is retried more times before giving up on it.
+const MAX_STARTUP_RESTARTS_PER_LAUNCH_TYPE: u32 = 1;
+const MAX_SESSION_RESTARTS_PER_LAUNCH_TYPE: u32 = 3;
+
+// The delay before the first restart with a launch type. It doubles with each restart after that.
+const FIRST_RESTART_DELAY: Duration = Duration::from_secs(2);
+
+// How the python installer exited
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExitKind {
+	// The user closed the installer, or the install finished
+	CleanQuit,
+	// Python crashed before its web server was started (or the text mode installer crashed)
+	CrashDuringStartup,
+	// Python crashed after its web server was started, so the user was probably using the installer
+	CrashAfterServerStarted,
+}
+
+impl ExitKind {
+	// 'server_started' should be true if python wrote the server info file before it exited
+	pub fn classify(exit_status: ExitStatus, server_started: bool) -> ExitKind {
+		if exit_status.success() {
+			ExitKind::CleanQuit
+		} else if server_started {
+			ExitKind::CrashAfterServerStarted
+		} else {
+			ExitKind::CrashDuringStartup
+		}
+	}
+}
+
+impl fmt::Display for ExitKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ExitKind::CleanQuit => write!(f, "quit"),
+			ExitKind::CrashDuringStartup => write!(f, "crashed during startup"),
+			ExitKind::CrashAfterServerStarted => write!(f, "crashed after the installer started"),
+		}
+	}
+}
+
+// What to do after the python installer exited
+#[derive(Debug, PartialEq, Eq)]
+pub enum SupervisorDecision {
+	Quit,
+	Restart {
+		launch_type: LaunchType,
+		delay: Duration,
+	},
+	// Python crashed too many times with every launch type
+	GiveUp,
+}
+
+// A launch of the python installer, shown to the user in the launch history
+pub struct LaunchAttempt {
+	launch_type: LaunchType,
+	started: Instant,
+	// How the attempt ended and how long python ran for, or None if python is still running
+	result: Option<(String, Duration)>,
+}
+
+impl fmt::Display for LaunchAttempt {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match &self.result {
+			Some((result, run_time)) => write!(
+				f,
+				"{:?} (ran for {} seconds): {}",
+				self.launch_type,
+				run_time.as_secs(),
+				result
+			),
+			None => write!(
+				f,
+				"{:?} (running for {} seconds)",
+				self.launch_type,
+				self.started.elapsed().as_secs()
+			),
+		}
+	}
+}
+
+// Decides whether to restart the python installer when it exits. Crashed installers are restarted
+// with the same launch type, with a delay which increases each time. If the installer keeps crashing,
+// the next launch type is tried (WebView, then Browser, then TextMode), and if every launch type fails
+// the install has failed. Crashes during startup switch to the next launch type sooner than crashes
+// after the server started. Restarts chosen by the user don't count towards the automatic restarts.
+#[derive(Default)]
+pub struct PythonSupervisor {
+	history: Vec<LaunchAttempt>,
+	// Automatic restarts after each kind of crash, since the launch type last changed
+	startup_restart_count: u32,
+	session_restart_count: u32,
+}
+
+impl PythonSupervisor {
+	// Records that the python installer was launched
+	pub fn launched(&mut self, launch_type: LaunchType) {
+		if self
+			.history
+			.last()
+			.map_or(true, |attempt| attempt.launch_type != launch_type)
+		{
+			self.reset_restart_counts();
+		}
+
+		self.history.push(LaunchAttempt {
+			launch_type,
+			started: Instant::now(),
+			result: None,
+		});
+	}
+
+	// Records how the python installer was stopped by the launcher (e.g. when the user chose to restart it)
+	pub fn stopped(&mut self, description: String) {
+		self.finish_attempt(description);
+		self.reset_restart_counts();
+	}
+
+	// Records how the python installer exited by itself, and decides what to do next
+	pub fn exited(&mut self, exit_status: ExitStatus, server_started: bool) -> SupervisorDecision {
+		let exit_kind = ExitKind::classify(exit_status, server_started);
+		self.finish_attempt(format!("{} ({})", exit_kind, exit_status));
+
+		match self.history.last() {
+			Some(attempt) => self.decide(attempt.launch_type, exit_kind),
+			None => SupervisorDecision::GiveUp,
+		}
+	}
+
+	// Every launch of the python installer, oldest first
+	pub fn history(&self) -> &[LaunchAttempt] {
+		&self.history
+	}
+
+	fn decide(&mut self, launch_type: LaunchType, exit_kind: ExitKind) -> SupervisorDecision {
+		let (restart_count, max_restarts) = match exit_kind {
+			ExitKind::CleanQuit => return SupervisorDecision::Quit,
+			ExitKind::CrashDuringStartup => (
+				&mut self.startup_restart_count,
+				MAX_STARTUP_RESTARTS_PER_LAUNCH_TYPE,
+			),
+			ExitKind::CrashAfterServerStarted => (
+				&mut self.session_restart_count,
+				MAX_SESSION_RESTARTS_PER_LAUNCH_TYPE,
+			),
+		};
+
+		if *restart_count < max_restarts {
+			*restart_count += 1;
+			return SupervisorDecision::Restart {
+				launch_type,
+				delay: FIRST_RESTART_DELAY * 2u32.pow(*restart_count - 1),
+			};
+		}
+
+		match next_launch_type(launch_type) {
+			Some(next_launch_type) => SupervisorDecision::Restart {
+				launch_type: next_launch_type,
+				delay: FIRST_RESTART_DELAY,
+			},
+			None => SupervisorDecision::GiveUp,
+		}
+	}
+
+	fn reset_restart_counts(&mut self) {
+		self.startup_restart_count = 0;
+		self.session_restart_count = 0;
+	}
+
+	// Sets the result of the latest attempt, if it doesn't already have one
+	fn finish_attempt(&mut self, result: String) {
+		if let Some(attempt) = self.history.last_mut() {
+			if attempt.result.is_none() {
+				attempt.result = Some((result, attempt.started.elapsed()));
+			}
+		}
+	}
+}
+
+// The launch type to fall back to if python keeps crashing with 'launch_type'
+fn next_launch_type(launch_type: LaunchType) -> Option<LaunchType> {
+	match launch_type {
+		LaunchType::WebView => Some(LaunchType::Browser),
+		LaunchType::Browser => Some(LaunchType::TextMode),
+		LaunchType::TextMode => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(unix)]
+	fn exit_status(code: i32) -> ExitStatus {
+		use std::os::unix::process::ExitStatusExt;
+		// The raw wait status has the exit code in the second byte
+		ExitStatus::from_raw(code << 8)
+	}
+
+	#[cfg(windows)]
+	fn exit_status(code: i32) -> ExitStatus {
+		use std::os::windows::process::ExitStatusExt;
+		ExitStatus::from_raw(code as u32)
+	}
+
+	fn restart(launch_type: LaunchType, delay_seconds: u64) -> SupervisorDecision {
+		SupervisorDecision::Restart {
+			launch_type,
+			delay: Duration::from_secs(delay_seconds),
+		}
+	}
+
+	// Launches python with the given launch type, then has it exit with 'exit_kind'
+	fn launch_and_exit(
+		supervisor: &mut PythonSupervisor,
+		launch_type: LaunchType,
+		exit_kind: ExitKind,
+	) -> SupervisorDecision {
+		supervisor.launched(launch_type);
+		let (code, server_started) = match exit_kind {
+			ExitKind::CleanQuit => (0, true),
+			ExitKind::CrashDuringStartup => (1, false),
+			ExitKind::CrashAfterServerStarted => (1, true),
+		};
+		supervisor.exited(exit_status(code), server_started)
+	}
+
+	#[test]
+	fn classifies_exits() {
+		assert_eq!(
+			ExitKind::classify(exit_status(0), false),
+			ExitKind::CleanQuit
+		);
+		assert_eq!(
+			ExitKind::classify(exit_status(0), true),
+			ExitKind::CleanQuit
+		);
+		assert_eq!(
+			ExitKind::classify(exit_status(1), false),
+			ExitKind::CrashDuringStartup
+		);
+		assert_eq!(
+			ExitKind::classify(exit_status(1), true),
+			ExitKind::CrashAfterServerStarted
+		);
+	}
+
+	#[test]
+	fn quits_after_clean_exit() {
+		let mut supervisor = PythonSupervisor::default();
+		assert_eq!(
+			launch_and_exit(&mut supervisor, LaunchType::WebView, ExitKind::CleanQuit),
+			SupervisorDecision::Quit
+		);
+	}
+
+	#[test]
+	fn falls_back_quickly_after_startup_crashes() {
+		let mut supervisor = PythonSupervisor::default();
+		let mut launch = |launch_type| {
+			launch_and_exit(&mut supervisor, launch_type, ExitKind::CrashDuringStartup)
+		};
+
+		assert_eq!(launch(LaunchType::WebView), restart(LaunchType::WebView, 2));
+		assert_eq!(launch(LaunchType::WebView), restart(LaunchType::Browser, 2));
+		assert_eq!(launch(LaunchType::Browser), restart(LaunchType::Browser, 2));
+		assert_eq!(
+			launch(LaunchType::Browser),
+			restart(LaunchType::TextMode, 2)
+		);
+		assert_eq!(
+			launch(LaunchType::TextMode),
+			restart(LaunchType::TextMode, 2)
+		);
+		assert_eq!(launch(LaunchType::TextMode), SupervisorDecision::GiveUp);
+	}
+
+	#[test]
+	fn restarts_same_launch_type_with_backoff_after_session_crashes() {
+		let mut supervisor = PythonSupervisor::default();
+		let mut launch = |launch_type| {
+			launch_and_exit(
+				&mut supervisor,
+				launch_type,
+				ExitKind::CrashAfterServerStarted,
+			)
+		};
+
+		assert_eq!(launch(LaunchType::WebView), restart(LaunchType::WebView, 2));
+		assert_eq!(launch(LaunchType::WebView), restart(LaunchType::WebView, 4));
+		assert_eq!(launch(LaunchType::WebView), restart(LaunchType::WebView, 8));
+		assert_eq!(launch(LaunchType::WebView), restart(LaunchType::Browser, 2));
+		// The restart count starts again with the new launch type
+		assert_eq!(launch(LaunchType::Browser), restart(LaunchType::Browser, 2));
+	}
+
+	#[test]
+	fn counts_startup_and_session_crashes_separately() {
+		let mut supervisor = PythonSupervisor::default();
+
+		assert_eq!(
+			launch_and_exit(
+				&mut supervisor,
+				LaunchType::WebView,
+				ExitKind::CrashAfterServerStarted
+			),
+			restart(LaunchType::WebView, 2)
+		);
+		assert_eq!(
+			launch_and_exit(
+				&mut supervisor,
+				LaunchType::WebView,
+				ExitKind::CrashDuringStartup
+			),
+			restart(LaunchType::WebView, 2)
+		);
+		assert_eq!(
+			launch_and_exit(
+				&mut supervisor,
+				LaunchType::WebView,
+				ExitKind::CrashAfterServerStarted
+			),
+			restart(LaunchType::WebView, 4)
+		);
+		assert_eq!(
+			launch_and_exit(
+				&mut supervisor,
+				LaunchType::WebView,
+				ExitKind::CrashDuringStartup
+			),
+			restart(LaunchType::Browser, 2)
+		);
+	}
+
+	#[test]
+	fn user_restart_resets_restart_counts() {
+		let mut supervisor = PythonSupervisor::default();
+
+		launch_and_exit(
+			&mut supervisor,
+			LaunchType::WebView,
+			ExitKind::CrashDuringStartup,
+		);
+		supervisor.launched(LaunchType::WebView);
+		supervisor.stopped(String::from("Restarted by the user"));
+
+		assert_eq!(
+			launch_and_exit(
+				&mut supervisor,
+				LaunchType::WebView,
+				ExitKind::CrashDuringStartup
+			),
+			restart(LaunchType::WebView, 2)
+		);
+		assert_eq!(supervisor.history().len(), 3);
+	}
+}
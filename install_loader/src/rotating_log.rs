@@ -0,0 +1,290 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Once a log file reaches this size, a new log file is started
+const MAX_LOG_FILE_BYTES: u64 = 5 * 1024 * 1024;
+// Only this many log files (with the same prefix) are kept - older files are deleted
+const MAX_LOG_FILES: usize = 10;
+
+// Writes lines to log files named '<prefix>_<UTC date and time>.log' in a folder. Each line is
+// prefixed with the time it was written. When a file gets too large, a new one is started with the
+// same name plus a number ('_001', '_002' etc.), and the oldest files are deleted so the folder
+// doesn't grow forever. Existing files are never overwritten - if a file with the same name already
+// exists (e.g. two launchers started in the same second), the next number is used instead.
+pub struct RotatingLog {
+	folder: PathBuf,
+	prefix: String,
+	// '<prefix>_<UTC date and time the log was started>'
+	base_name: String,
+	rotation_count: u32,
+	file: File,
+	file_size: u64,
+}
+
+impl RotatingLog {
+	pub fn new(folder: &Path, prefix: &str) -> io::Result<RotatingLog> {
+		fs::create_dir_all(folder)?;
+
+		let now = UtcDateTime::from(SystemTime::now());
+		let base_name = format!(
+			"{}_{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+			prefix, now.year, now.month, now.day, now.hour, now.minute, now.second
+		);
+		let mut rotation_count = 0;
+		let (path, file) = create_log_file(folder, &base_name, &mut rotation_count)?;
+		println!("Logging to {:?}", path);
+
+		let log = RotatingLog {
+			folder: folder.to_path_buf(),
+			prefix: prefix.to_string(),
+			base_name,
+			rotation_count,
+			file,
+			file_size: 0,
+		};
+		log.remove_old_logs();
+		Ok(log)
+	}
+
+	// Writes a line to the log, tagged with the stream it came from (e.g. "stdout")
+	pub fn write_line(&mut self, stream_name: &str, line: &str) -> io::Result<()> {
+		if self.file_size >= MAX_LOG_FILE_BYTES {
+			self.rotate()?;
+		}
+
+		let now = UtcDateTime::from(SystemTime::now());
+		let record = format!(
+			"[{:02}:{:02}:{:02} {}] {}\n",
+			now.hour, now.minute, now.second, stream_name, line
+		);
+		self.file.write_all(record.as_bytes())?;
+		self.file_size += record.len() as u64;
+		Ok(())
+	}
+
+	fn rotate(&mut self) -> io::Result<()> {
+		self.rotation_count += 1;
+		let (_, file) = create_log_file(&self.folder, &self.base_name, &mut self.rotation_count)?;
+		self.file = file;
+		self.file_size = 0;
+		self.remove_old_logs();
+		Ok(())
+	}
+
+	// Deletes all but the newest MAX_LOG_FILES log files. The file names start with the date and time,
+	// so sorting by name sorts them from oldest to newest.
+	fn remove_old_logs(&self) {
+		let mut log_paths: Vec<PathBuf> = match fs::read_dir(&self.folder) {
+			Ok(entries) => entries
+				.filter_map(|entry| entry.ok())
+				.map(|entry| entry.path())
+				.filter(|path| is_log_file(path, &self.prefix))
+				.collect(),
+			Err(_) => return,
+		};
+
+		log_paths.sort();
+		let remove_count = log_paths.len().saturating_sub(MAX_LOG_FILES);
+		for path in &log_paths[..remove_count] {
+			if let Err(e) = fs::remove_file(path) {
+				println!("Failed to remove old log file {:?}: {}", path, e);
+			}
+		}
+	}
+}
+
+// Creates '<base_name>.log' (if rotation_count is 0) or '<base_name>_<rotation_count>.log'. If the file
+// already exists, rotation_count is increased until a name which isn't taken is found.
+fn create_log_file(
+	folder: &Path,
+	base_name: &str,
+	rotation_count: &mut u32,
+) -> io::Result<(PathBuf, File)> {
+	loop {
+		// The number is zero padded so the names still sort from oldest to newest
+		let path = if *rotation_count == 0 {
+			folder.join(format!("{}.log", base_name))
+		} else {
+			folder.join(format!("{}_{:03}.log", base_name, rotation_count))
+		};
+
+		match OpenOptions::new().write(true).create_new(true).open(&path) {
+			Ok(file) => return Ok((path, file)),
+			Err(e) if e.kind() == io::ErrorKind::AlreadyExists => *rotation_count += 1,
+			Err(e) => return Err(e),
+		}
+	}
+}
+
+fn is_log_file(path: &Path, prefix: &str) -> bool {
+	path.file_name()
+		.and_then(|name| name.to_str())
+		.map_or(false, |name| {
+			name.starts_with(&format!("{}_", prefix)) && name.ends_with(".log")
+		})
+}
+
+// A UTC date and time, used to name the log files and timestamp each line
+struct UtcDateTime {
+	year: i64,
+	month: u32,
+	day: u32,
+	hour: u64,
+	minute: u64,
+	second: u64,
+}
+
+impl From<SystemTime> for UtcDateTime {
+	fn from(time: SystemTime) -> UtcDateTime {
+		let seconds = time
+			.duration_since(UNIX_EPOCH)
+			.map(|duration| duration.as_secs())
+			.unwrap_or(0);
+		let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+		let seconds_of_day = seconds % 86400;
+
+		UtcDateTime {
+			year,
+			month,
+			day,
+			hour: seconds_of_day / 3600,
+			minute: seconds_of_day / 60 % 60,
+			second: seconds_of_day % 60,
+		}
+	}
+}
+
+// Converts days since 1970-01-01 to a (year, month, day) date.
+// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let day_of_era = z.rem_euclid(146_097);
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month_index = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+	let month = if month_index < 10 {
+		month_index + 3
+	} else {
+		month_index - 9
+	};
+	let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+	(year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn log_file_names(folder: &Path) -> Vec<String> {
+		let mut names: Vec<String> = fs::read_dir(folder)
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+			.collect();
+		names.sort();
+		names
+	}
+
+	#[test]
+	fn converts_days_to_dates() {
+		assert_eq!(civil_from_days(0), (1970, 1, 1));
+		assert_eq!(civil_from_days(-1), (1969, 12, 31));
+		assert_eq!(civil_from_days(59), (1970, 3, 1));
+		assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+		assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+		assert_eq!(civil_from_days(19_358), (2023, 1, 1));
+		assert_eq!(civil_from_days(47_541), (2100, 3, 1));
+	}
+
+	#[test]
+	fn formats_system_time() {
+		// 2001-09-09 01:46:40 UTC
+		let time = UtcDateTime::from(UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000));
+		assert_eq!((time.year, time.month, time.day), (2001, 9, 9));
+		assert_eq!((time.hour, time.minute, time.second), (1, 46, 40));
+	}
+
+	#[test]
+	fn never_overwrites_existing_log_files() {
+		let folder = tempfile::tempdir().unwrap();
+		fs::write(folder.path().join("test.log"), "first").unwrap();
+		fs::write(folder.path().join("test_001.log"), "second").unwrap();
+
+		let mut rotation_count = 0;
+		let (path, _) = create_log_file(folder.path(), "test", &mut rotation_count).unwrap();
+		assert_eq!(path, folder.path().join("test_002.log"));
+		assert_eq!(rotation_count, 2);
+		assert_eq!(
+			fs::read_to_string(folder.path().join("test.log")).unwrap(),
+			"first"
+		);
+		assert_eq!(
+			fs::read_to_string(folder.path().join("test_001.log")).unwrap(),
+			"second"
+		);
+	}
+
+	#[test]
+	fn rotates_once_the_file_is_too_large() {
+		let folder = tempfile::tempdir().unwrap();
+		let mut log = RotatingLog::new(folder.path(), "test").unwrap();
+		let first_file = format!("{}.log", log.base_name);
+		let second_file = format!("{}_001.log", log.base_name);
+
+		// Files are only rotated once they have reached the limit
+		log.file_size = MAX_LOG_FILE_BYTES - 1;
+		log.write_line("stdout", "still in the first file").unwrap();
+		assert_eq!(log_file_names(folder.path()), vec![first_file.clone()]);
+
+		log.write_line("stdout", "in the second file").unwrap();
+		assert_eq!(
+			log_file_names(folder.path()),
+			vec![first_file.clone(), second_file.clone()]
+		);
+
+		let first_contents = fs::read_to_string(folder.path().join(&first_file)).unwrap();
+		let second_contents = fs::read_to_string(folder.path().join(&second_file)).unwrap();
+		assert!(first_contents.ends_with("stdout] still in the first file\n"));
+		assert!(second_contents.ends_with("stdout] in the second file\n"));
+		assert_eq!(log.file_size, second_contents.len() as u64);
+	}
+
+	#[test]
+	fn removes_oldest_log_files() {
+		let folder = tempfile::tempdir().unwrap();
+		for day in 1..=MAX_LOG_FILES + 2 {
+			fs::write(
+				folder
+					.path()
+					.join(format!("test_2000-01-{:02}_00-00-00.log", day)),
+				"",
+			)
+			.unwrap();
+		}
+		// Files which aren't logs with this prefix are never removed
+		fs::write(folder.path().join("other_2000-01-01_00-00-00.log"), "").unwrap();
+		fs::write(folder.path().join("test_2000-01-01_00-00-00.txt"), "").unwrap();
+
+		let log = RotatingLog::new(folder.path(), "test").unwrap();
+
+		let names = log_file_names(folder.path());
+		let log_names: Vec<&String> = names
+			.iter()
+			.filter(|name| is_log_file(Path::new(name), "test"))
+			.collect();
+		assert_eq!(log_names.len(), MAX_LOG_FILES);
+		// The three oldest logs were removed to make room for the new one
+		assert_eq!(log_names[0], "test_2000-01-04_00-00-00.log");
+		assert_eq!(
+			*log_names[MAX_LOG_FILES - 1],
+			format!("{}.log", log.base_name)
+		);
+		assert!(names.contains(&"other_2000-01-01_00-00-00.log".to_string()));
+		assert!(names.contains(&"test_2000-01-01_00-00-00.txt".to_string()));
+	}
+}
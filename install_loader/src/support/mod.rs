@@ -13,6 +13,7 @@ use std::time::Instant;
 use crate::installer_webview::UserEvent;
 use crate::resources;
 
+#[cfg(any(windows, target_os = "macos"))]
 mod clipboard;
 
 pub struct System {
@@ -44,12 +45,14 @@ pub fn init(title: &str, window_size: [f64; 2]) -> System {
 	let mut imgui = Context::create();
 	imgui.set_ini_filename(None);
 
-	// Clipboard disabled for now as we don't use it
-	// if let Some(backend) = clipboard::init() {
-	// 	imgui.set_clipboard_backend(backend);
-	// } else {
-	// 	eprintln!("Failed to initialize clipboard");
-	// }
+	// The clipboard is used to copy the installer output from the log viewer. On Linux, imgui's own
+	// clipboard is used instead, so copied text can only be pasted within the launcher.
+	#[cfg(any(windows, target_os = "macos"))]
+	if let Some(backend) = clipboard::init() {
+		imgui.set_clipboard_backend(backend);
+	} else {
+		eprintln!("Failed to initialize clipboard");
+	}
 
 	let mut platform = WinitPlatform::init(&mut imgui);
 	{
@@ -147,6 +150,10 @@ impl System {
 						terminate_next_frame = true;
 					}
 
+					if let Some(window_title) = &next_frame_commands.window_title {
+						window.set_title(window_title);
+					}
+
 					if next_frame_commands.force_show_window {
 						window.set_minimized(false);
 						window.set_visible(true);
@@ -195,6 +202,8 @@ pub struct NextFrameCommands {
 	pub run: bool,
 	pub force_show_window: bool,
 	pub retry_using_tempdir: bool,
+	// Changes the window title, if set
+	pub window_title: Option<String>,
 }
 
 pub trait ApplicationGUI {
@@ -5,20 +5,36 @@ use imgui::*;
 use tempfile::TempDir;
 use wry::application::event_loop::EventLoopProxy;
 
-use crate::archive_extractor::{ArchiveExtractor, ExtractionStatus};
+use crate::archive_extractor::{ArchiveExtractor, ExtractionProgress, ExtractionStatus};
 use crate::config::{InstallerConfig, LaunchType};
+use crate::install_status::InstallStatus;
 use crate::installer_webview::UserEvent;
-use crate::process_runner::ProcessRunner;
+use crate::launch_preferences::LaunchPreferences;
+use crate::loader_config::{LoaderConfig, TempDirPolicy};
+use crate::payload::PayloadSource;
+use crate::process_runner::{OutputHistory, ProcessRunner};
+use crate::python_health_check::{HealthCheck, HealthProblem};
+use crate::python_interpreter::{InterpreterResolver, PythonInterpreter};
+use crate::python_shutdown::{PythonStopper, StopOutcome};
+use crate::python_supervisor::{PythonSupervisor, SupervisorDecision};
+use crate::session_token;
 use crate::{python_launcher, installer_webview};
 use crate::support;
 use crate::support::{AppBuilder, ApplicationGUI, NextFrameCommands};
 use crate::version;
 use crate::windows_utilities;
-use std::path::{PathBuf, Path};
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use anyhow::Result;
 
 const MOUSE_ACTIVITY_TIMEOUT_SECS: u64 = 1;
 
+fn launcher_window_title() -> String {
+	format!("07th-Mod Installer Launcher [{}]", version::travis_tag())
+}
+
 pub struct TimeoutTimer {
 	last_refresh: std::time::Instant,
 	timeout: std::time::Duration,
@@ -88,6 +104,11 @@ pub struct InstallStartedState {
 	pub timer: TimeoutTimer,
 	pub webview_launched: bool,
 	pub python_started_poll_count: usize,
+	// Set once this launch type has been remembered as working (see LaunchPreferences)
+	pub launch_worked: bool,
+	// The installer page url, including the session token. It is only shown in this window (never printed, as
+	// the output is logged), so the user can open it manually if the web page did not open.
+	pub installer_url: Option<String>,
 }
 
 impl InstallStartedState {
@@ -98,6 +119,8 @@ impl InstallStartedState {
 			timer,
 			webview_launched: false,
 			python_started_poll_count: 0,
+			launch_worked: false,
+			installer_url: None,
 		}
 	}
 }
@@ -105,6 +128,8 @@ impl InstallStartedState {
 pub struct InstallFailedState {
 	pub failure_reason: String,
 	pub console_window_displayed: bool,
+	// Set if the install failed as the python runtime failed its health check
+	pub health_problems: Vec<HealthProblem>,
 }
 
 impl InstallFailedState {
@@ -112,28 +137,84 @@ impl InstallFailedState {
 		InstallFailedState {
 			failure_reason,
 			console_window_displayed: false,
+			health_problems: Vec::new(),
 		}
 	}
 }
 
 pub struct ExtractingPythonState {
-	pub extractor: ArchiveExtractor
+	pub extractor: ArchiveExtractor,
+	// The most recent progress report from the extractor
+	pub progress: Option<ExtractionProgress>,
+	// Files which failed verification after extraction, and are being restored
+	pub damaged_files: Vec<String>,
+	// Set when the user quits during extraction. The extraction has been cancelled, and the program
+	// exits once the extraction thread has stopped.
+	pub quit_when_stopped: bool,
 }
 
 impl ExtractingPythonState {
-	pub fn new() -> ExtractingPythonState {
+	pub fn new(force_extraction: bool, preflight_checks: bool, config: &InstallerConfig) -> ExtractingPythonState {
+		let mut extractor = ArchiveExtractor::new(
+			force_extraction,
+			config.payload_source.clone(),
+			config.delta_payload.clone(),
+		);
+		if preflight_checks {
+			extractor.enable_preflight_checks();
+		}
+
 		ExtractingPythonState {
-			extractor: ArchiveExtractor::new(),
+			extractor,
+			progress: None,
+			damaged_files: Vec::new(),
+			quit_when_stopped: false,
 		}
 	}
 }
 
+// What to do once the python installer has been stopped
+#[derive(Copy, Clone)]
+pub enum AfterPythonStopped {
+	Quit,
+	Restart(LaunchType),
+	RetryUsingTempDir,
+}
+
+// Finding the python interpreter to launch the installer with
+pub struct ResolvingPythonState {
+	pub resolver: InterpreterResolver,
+	pub launch_type: LaunchType,
+}
+
+// Checking the python runtime works, before the installer is first launched with it
+pub struct CheckingPythonState {
+	pub health_check: HealthCheck,
+	pub python: PythonInterpreter,
+	pub launch_type: LaunchType,
+}
+
+pub struct StoppingPythonState {
+	pub stopper: PythonStopper,
+	pub after_stopped: AfterPythonStopped,
+}
+
+// The python installer crashed, and will be restarted automatically
+pub struct WaitingToRestartState {
+	pub launch_type: LaunchType,
+	pub restart_time: Instant,
+}
+
 pub enum InstallerProgression {
 	PreExtractionChecks,
 	PreExtractionChecksFailed(String),
 	ExtractingPython(ExtractingPythonState),
 	UserNeedsCPPRedistributable,
+	ResolvingPython(ResolvingPythonState),
+	CheckingPython(CheckingPythonState),
 	InstallStarted(InstallStartedState),
+	StoppingPython(StoppingPythonState),
+	WaitingToRestart(WaitingToRestartState),
 	InstallFinished,
 	InstallFailed(InstallFailedState),
 	TempDirCleanupFailed(PathBuf),
@@ -160,6 +241,10 @@ struct UIState {
 	// Set True to force app focus next frame
 	focus_requested: bool,
 	exit_modal_requested: bool,
+	// Only lines containing this text (ignoring case) are shown in the installer output viewer
+	output_filter: String,
+	// Keep the installer output viewer scrolled to the newest line
+	output_auto_scroll: bool,
 }
 
 impl UIState {
@@ -176,6 +261,8 @@ impl UIState {
 			program_is_focused: true,
 			focus_requested: true,
 			exit_modal_requested: false,
+			output_filter: String::new(),
+			output_auto_scroll: true,
 		}
 	}
 }
@@ -189,6 +276,17 @@ struct InstallerGUI {
 	config: InstallerConfig,
 	retry_using_temp_dir: bool,
 	progress_percentage: usize,
+	// The output of the most recently launched python installer, shown in the installer output viewer
+	python_output: Option<Arc<Mutex<OutputHistory>>>,
+	// Restarts the python installer if it crashes, and keeps the history of launches shown to the user
+	supervisor: PythonSupervisor,
+	// The title most recently given to this window, which shows the install progress
+	window_title: Option<String>,
+	// Which launch types worked or failed previously, used to choose the launch type
+	launch_preferences: LaunchPreferences,
+	// Set once the python runtime has passed its health check (or the user chose to skip it), so it
+	// isn't checked again each time python is restarted
+	python_health_checked: bool,
 }
 
 impl InstallerGUI {
@@ -201,6 +299,8 @@ impl InstallerGUI {
 		// a server info file from a previous run of the installer
 		let _ = std::fs::rename(&constants.server_info_path, &constants.server_info_old);
 
+		let launch_preferences = LaunchPreferences::load(&constants);
+
 		InstallerGUI {
 			ui_state: UIState::new(window_size),
 			state: InstallerState {
@@ -209,6 +309,11 @@ impl InstallerGUI {
 			config: constants,
 			retry_using_temp_dir: false,
 			progress_percentage: 0,
+			python_output: None,
+			supervisor: PythonSupervisor::default(),
+			window_title: None,
+			launch_preferences,
+			python_health_checked: false,
 		}
 	}
 
@@ -227,6 +332,9 @@ impl InstallerGUI {
 		// Tools and information related to where the python part of the installer is extracted
 		self.display_extraction_info(ui);
 
+		// Show the output of the python installer, once it has been launched
+		self.display_python_output(ui);
+
 		ui.new_line();
 
 		// Show the advanced tools section
@@ -238,21 +346,48 @@ impl InstallerGUI {
 		if let InstallerProgression::ExtractingPython(extraction_state) =
 			&mut self.state.progression
 		{
-			match extraction_state.extractor.poll_status() {
+			let status = extraction_state.extractor.poll_status();
+
+			// The extraction was cancelled because the user quit - however it ended, the program can now exit
+			if extraction_state.quit_when_stopped {
+				match status {
+					ExtractionStatus::Finished
+					| ExtractionStatus::Cancelled
+					| ExtractionStatus::Error(_)
+					| ExtractionStatus::PreflightFailed(_) => {
+						self.state.progression = InstallerProgression::InstallFinished
+					}
+					_ => {}
+				}
+				return;
+			}
+
+			match status {
 				ExtractionStatus::NotStarted => extraction_state
 					.extractor
 					.start_extraction(&self.config.sub_folder),
 				ExtractionStatus::Started(Some(progress)) => {
-					self.progress_percentage = progress;
+					self.progress_percentage = progress.percentage;
+					extraction_state.progress = Some(progress);
+				}
+				ExtractionStatus::PreflightFailed(reason) => {
+					self.state.progression = InstallerProgression::PreExtractionChecksFailed(reason);
 				}
 				ExtractionStatus::Started(None) => {}
+				ExtractionStatus::Repairing(damaged_files) => {
+					extraction_state.damaged_files = damaged_files;
+				}
 				ExtractionStatus::Finished => {
+					self.progress_percentage = 100;
 					if windows_utilities::x86_cpp_redist_is_installed() {
 						self.start_install_default();
 					} else {
 						self.state.progression = InstallerProgression::UserNeedsCPPRedistributable;
 					};
 				}
+				ExtractionStatus::Cancelled => {
+					self.on_install_failed("Extraction was cancelled");
+				}
 				ExtractionStatus::Error(error) => {
 					self.on_install_failed(error);
 				}
@@ -274,6 +409,9 @@ impl InstallerGUI {
 				| InstallerProgression::PreExtractionChecksFailed(_)
 				| InstallerProgression::ExtractingPython(_)
 				| InstallerProgression::UserNeedsCPPRedistributable
+				| InstallerProgression::ResolvingPython(_)
+				| InstallerProgression::CheckingPython(_)
+				| InstallerProgression::WaitingToRestart(_)
 				| InstallerProgression::InstallFinished
 				| InstallerProgression::TempDirCleanupFailed(_) => self.quit(),
 				InstallerProgression::InstallStarted(_)
@@ -281,6 +419,11 @@ impl InstallerGUI {
 					self.ui_state.focus_requested = true;
 					self.ui_state.exit_modal_requested = true;
 				}
+				// Closing the window again while python is stopping kills it immediately
+				InstallerProgression::StoppingPython(ref mut stopping_state) => {
+					stopping_state.after_stopped = AfterPythonStopped::Quit;
+					stopping_state.stopper.stop_now();
+				}
 			}
 		}
 
@@ -314,9 +457,21 @@ impl InstallerGUI {
 	/// destructured state. On the next time the function is called, the match statement can then
 	/// correctly destructure/match the new state.
 	fn display_main_installer_flow(&mut self, ui: &Ui, proxy: &mut Option<EventLoopProxy<UserEvent>>) {
+		// Once the python installer reports its progress, it is shown instead of the launch progress
+		let install_status = self.install_status();
+		if let Some(percentage) = install_status.as_ref().and_then(|status| status.percentage) {
+			self.progress_percentage = percentage;
+		}
+		let install_phase = install_status.as_ref().and_then(|status| status.phase.as_deref());
+
 		let current_task_description = match &self.state.progression {
+			InstallerProgression::ExtractingPython(extraction_state) if extraction_state.quit_when_stopped => "Stopping...",
 			InstallerProgression::ExtractingPython(_) => "Extracting...",
-			InstallerProgression::InstallStarted(_) => "Launching Python",
+			InstallerProgression::ResolvingPython(_) => "Finding Python...",
+			InstallerProgression::CheckingPython(_) => "Checking Python...",
+			InstallerProgression::InstallStarted(_) => install_phase.unwrap_or("Launching Python"),
+			InstallerProgression::StoppingPython(_) => "Stopping...",
+			InstallerProgression::WaitingToRestart(_) => "Restarting...",
 			InstallerProgression::InstallFinished => "Finished...",
 			InstallerProgression::InstallFailed(_) => "Failed...",
 			InstallerProgression::PreExtractionChecks => "Pre-Extraction...",
@@ -345,20 +500,39 @@ Please download the installer to your Downloads or other known location, then ru
 					return;
 				}
 
+				// The extraction thread checks there is enough disk space, and the extraction folder is writeable
 				self.state.progression =
-					InstallerProgression::ExtractingPython(ExtractingPythonState::new());
+					InstallerProgression::ExtractingPython(ExtractingPythonState::new(false, true, &self.config));
 				return;
 			}
 			InstallerProgression::PreExtractionChecksFailed(reason) => {
 				ui.text_yellow(reason);
 				if ui.simple_button("Try to continue install anyway") {
 					self.state.progression =
-						InstallerProgression::ExtractingPython(ExtractingPythonState::new());
+						InstallerProgression::ExtractingPython(ExtractingPythonState::new(false, false, &self.config));
 					return;
 				}
 			}
-			InstallerProgression::ExtractingPython(_) => {
+			InstallerProgression::ExtractingPython(extraction_state) => {
+				if extraction_state.quit_when_stopped {
+					ui.text_yellow("Stopping extraction and removing the partially extracted files...");
+					return;
+				}
+
 				ui.text_yellow("Please wait for extraction to finish...");
+				if let Some(progress) = &extraction_state.progress {
+					ui.text(progress.summary());
+					ui.text(format!("Extracting: {}", progress.current_entry));
+				}
+				if !extraction_state.damaged_files.is_empty() {
+					ui.text_yellow(format!(
+						"Restoring {} files which are missing or modified (possibly removed by antivirus):",
+						extraction_state.damaged_files.len()
+					));
+					for damaged_file in &extraction_state.damaged_files {
+						ui.text(format!(" - {}", damaged_file));
+					}
+				}
 			}
 			InstallerProgression::UserNeedsCPPRedistributable => {
 				let download_failure_modal_name = "Download Failure (C++ Redistributable)";
@@ -420,6 +594,36 @@ Please download the installer to your Downloads or other known location, then ru
 					}
 				});
 			}
+			InstallerProgression::ResolvingPython(resolving_state) => {
+				match resolving_state.resolver.poll() {
+					Some(Ok(python)) => {
+						let launch_type = resolving_state.launch_type;
+						self.launch_python(python, launch_type);
+						return;
+					}
+					Some(Err(e)) => {
+						self.on_install_failed(e);
+						return;
+					}
+					None => ui.text_yellow("Finding a Python interpreter to run the installer..."),
+				}
+			}
+			InstallerProgression::CheckingPython(checking_state) => {
+				match checking_state.health_check.poll() {
+					Some(health_problems) if !health_problems.is_empty() => {
+						self.on_health_check_failed(health_problems);
+						return;
+					}
+					Some(_) => {
+						self.python_health_checked = true;
+						let python = checking_state.python.clone();
+						let launch_type = checking_state.launch_type;
+						self.launch_python(python, launch_type);
+						return;
+					}
+					None => ui.text_yellow("Checking the Python runtime... (this can take a while the first time, while antivirus software scans it)"),
+				}
+			}
 			InstallerProgression::InstallStarted(graphical_install) => {
 				if graphical_install.launch_type == LaunchType::WebView && !graphical_install.webview_launched
 				{
@@ -428,10 +632,14 @@ Please download the installer to your Downloads or other known location, then ru
 
 					if graphical_install.python_started_poll_count > max_poll_count
 					{
-						let default_url = String::from("http://127.0.0.1:8000/loading_screen.html");
-						println!("Error: Couldn't determine python launch url, will try default url {}", &default_url);
+						// The session token is only added after printing the url, so it isn't logged
+						let default_url = "http://127.0.0.1:8000/loading_screen.html";
+						println!("Error: Couldn't determine python launch url, will try default url {}", default_url);
 						graphical_install.webview_launched = true;
-						if let Err(e) = Self::launch_or_reuse_webview(default_url, self.config.webview_data_directory.as_path(), proxy) {
+						let default_url = session_token::add_to_url(default_url, &self.config.session_token);
+						graphical_install.installer_url = Some(default_url.clone());
+						if let Err(e) = Self::launch_or_reuse_webview(default_url, &self.config, proxy) {
+							self.launch_preferences.record_failed(LaunchType::WebView);
 							self.on_install_failed(e);
 							return;
 						}
@@ -445,10 +653,14 @@ Please download the installer to your Downloads or other known location, then ru
 							Ok(url) => {
 								graphical_install.webview_launched = true;
 								self.progress_percentage = 100;
-								if let Err(e) = Self::launch_or_reuse_webview(url, self.config.webview_data_directory.as_path(), proxy) {
+								graphical_install.installer_url = Some(url.clone());
+								if let Err(e) = Self::launch_or_reuse_webview(url, &self.config, proxy) {
+									self.launch_preferences.record_failed(LaunchType::WebView);
 									self.on_install_failed(e);
 									return;
 								}
+								graphical_install.launch_worked = true;
+								self.launch_preferences.record_working(LaunchType::WebView);
 							},
 							Err(_) => {
 								if graphical_install.python_started_poll_count == 1 {
@@ -464,6 +676,16 @@ Please download the installer to your Downloads or other known location, then ru
 					}
 				}
 
+				// Python opens the web browser once its web server has started (it writes the server info file then)
+				if graphical_install.launch_type == LaunchType::Browser
+					&& !graphical_install.launch_worked
+					&& self.config.server_info_path.exists()
+				{
+					graphical_install.launch_worked = true;
+					self.launch_preferences.record_working(LaunchType::Browser);
+					graphical_install.installer_url = installer_webview::get_url(&self.config).ok();
+				}
+
 				match graphical_install.launch_type {
 					LaunchType::TextMode => {
 						ui.text_yellow(
@@ -482,59 +704,145 @@ Please download the installer to your Downloads or other known location, then ru
 					}
 				}
 
+				if let Some(install_status) = &install_status {
+					if let Some(current_file) = &install_status.current_file {
+						ui.text(format!("Current file: {}", current_file));
+					}
+					for error in &install_status.errors {
+						ui.text_red(error);
+					}
+				}
+
+				Self::display_launch_history(ui, &self.supervisor);
+
 				ui.dummy([0.0, 20.0]);
 
 				if graphical_install.launch_type != LaunchType::TextMode {
 					ui.text_yellow("If you have problems:");
 					ui.text_yellow(" - try refreshing the webpage (CTRL + R)");
 					ui.text_yellow(" - try one of the below restart options");
+
+					if let Some(installer_url) = &graphical_install.installer_url {
+						ui.text_yellow(" - if the web page did not open, you can manually navigate to:");
+						ui.text_wrapped(installer_url);
+						// Only the system clipboard can be pasted into the browser (see support/mod.rs)
+						if cfg!(any(windows, target_os = "macos")) && ui.simple_button("Copy URL") {
+							ui.set_clipboard_text(installer_url);
+						}
+					}
 				}
 
 				if let Some(exit_status) =
 					graphical_install.python_monitor.try_wait().unwrap_or(None)
 				{
-					if exit_status.success() {
-						self.quit();
-					} else {
-						self.on_install_failed("Python Installer Failed - See Console Window");
+					// Python writes the server info file once its web server has started
+					let server_started = self.config.server_info_path.exists();
+					let exited_launch_type = graphical_install.launch_type;
+
+					match self.supervisor.exited(exit_status, server_started) {
+						SupervisorDecision::Quit => {
+							self.launch_preferences.record_working(exited_launch_type);
+							self.quit();
+						}
+						SupervisorDecision::Restart { launch_type, delay } => {
+							if launch_type != exited_launch_type {
+								self.launch_preferences.record_failed(exited_launch_type);
+							}
+
+							println!(
+								"Python Installer exited with {} - restarting in {:?} mode in {} seconds",
+								exit_status,
+								launch_type,
+								delay.as_secs()
+							);
+
+							// The webview is shown again once the restarted installer's server has started
+							if let Some(proxy) = proxy {
+								let _ = proxy.send_event(UserEvent::SetVisible(false));
+							}
+
+							self.state.progression = InstallerProgression::WaitingToRestart(WaitingToRestartState {
+								launch_type,
+								restart_time: Instant::now() + delay,
+							});
+						}
+						SupervisorDecision::GiveUp => {
+							self.launch_preferences.record_failed(exited_launch_type);
+							self.on_install_failed("Python Installer Failed - See Console Window");
+						}
 					};
 					return;
 				}
 
-				let mut kill_python_and_hide_webview = false;
-				let mut restart_install = None;
+				let mut after_stopped = None;
 
 				if ui.simple_button("Restart Installer in Web Browser")
 				{
-					kill_python_and_hide_webview = true;
-					restart_install = Some(LaunchType::Browser);
+					after_stopped = Some(AfterPythonStopped::Restart(LaunchType::Browser));
 				}
 
 				if ui.simple_button("Restart Installer in Text Mode")
 				{
-					kill_python_and_hide_webview = true;
-					restart_install = Some(LaunchType::TextMode);
+					after_stopped = Some(AfterPythonStopped::Restart(LaunchType::TextMode));
 				}
 
-				if !self.config.use_temp_dir && ui.button("Restart using temporary folder") {
-					kill_python_and_hide_webview = true;
-					self.retry_using_temp_dir = true;
+				if !self.config.use_temp_dir
+					&& self.config.temp_dir_policy != TempDirPolicy::Never
+					&& ui.button("Restart using temporary folder")
+				{
+					after_stopped = Some(AfterPythonStopped::RetryUsingTempDir);
 				}
 
-				if kill_python_and_hide_webview {
-					if let Err(e) = graphical_install.python_monitor.kill_wait() {
-						self.on_install_failed(e);
-						return;
+				if let Some(after_stopped) = after_stopped {
+					// Switching to another mode means this one didn't work for the user
+					if let AfterPythonStopped::Restart(launch_type) = after_stopped {
+						if launch_type != graphical_install.launch_type {
+							self.launch_preferences.record_failed(graphical_install.launch_type);
+						}
 					}
 
 					// We don't really care if this fails because it just hides the window
 					if let Some(proxy) = proxy {
 						let _ = proxy.send_event(UserEvent::SetVisible(false));
 					}
+
+					self.stop_python(after_stopped);
+					return;
 				}
+			}
+			InstallerProgression::StoppingPython(stopping_state) => {
+				match stopping_state.stopper.poll() {
+					Some(outcome) => {
+						let after_stopped = stopping_state.after_stopped;
+						self.on_python_stopped(outcome, after_stopped);
+						return;
+					}
+					None => {
+						ui.text_yellow(format!(
+							"Stopping the installer... (it will be forced to stop in {:.0} seconds)",
+							stopping_state.stopper.time_remaining().as_secs_f32().ceil()
+						));
+						if ui.simple_button("Force Stop Now") {
+							stopping_state.stopper.stop_now();
+						}
+					}
+				}
+			}
+			InstallerProgression::WaitingToRestart(restart_state) => {
+				let time_remaining = restart_state.restart_time.saturating_duration_since(Instant::now());
 
-				if let Some(restart_install) = restart_install {
-					self.start_install(restart_install);
+				ui.text_yellow(format!(
+					"The installer stopped unexpectedly. Restarting it in {:?} mode in {:.0} seconds...",
+					restart_state.launch_type,
+					time_remaining.as_secs_f32().ceil()
+				));
+				let restart_now = ui.simple_button("Restart Now");
+
+				Self::display_launch_history(ui, &self.supervisor);
+
+				if restart_now || time_remaining == Duration::from_secs(0) {
+					let launch_type = restart_state.launch_type;
+					self.start_install(launch_type);
 					return;
 				}
 			}
@@ -542,19 +850,35 @@ Please download the installer to your Downloads or other known location, then ru
 				ui.text_yellow(
 					"The install is finished. Cleaning up...please wait"
 				);
+				if let Some(last_launch) = self.supervisor.history().last() {
+					ui.text(last_launch.to_string());
+				}
 				self.ui_state.run = false;
 			}
 			InstallerProgression::InstallFailed(install_failed_state) => {
 				ui.text_red("The installation failed!");
 				ui.text_red(format!("[{}]", install_failed_state.failure_reason));
+				for problem in &install_failed_state.health_problems {
+					ui.new_line();
+					ui.text_red(problem.to_string());
+					ui.text_wrapped(problem.remediation());
+				}
 				if ui.simple_button("Open 07th-mod Support Page") {
 					let _ = open::that("https://07th-mod.com/wiki/Installer/support/");
 				}
 
+				Self::display_launch_history(ui, &self.supervisor);
+
 				if !install_failed_state.console_window_displayed {
 					windows_utilities::show_console_window();
 					install_failed_state.console_window_displayed = true;
 				}
+
+				// The health check could be wrong, so let the user try the installer anyway
+				if !install_failed_state.health_problems.is_empty() && ui.simple_button("Start Installer Anyway") {
+					self.python_health_checked = true;
+					self.start_install_default();
+				}
 			}
 			InstallerProgression::TempDirCleanupFailed(last_temp_dir) => {
 				ui.text_yellow("Warning: Failed to delete extraction folder.");
@@ -571,8 +895,8 @@ Please download the installer to your Downloads or other known location, then ru
 	fn display_advanced_tools(&mut self, ui: &Ui) {
 		// Advanced Tools Section
 		if CollapsingHeader::new("Advanced Tools").build(&ui) {
-			// Button which shows the python installer logs folder.
-			// NOTE: the output of this launcher is currently not logged.
+			// Button which shows the python installer logs folder. The output of the python
+			// installer is also logged there by the launcher (see ProcessRunner).
 			if ui.button("Show Installer Logs") {
 				let _ = windows_utilities::system_open(&self.config.logs_folder);
 			}
@@ -583,13 +907,25 @@ Please download the installer to your Downloads or other known location, then ru
 				| InstallerProgression::InstallFailed(_) => {
 					ui.same_line();
 					if ui.simple_button("Force Re-Extraction") {
+						self.python_health_checked = false;
 						self.state.progression =
-							InstallerProgression::ExtractingPython(ExtractingPythonState::new());
+							InstallerProgression::ExtractingPython(ExtractingPythonState::new(true, false, &self.config));
 					}
 				}
 				_ => {}
 			}
 
+			// Forget which launch modes worked or failed, so the default mode is tried first again
+			if ui.simple_button("Reset Preferred Mode") {
+				self.launch_preferences.reset();
+			}
+			ui.same_line();
+			if self.config.launch_type_configured {
+				ui.text(format!("The launch mode from the loader settings is used ({:?})", self.config.default_launch_type));
+			} else {
+				ui.text(self.launch_preferences.summary().unwrap_or_else(|| String::from("No preferred mode remembered")));
+			}
+
 			// Show windows' 'cmd' console
 			if ui.checkbox(
 				"Show Debug Console",
@@ -612,6 +948,114 @@ Please download the installer to your Downloads or other known location, then ru
 		}
 	}
 
+	// Shows the most recent output of the python installer, so users can see what the installer is
+	// doing (or where it got stuck) without enabling the debug console
+	fn display_python_output(&mut self, ui: &Ui) {
+		let python_output = match &self.python_output {
+			Some(python_output) => Arc::clone(python_output),
+			None => return,
+		};
+
+		if !CollapsingHeader::new("Installer Output").build(&ui) {
+			return;
+		}
+
+		ui.input_text("Filter", &mut self.ui_state.output_filter).build();
+		ui.same_line();
+		ui.checkbox("Auto-scroll", &mut self.ui_state.output_auto_scroll);
+		ui.same_line();
+		let copy_requested = ui.simple_button("Copy to Clipboard");
+
+		let filter = self.ui_state.output_filter.to_lowercase();
+		let history = match python_output.lock() {
+			Ok(history) => history,
+			Err(poisoned) => poisoned.into_inner(),
+		};
+		let visible_lines: Vec<&str> = history
+			.lines()
+			.map(|line| line.as_str())
+			.filter(|line| filter.is_empty() || line.to_lowercase().contains(&filter))
+			.collect();
+
+		// Copies only the lines matching the filter, as shown
+		if copy_requested {
+			ui.set_clipboard_text(visible_lines.join("\n"));
+		}
+
+		let auto_scroll = self.ui_state.output_auto_scroll;
+		ChildWindow::new("Installer Output Lines")
+			.size([0.0, 300.0])
+			.border(true)
+			.horizontal_scrollbar(true)
+			.build(ui, || {
+				for line in &visible_lines {
+					let lowercase_line = line.to_lowercase();
+					if ["error", "exception", "traceback", "failed"]
+						.iter()
+						.any(|keyword| lowercase_line.contains(keyword))
+					{
+						ui.text_red(line);
+					} else if lowercase_line.contains("warn") {
+						ui.text_yellow(line);
+					} else {
+						ui.text(line);
+					}
+				}
+
+				// Don't scroll down if the user has scrolled up to read earlier output
+				if auto_scroll && ui.scroll_y() >= ui.scroll_max_y() {
+					ui.set_scroll_here_y_with_ratio(1.0);
+				}
+			});
+	}
+
+	// The progress reported by the running python installer, or None if it hasn't reported any yet
+	fn install_status(&self) -> Option<InstallStatus> {
+		match &self.state.progression {
+			InstallerProgression::InstallStarted(install_started_state) => {
+				Some(install_started_state.python_monitor.install_status())
+					.filter(|install_status| install_status.phase.is_some() || !install_status.errors.is_empty())
+			}
+			_ => None,
+		}
+	}
+
+	// Shows the install progress in the title of this window and the webview window, if it has changed
+	fn update_window_titles(&mut self, proxy: &mut Option<EventLoopProxy<UserEvent>>) -> Option<String> {
+		let progress = self.install_status().and_then(|install_status| {
+			let phase = install_status.phase?;
+			Some(match install_status.percentage {
+				Some(percentage) => format!("{}% {} - ", percentage, phase),
+				None => format!("{} - ", phase),
+			})
+		}).unwrap_or_default();
+
+		let window_title = format!("{}{}", progress, launcher_window_title());
+		if self.window_title.as_ref() == Some(&window_title) {
+			return None;
+		}
+
+		if let Some(proxy) = proxy {
+			let _ = proxy.send_event(UserEvent::SetTitle(format!("{}{}", progress, installer_webview::WINDOW_TITLE)));
+		}
+
+		self.window_title = Some(window_title.clone());
+		Some(window_title)
+	}
+
+	// Shows each launch of the python installer and how it ended, once it has been launched more than once
+	fn display_launch_history(ui: &Ui, supervisor: &PythonSupervisor) {
+		let history = supervisor.history();
+		if history.len() < 2 {
+			return;
+		}
+
+		ui.text("Installer launch history:");
+		for (attempt_number, attempt) in history.iter().enumerate() {
+			ui.text(format!(" {}. {}", attempt_number + 1, attempt));
+		}
+	}
+
 	fn display_extraction_info(&mut self, ui: &Ui) {
 		match self.state.progression {
 			InstallerProgression::PreExtractionChecks
@@ -632,19 +1076,51 @@ Please download the installer to your Downloads or other known location, then ru
 		ui.text_wrapped(&self.config.sub_folder_display);
 	}
 
+	// Start the installer with the launch type from the loader settings if one was chosen there,
+	// otherwise with the one which worked last time
 	fn start_install_default(&mut self)
 	{
-		self.start_install(LaunchType::WebView)
+		let launch_type = if self.config.launch_type_configured {
+			self.config.default_launch_type
+		} else {
+			self.launch_preferences.preferred_launch_type(self.config.default_launch_type)
+		};
+
+		self.start_install(launch_type)
 	}
 
-	// Start either the graphical or console install. Advances the installer progression to "InstallStarted"
+	// Start either the graphical or console install. The python interpreter is found on another thread
+	// first (see the ResolvingPython progression), then launch_python() is called.
 	fn start_install(&mut self, launch_type: LaunchType) {
 		if launch_type == LaunchType::TextMode {
 			windows_utilities::show_console_window();
 		}
 
+		// Avoid reading the server info of a previous python installer which didn't exit cleanly
+		let _ = std::fs::rename(&self.config.server_info_path, &self.config.server_info_old);
+
+		self.state.progression = InstallerProgression::ResolvingPython(ResolvingPythonState {
+			resolver: InterpreterResolver::start(&self.config),
+			launch_type,
+		});
+	}
+
+	// Launch the python installer with the given interpreter. Advances the installer progression to "InstallStarted"
+	fn launch_python(&mut self, python: PythonInterpreter, launch_type: LaunchType) {
+		// Check the python runtime before it is first launched, as antivirus software may have removed parts of it.
+		// The check runs on another thread (see the CheckingPython progression), then this is called again.
+		if !self.python_health_checked {
+			self.state.progression = InstallerProgression::CheckingPython(CheckingPythonState {
+				health_check: HealthCheck::start(&python, &self.config),
+				python,
+				launch_type,
+			});
+			return;
+		}
+
 		let python_monitor = python_launcher::launch_python_script(
 			&self.config,
+			&python,
 			launch_type,
 		);
 
@@ -655,6 +1131,8 @@ Please download the installer to your Downloads or other known location, then ru
 				return;
 			},
 		};
+		self.python_output = Some(python_monitor.output_history());
+		self.supervisor.launched(launch_type);
 
 		self.state.progression = InstallerProgression::InstallStarted(
 			InstallStartedState::new(
@@ -665,14 +1143,69 @@ Please download the installer to your Downloads or other known location, then ru
 		);
 	}
 
+	// Ask the python installer to shut down, and continue with 'after_stopped' once it has stopped
+	// (see the StoppingPython progression). Python is killed if it doesn't exit in time.
+	fn stop_python(&mut self, after_stopped: AfterPythonStopped) {
+		let progression = std::mem::replace(&mut self.state.progression, InstallerProgression::InstallFinished);
+
+		self.state.progression = match progression {
+			InstallerProgression::InstallStarted(install_started_state) => {
+				InstallerProgression::StoppingPython(StoppingPythonState {
+					stopper: PythonStopper::start(
+						install_started_state.python_monitor,
+						install_started_state.launch_type,
+						&self.config,
+					),
+					after_stopped,
+				})
+			}
+			progression => progression,
+		};
+	}
+
+	fn on_python_stopped(&mut self, outcome: Result<StopOutcome, Box<dyn Error>>, after_stopped: AfterPythonStopped) {
+		match outcome {
+			Ok(outcome) => {
+				println!("{}", outcome);
+				self.supervisor.stopped(outcome.to_string());
+			}
+			// Even if killing fails, still quit. If python is still running, the user can close it using task manager.
+			Err(e) => {
+				self.supervisor.stopped(format!("Failed to stop the python installer: {}", e));
+				if let AfterPythonStopped::Quit = after_stopped {
+					println!("Failed to stop the python installer: {}", e);
+				} else {
+					self.on_install_failed(format!("Failed to stop the python installer: {}", e));
+					return;
+				}
+			}
+		}
+
+		match after_stopped {
+			AfterPythonStopped::Quit => self.state.progression = InstallerProgression::InstallFinished,
+			AfterPythonStopped::Restart(launch_type) => self.start_install(launch_type),
+			// This window is replaced with one using a temporary folder at the end of the frame
+			AfterPythonStopped::RetryUsingTempDir => self.retry_using_temp_dir = true,
+		}
+	}
+
 	// Close the UI and the installer thread
 	fn quit(&mut self) {
-		// Attempt to kill the python process, if the installer has already been started.
-		// Even if killing fails, attempt to wait on the process.
-		// This will make it obvious if something went wrong as the UI will (probably) hang,
-		// so the user can close the program using task manager.
-		if let InstallerProgression::InstallStarted(settings) = &mut self.state.progression {
-			let _ = settings.python_monitor.kill_wait();
+		// If the installer has already been started, python is stopped first - this is finished once it has stopped.
+		if let InstallerProgression::InstallStarted(_) = &self.state.progression {
+			self.stop_python(AfterPythonStopped::Quit);
+			return;
+		}
+
+		// Stop any extraction in progress, so it doesn't keep writing files after the program exits.
+		// The program exits once the partially extracted files have been removed (see extraction_update()).
+		// Quitting again while the extraction is stopping exits immediately.
+		if let InstallerProgression::ExtractingPython(extraction_state) = &mut self.state.progression {
+			if !extraction_state.quit_when_stopped {
+				extraction_state.quit_when_stopped = true;
+				extraction_state.extractor.cancel();
+				return;
+			}
 		}
 
 		self.state.progression = InstallerProgression::InstallFinished;
@@ -687,13 +1220,13 @@ Please download the installer to your Downloads or other known location, then ru
 		!self.ui_state.program_is_focused && self.ui_state.mouse_activity_timer.expired()
 	}
 
-	fn launch_or_reuse_webview<P: AsRef<Path>>(url: String, data_directory: P, proxy: &mut Option<EventLoopProxy<UserEvent>>) -> Result<()> {
+	fn launch_or_reuse_webview(url: String, config: &InstallerConfig, proxy: &mut Option<EventLoopProxy<UserEvent>>) -> Result<()> {
 		match proxy {
 			Some(proxy) => {
 				proxy.send_event(UserEvent::SetVisible(true))?;
 				proxy.send_event(UserEvent::NavigateToURL(url))?;
 			}
-			None => *proxy = Some(installer_webview::launch(url, data_directory)?),
+			None => *proxy = Some(installer_webview::launch(url, &config.webview_data_directory, config.session_token.clone())?),
 		};
 
 		Ok(())
@@ -705,6 +1238,15 @@ Please download the installer to your Downloads or other known location, then ru
 		eprintln!("ERROR: {}", &reason);
 		self.state.progression = InstallerProgression::InstallFailed(InstallFailedState::new(reason));
 	}
+
+	fn on_health_check_failed(&mut self, health_problems: Vec<HealthProblem>)
+	{
+		let reason = String::from("The Python runtime failed its health check");
+		eprintln!("ERROR: {}", &reason);
+		let mut install_failed_state = InstallFailedState::new(reason);
+		install_failed_state.health_problems = health_problems;
+		self.state.progression = InstallerProgression::InstallFailed(install_failed_state);
+	}
 }
 
 impl ApplicationGUI for InstallerGUI {
@@ -740,6 +1282,7 @@ impl ApplicationGUI for InstallerGUI {
 			run: self.ui_state.run,
 			force_show_window,
 			retry_using_tempdir: self.retry_using_temp_dir,
+			window_title: self.update_window_titles(proxy),
 		}
 	}
 
@@ -755,12 +1298,16 @@ impl ApplicationGUI for InstallerGUI {
 
 struct InstallerBuilder {
 	temp_dir: Option<TempDir>,
-	use_temp_dir: bool
+	use_temp_dir: bool,
+	payload_source: PayloadSource,
+	delta_payload: Option<PathBuf>,
+	loader_config: LoaderConfig,
 }
 
 impl InstallerBuilder {
-	fn new() -> InstallerBuilder {
-		InstallerBuilder { temp_dir: None, use_temp_dir: false }
+	fn new(payload_source: PayloadSource, delta_payload: Option<PathBuf>, loader_config: LoaderConfig) -> InstallerBuilder {
+		let use_temp_dir = loader_config.temp_dir.value == TempDirPolicy::Always;
+		InstallerBuilder { temp_dir: None, use_temp_dir, payload_source, delta_payload, loader_config }
 	}
 }
 
@@ -770,13 +1317,13 @@ impl AppBuilder<InstallerGUI> for InstallerBuilder {
 	}
 
 	fn window_name(&self) -> String {
-		format!("07th-Mod Installer Launcher [{}]", version::travis_tag())
+		launcher_window_title()
 	}
 
 	fn build(&mut self) -> InstallerGUI {
 		// Note: Temp dir will attempt to delete itself once it goes out of scope, so make
 		// sure to keep it in scope until you are finished with it
-		let mut root = PathBuf::from("07th-mod_installer");
+		let mut root = self.loader_config.extraction_root.value.clone();
 
 
 		if self.use_temp_dir {
@@ -797,7 +1344,7 @@ impl AppBuilder<InstallerGUI> for InstallerBuilder {
 		// if self.retry {
 		InstallerGUI::init(
 			[window_size[0] as f32, window_size[1] as f32],
-			InstallerConfig::new(&root, self.use_temp_dir),
+			InstallerConfig::new(&root, self.use_temp_dir, self.payload_source.clone(), self.delta_payload.clone(), &self.loader_config),
 			InstallerProgression::PreExtractionChecks,
 		)
 	}
@@ -807,7 +1354,7 @@ impl AppBuilder<InstallerGUI> for InstallerBuilder {
 		// if self.retry {
 		InstallerGUI::init(
 			[window_size[0] as f32, window_size[1] as f32],
-			InstallerConfig::new(&PathBuf::from("07th-mod_installer"), false),
+			InstallerConfig::new(&self.loader_config.extraction_root.value, false, self.payload_source.clone(), self.delta_payload.clone(), &self.loader_config),
 			InstallerProgression::TempDirCleanupFailed(failed_cleanup_path),
 		)
 	}
@@ -840,8 +1387,8 @@ impl AppBuilder<InstallerGUI> for InstallerBuilder {
     }
 }
 
-pub fn ui_loop() {
-	let builder = InstallerBuilder::new();
+pub fn ui_loop(payload_source: PayloadSource, delta_payload: Option<PathBuf>, loader_config: LoaderConfig) {
+	let builder = InstallerBuilder::new(payload_source, delta_payload, loader_config);
 	let system = support::init(&builder.window_name(), builder.window_size());
 	system.main_loop(builder);
 }
@@ -1,6 +1,8 @@
 extern crate open;
+#[cfg(windows)]
 extern crate winapi;
 
+#[cfg(windows)]
 use self::winapi::um::winnt::HANDLE;
 use path_clean::PathClean;
 use regex::Regex;
@@ -9,12 +11,14 @@ use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+#[cfg(windows)]
 use std::ptr;
 use std::{env, process};
 
 // https://stackoverflow.com/questions/29763647/how-to-make-a-program-that-does-not-display-the-console-window
 // https://msdn.microsoft.com/en-us/library/windows/desktop/ms633548%28v=vs.85%29.aspx
 // cmd_show should be one of SW_HIDE, SW_SHOW etc. from winapi::um::winuser
+#[cfg(windows)]
 fn set_console_window_display_mode(cmd_show: winapi::ctypes::c_int) {
 	let window = unsafe { winapi::um::wincon::GetConsoleWindow() };
 	if window != ptr::null_mut() {
@@ -24,14 +28,30 @@ fn set_console_window_display_mode(cmd_show: winapi::ctypes::c_int) {
 	}
 }
 
+#[cfg(windows)]
 pub fn hide_console_window() {
 	set_console_window_display_mode(winapi::um::winuser::SW_HIDE);
 }
 
+#[cfg(windows)]
 pub fn show_console_window() {
 	set_console_window_display_mode(winapi::um::winuser::SW_SHOW);
 }
 
+// Elsewhere the loader is run from a terminal (or without one), which it can't show or hide
+#[cfg(not(windows))]
+pub fn hide_console_window() {}
+
+#[cfg(not(windows))]
+pub fn show_console_window() {}
+
+// Returns true if this process has a console window, and it is currently shown
+#[cfg(windows)]
+pub fn console_window_is_visible() -> bool {
+	let window = unsafe { winapi::um::wincon::GetConsoleWindow() };
+	window != ptr::null_mut() && unsafe { winapi::um::winuser::IsWindowVisible(window) } != 0
+}
+
 /***
 Tries to open a given path using the system 'open' function
 The path can be a on-disk folder or a URL
@@ -53,7 +73,14 @@ where
 		&path, normalized_path
 	);
 
-	Ok(Command::new("explorer").arg(normalized_path).spawn()?)
+	#[cfg(windows)]
+	let open_command = "explorer";
+	#[cfg(target_os = "macos")]
+	let open_command = "open";
+	#[cfg(not(any(windows, target_os = "macos")))]
+	let open_command = "xdg-open";
+
+	Ok(Command::new(open_command).arg(normalized_path).spawn()?)
 }
 
 /// Gets normalized path of an EXISTING file. This function will fail if the path/file doesn't exist
@@ -102,6 +129,7 @@ pub fn absolute_path(path: impl AsRef<Path>) -> Result<PathBuf, Box<dyn Error>>
 /// It checks these folders for "ucrtbase.dll" and "vcruntime140.dll" (you probably only need to check for "ucrtbase.dll" though)
 ///
 /// The Visual C++ Redist is required to run python, see: https://docs.python.org/3/using/windows.html#the-embeddable-package
+#[cfg(windows)]
 pub fn x86_cpp_redist_is_installed() -> bool {
 	// Need to handle if windows is not on the C: drive - use environment variable to determine location
 	let windows_folder = std::env::var("windir").unwrap_or(String::from(r"C:\Windows"));
@@ -126,6 +154,12 @@ pub fn x86_cpp_redist_is_installed() -> bool {
 	return true;
 }
 
+/// Only the Windows python runtime needs the Visual C++ Redist
+#[cfg(not(windows))]
+pub fn x86_cpp_redist_is_installed() -> bool {
+	true
+}
+
 pub fn cpp_redist_download_in_browser() -> std::io::Result<std::process::ExitStatus> {
 	open::that("https://aka.ms/vs/16/release/vc_redist.x86.exe")
 }
@@ -136,11 +170,19 @@ pub fn cpp_redist_open_website() -> std::io::Result<std::process::ExitStatus> {
 	)
 }
 
+#[cfg(windows)]
 pub fn installer_is_in_temp_folder() -> Result<bool, Box<dyn Error>> {
 	let app_data = format!("{}\\AppData", std::env::var("USERPROFILE")?);
 	Ok(std::env::current_exe()?.starts_with(app_data.as_str()))
 }
 
+// Only Windows extracts downloaded archives to a temporary folder when they are opened
+#[cfg(not(windows))]
+pub fn installer_is_in_temp_folder() -> Result<bool, Box<dyn Error>> {
+	Ok(false)
+}
+
+#[cfg(windows)]
 fn try_set_kill_on_job_close(
 	job: &mut win32job::Job,
 	handle: Option<HANDLE>,
@@ -160,6 +202,7 @@ fn try_set_kill_on_job_close(
 // Creates a new job object, with the process with the given handle attached to it.
 // When the job goes out of scope, the process and child processes will be closed too
 // Unlike new_job_kill_on_job_close(), the current process (this program) is unaffected
+#[cfg(windows)]
 pub fn new_job_kill_on_job_close_id(
 	handle: HANDLE,
 ) -> (Option<win32job::Job>, Result<(), Box<dyn Error>>) {
@@ -174,10 +217,12 @@ pub fn new_job_kill_on_job_close_id(
 // This includes the python process, and processes called from python like aria2c and 7z
 // Also see: https://stackoverflow.com/questions/23434842/python-how-to-kill-child-processes-when-parent-dies/23587108
 // On Windows 7, creating the job  seems to fail - see workaround at end of main()
+#[cfg(windows)]
 pub fn new_job_kill_on_job_close() -> (Option<win32job::Job>, Result<(), Box<dyn Error>>) {
 	new_job_kill_on_job_close_inner(None)
 }
 
+#[cfg(windows)]
 pub fn new_job_kill_on_job_close_inner(
 	handle: Option<HANDLE>,
 ) -> (Option<win32job::Job>, Result<(), Box<dyn Error>>) {
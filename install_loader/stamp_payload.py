@@ -0,0 +1,61 @@
+"""
+Appends installer data (a .tar.xz, .tar.zst or chunked .chunks archive and its .json manifest) to the end of a file, followed by
+a footer which the loader uses to find and verify it. See payload.rs for the footer format.
+
+Usage:
+	Append the installer data to an already compiled loader:
+		python stamp_payload.py seventh_mod_loader.exe install_data.tar.xz install_data_manifest.json
+
+	Create a standalone payload file, to be used with 'seventh_mod_loader.exe --payload install_data.payload':
+		python stamp_payload.py install_data.payload install_data.tar.xz install_data_manifest.json --new
+"""
+import argparse
+import hashlib
+import os
+import struct
+
+FOOTER_MAGIC = b'7THPAYLD'
+FOOTER_FORMAT = '<8sQQQ32s'
+
+
+def stamp_payload(output_path, archive_path, manifest_path, create_new_file):
+	with open(archive_path, 'rb') as archive_file:
+		archive = archive_file.read()
+
+	with open(manifest_path, 'rb') as manifest_file:
+		manifest = manifest_file.read()
+
+	with open(output_path, 'wb' if create_new_file else 'r+b') as output_file:
+		output_file.seek(0, os.SEEK_END)
+
+		# Refuse to stamp a file twice, as only the last payload would be used
+		if output_file.tell() >= struct.calcsize(FOOTER_FORMAT):
+			output_file.seek(-struct.calcsize(FOOTER_FORMAT), os.SEEK_END)
+			if output_file.read(len(FOOTER_MAGIC)) == FOOTER_MAGIC:
+				raise Exception(f"{output_path} already has installer data appended - please use a fresh copy")
+			output_file.seek(0, os.SEEK_END)
+
+		archive_offset = output_file.tell()
+		output_file.write(archive)
+		output_file.write(manifest)
+		output_file.write(struct.pack(
+			FOOTER_FORMAT,
+			FOOTER_MAGIC,
+			archive_offset,
+			len(archive),
+			len(manifest),
+			hashlib.sha256(archive).digest(),
+		))
+
+	print(f"Stamped {len(archive)} byte archive and {len(manifest)} byte manifest onto {output_path}")
+
+
+if __name__ == '__main__':
+	parser = argparse.ArgumentParser(description='Append installer data to the 07th-Mod installer loader, or create a standalone payload file')
+	parser.add_argument('output', help='The loader .exe to append to, or the payload file to create if --new is given')
+	parser.add_argument('archive', help='The installer data archive (.tar.xz, .tar.zst or .chunks)')
+	parser.add_argument('manifest', help='The manifest .json generated alongside the archive by travis_build_script.py')
+	parser.add_argument('--new', action='store_true', help='Create a new standalone payload file instead of appending to an existing file')
+	args = parser.parse_args()
+
+	stamp_payload(args.output, args.archive, args.manifest, args.new)
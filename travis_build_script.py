@@ -11,6 +11,7 @@ import datetime
 import platform
 import tempfile
 import hashlib
+import unicodedata
 
 from io import BytesIO
 from zipfile import ZipFile
@@ -81,6 +82,85 @@ def tar_gz(input_path, output_filename: str):
 	call(["7z", "a", output_filename, tempFileName])
 	os.remove(tempFileName)
 
+def write_payload_manifest(payload_folder, manifest_path, version):
+	"""
+	Writes a .json manifest containing the size and SHA-256 of each file in payload_folder.
+	Paths are relative to payload_folder, and always use '/' as the separator.
+	The version is written to the loader's extraction lock, so it should change whenever the payload changes.
+	"""
+	files = {}
+	for root, _dirs, filenames in os.walk(payload_folder):
+		for filename in filenames:
+			full_path = os.path.join(root, filename)
+			# The loader NFC normalizes archive paths, so the manifest paths must be normalized the same way
+			relative_path = unicodedata.normalize('NFC', os.path.relpath(full_path, payload_folder).replace(os.sep, '/'))
+			with open(full_path, 'rb') as file:
+				sha256 = hashlib.sha256(file.read()).hexdigest()
+			files[relative_path] = {
+				'size': os.path.getsize(full_path),
+				'sha256': sha256,
+			}
+
+	with open(manifest_path, 'w', encoding='utf-8') as manifest_file:
+		json.dump({'version': version, 'files': files}, manifest_file, indent='\t', sort_keys=True)
+
+	print(f"Wrote manifest of {len(files)} files to {manifest_path}")
+
+def compress_archive(tar_bytes, compression):
+	"""
+	Compresses an uncompressed .tar archive with either 'xz' or 'zstd', returning the compressed bytes.
+	zstd compression uses the zstd command line program.
+	"""
+	if compression == 'xz':
+		import lzma
+		return lzma.compress(tar_bytes, preset=9 | lzma.PRESET_EXTREME)
+
+	# The loader's zstd decoder accepts windows up to 2^27 bytes, so don't use a larger --long window
+	return subprocess.run(['zstd', '-19', '--long=27', '-T0', '-c', '-'], input=tar_bytes, stdout=subprocess.PIPE, check=True).stdout
+
+def write_chunked_archive(payload_folder, output_path, chunk_count, compression):
+	"""
+	Splits the files in payload_folder into chunk_count independently compressed .tar.xz or .tar.zst archives,
+	so the loader can decompress them in parallel. See payload.rs in the loader for the layout of the output file.
+	Paths are relative to payload_folder, as with the single .tar.xz/.tar.zst archive.
+	"""
+	import struct
+	import tarfile
+
+	relative_paths = []
+	for root, _dirs, filenames in os.walk(payload_folder):
+		for filename in filenames:
+			relative_paths.append(os.path.relpath(os.path.join(root, filename), payload_folder))
+
+	# Give each chunk a similar uncompressed size, by adding the largest files first to the smallest chunk
+	chunk_files = [[] for _ in range(chunk_count)]
+	chunk_sizes = [0] * chunk_count
+	for relative_path in sorted(relative_paths, key=lambda path: os.path.getsize(os.path.join(payload_folder, path)), reverse=True):
+		smallest_chunk = chunk_sizes.index(min(chunk_sizes))
+		chunk_files[smallest_chunk].append(relative_path)
+		chunk_sizes[smallest_chunk] += os.path.getsize(os.path.join(payload_folder, relative_path))
+
+	compressed_chunks = []
+	for files in chunk_files:
+		if not files:
+			continue
+
+		chunk_buffer = BytesIO()
+		with tarfile.open(fileobj=chunk_buffer, mode='w') as chunk_tar:
+			for relative_path in sorted(files):
+				chunk_tar.add(os.path.join(payload_folder, relative_path), arcname=relative_path.replace(os.sep, '/'), recursive=False)
+		compressed_chunks.append(compress_archive(chunk_buffer.getvalue(), compression))
+
+	with open(output_path, 'wb') as output_file:
+		output_file.write(b'7THCHUNK')
+		output_file.write(struct.pack('<I', len(compressed_chunks)))
+		for chunk in compressed_chunks:
+			output_file.write(struct.pack('<Q', len(chunk)))
+		for chunk in compressed_chunks:
+			output_file.write(chunk)
+
+	print(f"Wrote {len(relative_paths)} files in {len(compressed_chunks)} chunks to {output_path}")
+
 def pre_build_validation():
 	import installConfiguration
 	import common
@@ -262,19 +342,47 @@ if not BUILD_LINUX_MAC:
 	loader_src_folder = 'install_loader/src'
 	tar_path = os.path.join(loader_src_folder, 'install_data.tar')
 	xz_path = tar_path + '.xz'
+	zst_path = tar_path + '.zst'
+	# A chunked archive isn't a .tar.xz/.tar.zst file, so it gets its own name (see payload.rs in the loader)
+	chunks_path = os.path.join(loader_src_folder, 'install_data.chunks')
+	manifest_path = os.path.join(loader_src_folder, 'install_data_manifest.json')
 	try_remove_tree(tar_path)
 	try_remove_tree(xz_path)
-	call(['7z', 'a', '-aoa', tar_path, f'./{bootstrap_copy_folder}/higu_win_installer_32/install_data/*'])
-	call([
-			'7z',
-			'a',
-			'-mx=9',     # max compression level
-			'-md=256m',  # 256m dictionary size (memory used for compression is much higher than this)
-			'-mmt=3',    # use 3 threads (using > 3 threads results in increased archive size)
-			'-aoa',
-			xz_path,
-			tar_path
-		])
+	try_remove_tree(zst_path)
+	try_remove_tree(chunks_path)
+	try_remove_tree(manifest_path)
+
+	# xz is used unless INSTALLER_PAYLOAD_COMPRESSION is set to 'zstd'. zstd archives extract much faster than
+	# xz archives, but are slightly larger (see install_loader/benches).
+	# The loader is built with the matching decoder (see build_rust_loader() below).
+	payload_compression = os.environ.get('INSTALLER_PAYLOAD_COMPRESSION', 'xz')
+	if payload_compression not in ['xz', 'zstd']:
+		raise Exception(f"INSTALLER_PAYLOAD_COMPRESSION must be 'xz' or 'zstd', not '{payload_compression}'")
+	archive_path = zst_path if payload_compression == 'zstd' else xz_path
+
+	# Record the size and SHA-256 of every file in the archive, so the loader can verify the extracted files
+	write_payload_manifest(f'./{bootstrap_copy_folder}/higu_win_installer_32/install_data', manifest_path, GIT_TAG)
+
+	# Optionally split the archive into chunks which the loader can decompress in parallel.
+	# This extracts faster on multi-core machines, but the archive is slightly larger.
+	payload_chunks = int(os.environ.get('INSTALLER_PAYLOAD_CHUNKS', '1'))
+	if payload_chunks > 1:
+		write_chunked_archive(f'./{bootstrap_copy_folder}/higu_win_installer_32/install_data', chunks_path, payload_chunks, payload_compression)
+	elif payload_compression == 'zstd':
+		call(['7z', 'a', '-aoa', tar_path, f'./{bootstrap_copy_folder}/higu_win_installer_32/install_data/*'])
+		call(['zstd', '-19', '--long=27', '-T0', '-f', tar_path, '-o', zst_path])
+	else:
+		call(['7z', 'a', '-aoa', tar_path, f'./{bootstrap_copy_folder}/higu_win_installer_32/install_data/*'])
+		call([
+				'7z',
+				'a',
+				'-mx=9',     # max compression level
+				'-md=256m',  # 256m dictionary size (memory used for compression is much higher than this)
+				'-mmt=3',    # use 3 threads (using > 3 threads results in increased archive size)
+				'-aoa',
+				xz_path,
+				tar_path
+			])
 
 	# Compile the rust loader
 	# If not using a manifest file, DO NOT put the words "install", "patch", "update", etc. in the filename,
@@ -283,7 +391,10 @@ if not BUILD_LINUX_MAC:
 	# If using msvc linker, embed a manifest/change msvc linker options, as per
 	# https://www.reddit.com/r/rust/comments/8tooi0/hey_rustaceans_got_an_easy_question_ask_here/e1lk7tw?utm_source=share&utm_medium=web2x
 	def build_rust_loader(loader_exe_name, require_administrator):
-		args = ['cargo', 'rustc', '--release', '--', '-C', 'link-arg=/MANIFEST:embed']
+		args = ['cargo', 'rustc', '--release']
+		if payload_compression == 'zstd':
+			args += ['--features', 'zstd']
+		args += ['--', '-C', 'link-arg=/MANIFEST:embed']
 		if require_administrator:
 			args += ['-C', 'link-arg=/MANIFESTUAC:level=\'highestAvailable\'']
 